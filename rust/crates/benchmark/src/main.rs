@@ -1,7 +1,4 @@
-use game_core::bt::leafs::*;
-use game_core::bt::nodes::*;
 use game_core::bt::result::BTResult;
-use game_core::bt::wait::Wait;
 use game_core::bt::*;
 use game_core::character::snapshot::CharacterSnapshot;
 use game_core::NPCCharacterLogic;
@@ -15,15 +12,15 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
 
+mod script_tick;
+
 fn build_tree(route: Vec<Vector2D>) -> Arc<BehaviourTree> {
-    Arc::new(BehaviourTree::new(Box::new(Selector::new(vec![
-        Box::new(Sequence::new(vec![
-            Box::new(NextWaypoint::new(route.clone(), "target_pos", 64.0)),
-            Box::new(Wait::new(0.032)),
-            Box::new(IsAtTarget::new("target_pos")),
-        ])),
-        Box::new(MoveToTarget::new("target_pos")),
-    ]))))
+    NodeRegistry::default()
+        .build(
+            &TreeDescription::patrol(0.032, "MoveToTarget"),
+            &BuildContext::new(None, route),
+        )
+        .expect("the patrol tree is valid")
 }
 
 fn main() {
@@ -32,6 +29,12 @@ fn main() {
 
     init_logger(Box::new(DummyLogger));
 
+    // `bench scripts`: cost of the `update()` of the scripted characters
+    if std::env::args().nth(1).as_deref() == Some("scripts") {
+        script_tick::run();
+        return;
+    }
+
     println!("Running stress test for {n_characters} characters over {ticks} ticks.");
 
     // route sample
@@ -61,9 +64,14 @@ fn main() {
             direction: c.get_direction().clone(),
             velocity: Vector2D { x: 0.0, y: 0.0 },
             is_idle: c.is_idle(),
+            state: None,
             blackboard: Default::default(),
             current_speed: 0.0,
             cell_position: Vector2Di { x: 0, y: 0 },
+            perceived: Vec::new(),
+            trace: None,
+            inventory: Vec::new(),
+            frame: 0,
         })
         .collect();
 
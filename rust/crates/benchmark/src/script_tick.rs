@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use game_core::map::{LogicCell, LogicMap, StepType};
+use game_core::{CommandExecutor, ScriptedCharacterLogic, StateRequest};
+use scripting_vm::ScriptVM;
+
+use crate::DummyAnimator;
+
+// A typical `update()`: reads the character, a cell of the map and writes the memory
+const SCRIPT: &str = r#"
+let steps = 0;
+function update(character, dt) {
+    steps += 1;
+    if (character.state == "idle" && character.map.north.walkable) {
+        character.memory.steps = steps;
+    }
+}
+"#;
+
+const TICKS: u32 = 2000;
+
+fn make_map() -> Arc<LogicMap> {
+    let mut map = LogicMap::new(8, 8);
+    for j in 0..8 {
+        for i in 0..8 {
+            map.set_cell(
+                i,
+                j,
+                Some(LogicCell {
+                    walkable: true,
+                    height: 0,
+                    step_type: StepType::None,
+                }),
+            );
+        }
+    }
+    Arc::new(map)
+}
+
+/// Cost of `update()` for 1 and 100 scripted characters, each with its own coroutine.
+/// The characters stay idle, so the executor resumes the coroutine every tick like the
+/// world does, and `update()` runs with the character and the world of that tick.
+pub fn run() {
+    let map = make_map();
+    for n_characters in [1, 100] {
+        let mut scripts: Vec<(CommandExecutor, ScriptedCharacterLogic)> = (0..n_characters)
+            .map(|i| {
+                let mut character = ScriptedCharacterLogic::new(i, Box::new(DummyAnimator::new()));
+                character.set_logic_map(map.clone());
+                character.set_cell_position(3, 3);
+                character
+                    .try_transition(StateRequest::Idle)
+                    .expect("a new character can be idle");
+
+                let mut vm = ScriptVM::new(SCRIPT).expect("the VM starts");
+                let mut executor = CommandExecutor::new();
+                executor.set_source(Box::new(vm.start_coroutine()));
+                (executor, character)
+            })
+            .collect();
+
+        let start = Instant::now();
+        for frame in 0..TICKS {
+            for (executor, character) in scripts.iter_mut() {
+                executor.observe(frame as u64, 0.016, character);
+                executor.tick(0.016, character, &map);
+            }
+        }
+        let total_time = start.elapsed();
+        assert!(
+            scripts.iter().all(|(executor, _)| executor.has_source()),
+            "update() runs without errors"
+        );
+        let avg_per_tick = total_time / TICKS;
+
+        println!("\n==============================");
+        println!(" Script tick: {} scripted characters", n_characters);
+        println!(" ticks        = {}", TICKS);
+        println!(" total time   = {:?}", total_time);
+        println!(" avg/tick     = {:?}", avg_per_tick);
+        println!(" avg/character/tick = {:?}", avg_per_tick / n_characters);
+        println!("==============================");
+    }
+}
@@ -5,12 +5,12 @@ use log::LevelFilter;
 
 use console_animator::ConsoleAnimator;
 use console_logger::ConsoleLogger;
-use game_core::ai::worker::init_bt_system;
-use game_core::bt::leafs::{IsAtTarget, MoveToTarget, NextWaypoint};
-use game_core::bt::nodes::{Selector, Sequence};
-use game_core::bt::wait::Wait;
-use game_core::bt::BehaviourTree;
-use game_core::CommandExecutor;
+use game_core::ai::worker::BtExecutionMode;
+use game_core::bt::{BuildContext, NodeRegistry, TreeDescription};
+use game_core::executor::ExecutorResult;
+use game_core::level::{LevelEvaluator, LevelStatus, Objective};
+use game_core::replay::{replay, Recorder, Recording};
+use game_core::World;
 use game_core::{NPCCharacterLogic, ScriptedCharacterLogic};
 use platform::logger::LogType;
 use platform::types::Vector2D;
@@ -46,38 +46,6 @@ fn main() {
 
     log_info!("Patrol points: {:?}", route);
 
-    init_bt_system();
-
-    //blackboard.set("target_pos", BlackboardValue::Vector(first_point));
-
-    // Tree structure:
-    // Sequence
-    //  1. Patrol Logic (update target, return Success when arrived)
-    //  2. Wait (pause)
-    //  3. WalkToTarget
-
-    // Actually, a better structure for continuous movement is:
-    // Selector
-    //   1. Sequence (Target Reached?)
-    //       a. Patrol (Calculate next point if arrived)
-    //       b. Wait (Visualize looking around)
-    //   2. WalkToTarget (Keep moving to current target)
-
-    let tree = Arc::new(BehaviourTree::new(Box::new(Selector::new(vec![
-        Box::new(Sequence::new(vec![
-            Box::new(NextWaypoint::new(route, "target_pos", 64.0)),
-            //Box::new(IsAtTarget::new("target_pos")),
-            Box::new(Wait::new(0.032)),
-            Box::new(IsAtTarget::new("target_pos")),
-        ])),
-        Box::new(MoveToTarget::new("target_pos")),
-    ]))));
-
-    let mut character = NPCCharacterLogic::new(1, Box::new(animator));
-    character.bt = tree;
-    //character.set_position(Vector2D { x: 81.0, y: 745.0 });
-    character.set_cell_position(1, 11);
-
     let logic_map = LogicMap::load_from_file("logic_map.ron").unwrap();
     let map_width = logic_map.width;
     let map_height = logic_map.height;
@@ -86,26 +54,42 @@ fn main() {
 
     log_info!("Logic map was loaded, size: {}x{}", map_width, map_height);
 
-    let script_code = r"
+    // The behaviour of the NPC can be changed in npc.ron, the patrol is used without the file
+    let description = match TreeDescription::load_from_file("npc.ron") {
+        Ok(description) => description,
+        Err(err) => {
+            log_info!("Using the patrol behaviour: {}", err);
+            TreeDescription::patrol(0.032, "MoveToTarget")
+        }
+    };
+    let tree = match NodeRegistry::default().build(
+        &description,
+        &BuildContext::new(Some(arc_logic_map.clone()), route),
+    ) {
+        Ok(tree) => tree,
+        Err(err) => {
+            log_error!("Cannot build the behaviour tree: {}", err);
+            return;
+        }
+    };
+
+    let mut character = NPCCharacterLogic::new(1, Box::new(animator));
+    character.bt = tree;
+    //character.set_position(Vector2D { x: 81.0, y: 745.0 });
+    character.set_cell_position(1, 11);
+
+    let script_code = r#"
         // for (let i = 0; i< 3; i++) {
         //    step_up();
         // }
         //
-        step_up();
-        step_up();
-        step_up();
-
-        step_right();
-
-        function update(character) {
-            let new_x = character.x + 10;
-            let new_y = character.y + 10;
-
-            set_position(new_x, new_y);
+        // walk north until the goblin hits an obstacle
+        while (can_move("north")) {
             step_up();
-            step_left();
         }
-    ";
+
+        step_right();
+    "#;
 
     log_info!("Script: {}", &script_code);
 
@@ -125,55 +109,135 @@ fn main() {
     let scripted_animator = ConsoleAnimator::new();
     let mut scripted_character = ScriptedCharacterLogic::new(2, Box::new(scripted_animator));
     scripted_character.set_logic_map(arc_logic_map.clone());
+    scripted_character.set_cell_position(3, 3);
 
-    let commands = match script.run_script() {
-        Ok(commands) => {
-            log_info!("Got commands from script: {:?}", commands);
-            commands
-        }
-        Err(e) => {
-            log_info!(
-                "JavaScript execution error: {} [line: {}, col: {}]",
-                e,
-                e.line,
-                e.col
-            );
-            vec![]
-        }
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        let index = args.iter().position(|arg| arg == name)?;
+        args.get(index + 1).cloned()
     };
 
-    let mut executor = CommandExecutor::new();
-    executor.set_commands(commands);
+    let mut world = World::from_arc(arc_logic_map);
+    world.set_time_step(0.016);
+    world.set_bt_mode(BtExecutionMode::Batch);
+    // `console_app --trace` prints what the behaviour tree of the NPC did in every cycle
+    let trace = args.iter().any(|arg| arg == "--trace");
+    world.tracer().set_enabled(trace);
+    world.add_npc(character);
+    world.set_player(scripted_character);
+    world.set_level(LevelEvaluator::new(vec![
+        Objective::ReachCell { x: 4, y: 3 },
+        Objective::AvoidDetection { range: 3 },
+        Objective::MaxCommands(20),
+    ]));
+
+    // `console_app --replay run.ron` replays a recorded run instead of the script
+    if let Some(filename) = arg_value("--replay") {
+        let recording = match Recording::load_from_file(&filename) {
+            Ok(recording) => recording,
+            Err(err) => {
+                log_error!("Cannot load the recording {}: {}", filename, err);
+                return;
+            }
+        };
+        match replay(&recording, &mut world) {
+            Ok(frames) => log_info!("Replayed {} frames, no divergence", frames),
+            Err(divergence) => log_error!("{}", divergence),
+        }
+        return;
+    }
 
-    scripted_character.set_cell_position(3, 3);
+    // `console_app --record run.ron` writes the run to a file
+    let record_file = arg_value("--record");
+    let mut recorder = Recorder::new(Some(script_code.to_string()), world.time_step());
+
+    // `console_app --break 3 --break 7` pauses the script before the API calls on these lines
+    let breakpoints: Vec<usize> = args
+        .windows(2)
+        .filter(|pair| pair[0] == "--break")
+        .filter_map(|pair| pair[1].parse().ok())
+        .collect();
+    if !breakpoints.is_empty() {
+        script.set_debug(true);
+        for line in breakpoints {
+            world.executor_mut().debugger_mut().set_breakpoint(line);
+        }
+    }
+
+    // the script is suspended at every API call and resumed by the executor
+    world.set_source(recorder.record_source(Box::new(script.start_coroutine())));
 
     // run 10 cycles
     for i in 0..10 {
         log_info!("Cycle: {}", i);
-        log_debug!(
-            "Character position: {:?}",
-            scripted_character.get_position()
-        );
 
-        let current_line = executor.current_line();
-        executor.tick(0.016, &mut scripted_character, &arc_logic_map);
+        world.step(0.016);
 
+        if let Some(tick) = world.tracer().last(1) {
+            log_info!("NPC behaviour tree, {}", tick);
+        }
+
+        let executor = world.executor();
         log_info!("Current command: {:?}", executor.get_current_command());
+        if executor.current_line() > 0 {
+            log_info!("Executing script line: {}", executor.current_line());
+        }
+
+        if let Some(e) = script.take_error() {
+            log_error!(
+                "JavaScript execution error: {} [line: {}, col: {}]",
+                e,
+                e.line,
+                e.col
+            );
+            log_info!("{}", e.friendly_message());
+        }
 
-        if current_line > 0 {
-            log_info!("Executing script line: {}", current_line);
+        // print the variables at the breakpoint and go on step by step
+        if world.executor_result() == ExecutorResult::Paused {
+            let executor = world.executor_mut();
+            log_info!("Paused at line {}", executor.current_line());
+            if let Some(variables) = executor.variables() {
+                for variable in variables.globals.iter().chain(&variables.locals) {
+                    log_info!("  {} = {}", variable.name, variable.value);
+                }
+            }
+            executor.step_over();
         }
 
-        match script.tick(&scripted_character.snapshot()) {
-            Ok(commands) => {
-                log_info!("Run update: commands: {:?}", commands);
-                CommandExecutor::apply(commands, &mut scripted_character);
+        recorder.record_frame(0.016, &world);
+
+        if let (Some(level), Some(player)) = (world.level(), world.player()) {
+            log_debug!("Character position: {:?}", player.get_position());
+            for progress in level.progress(player, world.executor()) {
+                log_debug!(
+                    "Objective: {} [{}/{}]",
+                    progress.objective,
+                    progress.current,
+                    progress.target
+                );
             }
-            Err(err) => log_error!("{}", err),
         }
 
-        // scripted_character.process(0.016, &arc_logic_map);
+        match world.level_status() {
+            Some(LevelStatus::Success) => {
+                log_info!("Level complete!");
+                break;
+            }
+            Some(LevelStatus::Failed(reason)) => {
+                log_info!("Level failed: {}", reason);
+                break;
+            }
+            _ => (),
+        }
 
         std::thread::sleep(Duration::from_millis(50));
     }
+
+    if let Some(filename) = record_file {
+        match recorder.finish().save_to_file(&filename) {
+            Ok(()) => log_info!("The run is recorded to {}", filename),
+            Err(err) => log_error!("Cannot write the recording {}: {}", filename, err),
+        }
+    }
 }
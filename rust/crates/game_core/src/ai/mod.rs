@@ -1 +1,2 @@
+pub mod perception;
 pub mod worker;
@@ -0,0 +1,290 @@
+use platform::types::{Direction, Vector2D, Vector2Di};
+
+use crate::character::snapshot::CharacterSnapshot;
+use crate::character::CharacterId;
+use crate::map::LogicMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Player, // the scripted goblin
+    Npc,
+}
+
+/// A character noticed by an NPC in the current frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerceivedEntity {
+    pub id: CharacterId,
+    pub kind: EntityKind,
+    pub cell: Vector2Di,
+    pub position: Vector2D,
+    pub seen: bool,
+    pub heard: bool,
+}
+
+/// A character which can be noticed: where it is and whether it makes noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stimulus {
+    pub id: CharacterId,
+    pub kind: EntityKind,
+    pub cell: Vector2Di,
+    pub position: Vector2D,
+    pub moving: bool,
+}
+
+impl Stimulus {
+    pub fn from_snapshot(snapshot: &CharacterSnapshot, kind: EntityKind) -> Self {
+        Self {
+            id: snapshot.id,
+            kind,
+            cell: snapshot.cell_position,
+            position: snapshot.position,
+            moving: !snapshot.is_idle,
+        }
+    }
+}
+
+/*
+ * Senses of an NPC, evaluated on the cells of the logic map.
+ * Vision: a cone in the facing direction, `view_range` cells deep and `view_angle` degrees wide.
+ * A cell is visible only if the line of sight to it is not blocked by a non-walkable cell,
+ * a closed door or a cell higher than the one the NPC stands on.
+ * Hearing: a moving character is heard within `hearing_radius` cells, through walls.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perception {
+    pub view_range: i32,
+    pub view_angle: f32,
+    pub hearing_radius: i32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            view_range: 5,
+            view_angle: 90.0,
+            hearing_radius: 2,
+        }
+    }
+}
+
+impl Perception {
+    pub fn can_see(
+        &self,
+        map: &LogicMap,
+        from: Vector2Di,
+        direction: Direction,
+        target: Vector2Di,
+    ) -> bool {
+        let facing = direction.to_vector();
+        let offset = target - from;
+        // distance along the facing direction and to the side of it
+        let forward = offset.x * facing.x as i32 + offset.y * facing.y as i32;
+        let side = (offset.x * facing.y as i32 - offset.y * facing.x as i32).abs();
+
+        if forward <= 0 || forward > self.view_range {
+            return false;
+        }
+        let spread = (self.view_angle.to_radians() / 2.0).tan();
+        if side as f32 > forward as f32 * spread + f32::EPSILON {
+            return false;
+        }
+
+        line_of_sight(map, from, target)
+    }
+
+    pub fn can_hear(&self, from: Vector2Di, target: Vector2Di, moving: bool) -> bool {
+        moving && (target - from).length_squared() <= self.hearing_radius * self.hearing_radius
+    }
+
+    /// Returns the characters the observer sees or hears, the observer itself is skipped
+    pub fn perceive(
+        &self,
+        map: &LogicMap,
+        observer: &CharacterSnapshot,
+        stimuli: &[Stimulus],
+    ) -> Vec<PerceivedEntity> {
+        stimuli
+            .iter()
+            .filter(|stimulus| !(stimulus.kind == EntityKind::Npc && stimulus.id == observer.id))
+            .filter_map(|stimulus| {
+                let from = observer.cell_position;
+                let seen = self.can_see(map, from, observer.direction, stimulus.cell);
+                let heard = self.can_hear(from, stimulus.cell, stimulus.moving);
+                (seen || heard).then_some(PerceivedEntity {
+                    id: stimulus.id,
+                    kind: stimulus.kind,
+                    cell: stimulus.cell,
+                    position: stimulus.position,
+                    seen,
+                    heard,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Checks the cells between `from` and `to` (Bresenham line, both ends excluded)
+pub fn line_of_sight(map: &LogicMap, from: Vector2Di, to: Vector2Di) -> bool {
+    if from == to {
+        return true;
+    }
+    let level = map.get_cell_level(from.x, from.y);
+
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut cell = from;
+
+    loop {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            cell.x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            cell.y += step_y;
+        }
+        if cell == to {
+            return true;
+        }
+
+        if !map.is_walkable(cell.x, cell.y)
+            || map.objects().is_closed_door(cell)
+            || map.get_cell_level(cell.x, cell.y) > level
+        {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::logic_map::LogicCell;
+    use crate::map::{StepType, WorldObject};
+    use crate::test_utils::test_bt::snapshot;
+    use crate::test_utils::test_world::flat_map;
+
+    /// 7×7 flat map with a wall at (3,1) and a higher cell at (3,5)
+    fn make_map() -> LogicMap {
+        let mut map = flat_map(7, 7);
+        map.set_cell(
+            3,
+            1,
+            Some(LogicCell {
+                walkable: false,
+                height: 0,
+                step_type: StepType::None,
+            }),
+        );
+        map.set_cell(
+            3,
+            5,
+            Some(LogicCell {
+                walkable: true,
+                height: 1,
+                step_type: StepType::None,
+            }),
+        );
+        map
+    }
+
+    #[test]
+    fn test_vision_cone() {
+        let map = make_map();
+        let perception = Perception::default();
+        let from = Vector2Di::new(1, 3);
+        let see = |x, y| perception.can_see(&map, from, Direction::EAST, Vector2Di::new(x, y));
+
+        assert!(see(2, 3));
+        assert!(see(6, 3));
+        // 45 degrees to each side
+        assert!(see(3, 5));
+        assert!(!see(2, 5));
+        // behind and out of range
+        assert!(!see(0, 3));
+        assert!(!see(1, 3));
+        assert!(!perception.can_see(
+            &map,
+            Vector2Di::new(0, 0),
+            Direction::EAST,
+            Vector2Di::new(6, 0)
+        ));
+    }
+
+    #[test]
+    fn test_line_of_sight_is_blocked() {
+        let mut map = make_map();
+        // the wall at (3,1)
+        assert!(!line_of_sight(
+            &map,
+            Vector2Di::new(1, 1),
+            Vector2Di::new(5, 1)
+        ));
+        // the higher cell at (3,5)
+        assert!(!line_of_sight(
+            &map,
+            Vector2Di::new(1, 5),
+            Vector2Di::new(5, 5)
+        ));
+        assert!(line_of_sight(
+            &map,
+            Vector2Di::new(1, 3),
+            Vector2Di::new(5, 3)
+        ));
+
+        map.objects_mut().place(
+            Vector2Di::new(3, 3),
+            WorldObject::Door {
+                open: false,
+                key: None,
+            },
+        );
+        assert!(!line_of_sight(
+            &map,
+            Vector2Di::new(1, 3),
+            Vector2Di::new(5, 3)
+        ));
+    }
+
+    #[test]
+    fn test_perceive() {
+        let map = make_map();
+        let perception = Perception::default();
+        let mut observer = snapshot();
+        observer.cell_position = Vector2Di::new(1, 1);
+        observer.direction = Direction::EAST;
+
+        let stimulus = |id, kind, x, y, moving| Stimulus {
+            id,
+            kind,
+            cell: Vector2Di::new(x, y),
+            position: Vector2D::ZERO,
+            moving,
+        };
+        let perceived = perception.perceive(
+            &map,
+            &observer,
+            &[
+                // the observer itself
+                stimulus(1, EntityKind::Npc, 1, 1, true),
+                // behind the wall and too far to be heard
+                stimulus(2, EntityKind::Player, 5, 1, true),
+                stimulus(3, EntityKind::Npc, 4, 2, false),
+                // next to the observer: out of sight, but walking
+                stimulus(4, EntityKind::Player, 1, 2, true),
+                // behind, standing still
+                stimulus(5, EntityKind::Npc, 0, 1, false),
+            ],
+        );
+
+        let noticed: Vec<_> = perceived
+            .iter()
+            .map(|entity| (entity.id, entity.seen, entity.heard))
+            .collect();
+        assert_eq!(noticed, vec![(3, true, false), (4, false, true)]);
+    }
+}
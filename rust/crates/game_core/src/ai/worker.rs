@@ -1,9 +1,11 @@
 use crate::bt::result::BTResult;
+use crate::bt::trace::BtTracer;
 use crate::character::CharacterId;
 use crossbeam::channel::{unbounded, Receiver, Sender};
+use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
 use std::collections::HashMap;
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
 use super::super::bt::job::BTJob;
@@ -12,73 +14,149 @@ use num_cpus;
 use platform::log_debug;
 use platform::logger::LogType;
 
-pub static JOB_TX: OnceLock<Sender<BTJob>> = OnceLock::new();
-static RESULT_MAP: OnceLock<Mutex<HashMap<CharacterId, (u32, BTResult)>>> = OnceLock::new();
-static SHUTDOWN_TX: OnceLock<Sender<()>> = OnceLock::new();
-static WORKER_HANDLE: OnceLock<Mutex<Option<JoinHandle<()>>>> = OnceLock::new();
+/// How the behaviour trees of NPCs are ticked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BtExecutionMode {
+    /// Jobs are sent to the background worker, the result is applied in a later frame
+    /// when it is ready. Fast, but depends on thread timing.
+    #[default]
+    Async,
+    /// The tree is ticked on the caller's thread when the character is processed
+    Inline,
+    /// The trees of all characters are ticked in parallel and the caller waits for the
+    /// whole batch, so every frame sees the results for its own snapshots
+    Batch,
+}
+
+type ResultMap = Arc<Mutex<HashMap<CharacterId, (u32, BTResult)>>>;
+
+/*
+ * Runs behaviour tree jobs on a background worker thread backed by a rayon pool.
+ * NPCs in the Async mode are registered with a scheduler via
+ * `NPCCharacterLogic::set_scheduler` and share it through an Arc.
+ * Every scheduler owns its own thread, pool and results, so several schedulers
+ * (e.g. a level preview and the live level) can exist in one process.
+ * The worker is stopped and joined when the scheduler is shut down or dropped.
+ * The ticks of the jobs are recorded by the scheduler's tracer when it is enabled.
+ */
+pub struct BtScheduler {
+    job_tx: Sender<BTJob>,
+    results: ResultMap,
+    shutdown_tx: Sender<()>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    tracer: Arc<BtTracer>,
+}
+
+impl BtScheduler {
+    pub fn new() -> Self {
+        Self::with_threads(num_cpus::get())
+    }
+
+    pub fn with_threads(num_threads: usize) -> Self {
+        Self::build(num_threads, Arc::new(BtTracer::default()))
+    }
 
-pub fn init_bt_system() {
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(num_cpus::get())
-        .build()
-        .unwrap();
+    /// Creates a scheduler recording the traces into a shared tracer, e.g. the World's one
+    pub fn with_tracer(tracer: Arc<BtTracer>) -> Self {
+        Self::build(num_cpus::get(), tracer)
+    }
 
-    log_debug!("Thread pool created: {:?}", &pool);
+    fn build(num_threads: usize, tracer: Arc<BtTracer>) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
 
-    let (tx, rx) = unbounded::<BTJob>();
-    let (shutdown_tx, shutdown_rx) = unbounded::<()>();
+        log_debug!("Thread pool created: {:?}", &pool);
 
-    JOB_TX.set(tx).expect("init_bt_system called more than once");
-    RESULT_MAP
-        .set(Mutex::new(HashMap::new()))
-        .expect("init_bt_system called more than once");
-    SHUTDOWN_TX
-        .set(shutdown_tx)
-        .expect("init_bt_system called more than once");
-    WORKER_HANDLE
-        .set(Mutex::new(None))
-        .expect("init_bt_system called more than once");
+        let (job_tx, job_rx) = unbounded::<BTJob>();
+        let (shutdown_tx, shutdown_rx) = unbounded::<()>();
+        let results: ResultMap = Arc::new(Mutex::new(HashMap::new()));
 
-    std::panic::set_hook(Box::new(|panic_info| {
-        log_debug!("PANIC in worker thread: {:?}", &panic_info);
-        eprintln!("PANIC in worker thread: {:?}", panic_info);
-    }));
+        let worker_results = results.clone();
+        let worker_tracer = tracer.clone();
+        let handle = thread::spawn(move || {
+            worker_loop(job_rx, shutdown_rx, pool, worker_results, worker_tracer)
+        });
+        log_debug!("Separated thread created: {:?}", &handle);
 
-    let handle = thread::spawn(move || worker_loop(rx, shutdown_rx, pool));
-    log_debug!("Separated thread created: {:?}", &handle);
+        Self {
+            job_tx,
+            results,
+            shutdown_tx,
+            worker: Mutex::new(Some(handle)),
+            tracer,
+        }
+    }
 
-    if let Some(slot) = WORKER_HANDLE.get() {
-        *slot.lock().unwrap() = Some(handle);
+    /// Traces of the ticks run by this scheduler
+    pub fn tracer(&self) -> &Arc<BtTracer> {
+        &self.tracer
     }
-}
 
-pub fn shutdown_bt_system() {
-    // Signal the worker to stop.
-    if let Some(tx) = SHUTDOWN_TX.get() {
-        let _ = tx.send(());
+    /// Queues the job for the worker. Jobs sent after shutdown are ignored.
+    pub fn submit(&self, job: BTJob) {
+        let _ = self.job_tx.send(job);
     }
-    // Join the worker thread so it fully stops before the extension is unloaded.
-    if let Some(slot) = WORKER_HANDLE.get() {
-        if let Some(handle) = slot.lock().unwrap().take() {
-            let _ = handle.join();
+
+    /// Returns the BT result only if it matches `expected_generation`.
+    /// Always removes the stored entry so stale results don't accumulate.
+    pub fn take_result(
+        &self,
+        character_id: CharacterId,
+        expected_generation: u32,
+    ) -> Option<BTResult> {
+        let mut map = self.results.lock().unwrap();
+        if let Some((gen, result)) = map.remove(&character_id) {
+            if gen == expected_generation {
+                return Some(result);
+            }
         }
+        None
     }
-    log_debug!("BT system shut down");
-}
 
-/// Returns the BT result only if it matches `expected_generation`.
-/// Always removes the stored entry so stale results don't accumulate.
-pub fn take_result(character_id: CharacterId, expected_generation: u32) -> Option<BTResult> {
-    let mut map = RESULT_MAP.get().unwrap().lock().unwrap();
-    if let Some((gen, result)) = map.remove(&character_id) {
-        if gen == expected_generation {
-            return Some(result);
+    /// Stops the worker and waits until it finishes the current batch
+    pub fn shutdown(&self) {
+        // Signal the worker to stop.
+        let _ = self.shutdown_tx.send(());
+        // Join the worker thread so it fully stops before the scheduler is gone.
+        if let Some(handle) = self.worker.lock().unwrap().take() {
+            if handle.join().is_err() {
+                log_debug!("BT worker thread panicked");
+            }
+            log_debug!("BT scheduler shut down");
         }
     }
-    None
 }
 
-fn worker_loop(rx: Receiver<BTJob>, shutdown_rx: Receiver<()>, pool: rayon::ThreadPool) {
+impl Default for BtScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for BtScheduler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Ticks a batch of jobs in parallel and blocks until all of them are done.
+/// The results are returned in the order of the jobs, so the outcome does not depend
+/// on which thread finished first.
+pub fn tick_batch(jobs: Vec<BTJob>, tracer: Option<&BtTracer>) -> Vec<(CharacterId, BTResult)> {
+    jobs.into_par_iter()
+        .map(|job| (job.character_id, job.run(tracer)))
+        .collect()
+}
+
+fn worker_loop(
+    rx: Receiver<BTJob>,
+    shutdown_rx: Receiver<()>,
+    pool: rayon::ThreadPool,
+    results: ResultMap,
+    tracer: Arc<BtTracer>,
+) {
     loop {
         // Block until the first job arrives or a shutdown signal is received.
         crossbeam::select! {
@@ -104,18 +182,20 @@ fn worker_loop(rx: Receiver<BTJob>, shutdown_rx: Receiver<()>, pool: rayon::Thre
                 pool.scope(|scope| {
                     for job in jobs.drain(..) {
                         let batch_results = &batch_results;
+                        let tracer = &tracer;
                         scope.spawn(move |_| {
-                            let result = job.bt.tick(&job.snapshot, job.delta);
+                            let (character_id, generation) = (job.character_id, job.generation);
+                            let result = job.run(Some(tracer));
                             batch_results
                                 .lock()
                                 .unwrap()
-                                .push((job.character_id, job.generation, result));
+                                .push((character_id, generation, result));
                         });
                     }
                 });
 
-                // Single bulk insert into the global result map.
-                let mut map = RESULT_MAP.get().unwrap().lock().unwrap();
+                // Single bulk insert into the result map.
+                let mut map = results.lock().unwrap();
                 for (id, gen, result) in batch_results.into_inner().unwrap() {
                     map.insert(id, (gen, result));
                 }
@@ -123,3 +203,129 @@ fn worker_loop(rx: Receiver<BTJob>, shutdown_rx: Receiver<()>, pool: rayon::Thre
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::command::BTCommand;
+    use crate::bt::result::BTResult;
+    use crate::bt::{BTNode, BehaviourTree, NodeStatus};
+    use crate::character::snapshot::CharacterSnapshot;
+    use crate::fsm::StateType;
+    use platform::types::{Direction, Vector2D, Vector2Di};
+    use std::time::Duration;
+
+    // always asks to snap to the cell
+    struct SnapLeaf;
+
+    impl BTNode for SnapLeaf {
+        fn reset(&mut self) {}
+        fn tick(&self, _snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
+            (
+                NodeStatus::SUCCESS,
+                BTResult {
+                    commands: vec![BTCommand::SnapToCell],
+                },
+            )
+        }
+        fn set_id(&mut self, _id: usize) {}
+        fn id(&self) -> usize {
+            0
+        }
+    }
+
+    fn job(character_id: CharacterId, generation: u32) -> BTJob {
+        BTJob {
+            character_id,
+            snapshot: CharacterSnapshot {
+                id: character_id,
+                position: Vector2D::new(0.0, 0.0),
+                cell_position: Vector2Di::new(0, 0),
+                direction: Direction::SOUTH,
+                velocity: Vector2D::new(0.0, 0.0),
+                is_idle: true,
+                state: Some(StateType::IDLE),
+                blackboard: Default::default(),
+                current_speed: 0.0,
+                perceived: Vec::new(),
+                trace: None,
+                inventory: Vec::new(),
+                frame: 0,
+            },
+            bt: Arc::new(BehaviourTree::new(Box::new(SnapLeaf))),
+            delta: 0.016,
+            generation,
+        }
+    }
+
+    fn wait_result(
+        scheduler: &BtScheduler,
+        character_id: CharacterId,
+        generation: u32,
+    ) -> Option<BTResult> {
+        for _ in 0..200 {
+            if let Some(result) = scheduler.take_result(character_id, generation) {
+                return Some(result);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn test_two_schedulers_are_independent() {
+        let first = BtScheduler::with_threads(1);
+        let second = BtScheduler::with_threads(1);
+
+        first.submit(job(1, 0));
+        assert!(wait_result(&first, 1, 0).is_some());
+        // the result went only to the scheduler which ran the job
+        assert!(second.take_result(1, 0).is_none());
+    }
+
+    #[test]
+    fn test_scheduler_can_be_recreated() {
+        let scheduler = BtScheduler::with_threads(1);
+        scheduler.shutdown();
+        // jobs after shutdown are ignored
+        scheduler.submit(job(1, 0));
+        drop(scheduler);
+
+        let scheduler = BtScheduler::with_threads(1);
+        scheduler.submit(job(2, 0));
+        assert!(wait_result(&scheduler, 2, 0).is_some());
+    }
+
+    #[test]
+    fn test_stale_generation_is_dropped() {
+        let scheduler = BtScheduler::with_threads(1);
+        scheduler.submit(job(1, 0));
+        for _ in 0..200 {
+            if scheduler.results.lock().unwrap().contains_key(&1) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(scheduler.take_result(1, 1).is_none());
+        assert!(scheduler.take_result(1, 0).is_none());
+    }
+
+    #[test]
+    fn test_scheduler_records_traces() {
+        let scheduler = BtScheduler::with_threads(1);
+        scheduler.tracer().set_enabled(true);
+        scheduler.submit(job(3, 0));
+        assert!(wait_result(&scheduler, 3, 0).is_some());
+
+        let trace = scheduler.tracer().last(3).unwrap();
+        assert_eq!(trace.status, NodeStatus::SUCCESS);
+        assert_eq!(trace.nodes[0].name, "SnapLeaf");
+    }
+
+    #[test]
+    fn test_tick_batch_keeps_job_order() {
+        let results = tick_batch((0..16).map(|id| job(id, 0)).collect(), None);
+        let ids: Vec<CharacterId> = results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, (0..16).collect::<Vec<_>>());
+    }
+}
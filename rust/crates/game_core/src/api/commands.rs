@@ -1,23 +1,27 @@
 use platform::types::{Direction, Vector2Di};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+use crate::save::remote::{DirectionDef, Vector2DiDef};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExecutionPlayerCommand {
     pub command: PlayerCommand,
     pub line: usize,
+    pub column: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PlayerCommand {
     MoveNorth,
     MoveSouth,
     MoveEast,
     MoveWest,
     Wait(f32),
-    Move(Direction),
-    SetPosition(Vector2Di),
-    Pick, // Pick an object from ground
-    Open, // Open a gate, a door which is in front of the scripted_characters
-          // Say(String), //Say something
+    Move(#[serde(with = "DirectionDef")] Direction),
+    SetPosition(#[serde(with = "Vector2DiDef")] Vector2Di),
+    Pick,        // Pick an object from ground
+    Open,        // Open a gate, a door which is in front of the scripted_characters
+    Say(String), // Show a speech bubble, the character waits while it is shown
 }
 
 impl PlayerCommand {
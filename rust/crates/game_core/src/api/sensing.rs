@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use platform::types::{Direction, Vector2Di};
+
+use crate::map::{LogicMap, StepType};
+use crate::ScriptedCharacterLogic;
+
+/// Description of a single map cell as seen by a script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellInfo {
+    pub cell: Vector2Di,
+    pub walkable: bool,
+    pub height: i32,
+    pub stairs: bool,
+}
+
+/// Read-only view of the LogicMap around the scripted character.
+/// Answers the questions scripts can ask about the world (`can_move`, `cell_ahead`, ...)
+/// relative to the character cell and facing direction.
+#[derive(Debug, Clone)]
+pub struct WorldView {
+    map: Arc<LogicMap>,
+    cell: Vector2Di,
+    direction: Direction,
+}
+
+impl WorldView {
+    pub fn new(map: Arc<LogicMap>, cell: Vector2Di, direction: Direction) -> Self {
+        Self {
+            map,
+            cell,
+            direction,
+        }
+    }
+
+    pub fn from_character(character: &ScriptedCharacterLogic) -> Self {
+        Self::new(
+            character.get_logic_map(),
+            character.get_cell_position(),
+            character.get_direction(),
+        )
+    }
+
+    pub fn cell(&self) -> Vector2Di {
+        self.cell
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    // Checks if the character can make a step from its cell in the given direction
+    pub fn can_move(&self, direction: Direction) -> bool {
+        self.map
+            .is_walkable_from(self.cell, self.cell + direction.to_vector())
+    }
+
+    // Returns the cell in front of the character
+    pub fn cell_ahead(&self) -> CellInfo {
+        self.cell_info(self.cell + self.direction.to_vector())
+    }
+
+    // Returns the level of the cell at the offset (dx, dy) from the character cell
+    pub fn height_at(&self, dx: i32, dy: i32) -> i32 {
+        self.map.get_cell_level(self.cell.x + dx, self.cell.y + dy)
+    }
+
+    pub fn is_stairs_ahead(&self) -> bool {
+        self.cell_ahead().stairs
+    }
+
+    pub fn cell_info(&self, cell: Vector2Di) -> CellInfo {
+        CellInfo {
+            cell,
+            walkable: self.map.is_walkable(cell.x, cell.y),
+            height: self.map.get_cell_level(cell.x, cell.y),
+            stairs: self.map.get_step_type(cell) != StepType::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::LogicCell;
+
+    /// 4×3 map:
+    /// ```text
+    /// Row 0: wall      wall      wall      wall
+    /// Row 1: flat(h=0) flat(h=0) step(h=0) flat(h=1)
+    /// Row 2: flat(h=0) flat(h=0) flat(h=0) flat(h=1)
+    /// ```
+    fn make_map() -> Arc<LogicMap> {
+        let mut map = LogicMap::new(4, 3);
+        for j in 0..3 {
+            for i in 0..4 {
+                let (walkable, height, step_type) = match (i, j) {
+                    (_, 0) => (false, 0, StepType::None),
+                    (2, 1) => (true, 0, StepType::Left),
+                    (3, _) => (true, 1, StepType::None),
+                    _ => (true, 0, StepType::None),
+                };
+                map.set_cell(
+                    i,
+                    j,
+                    Some(LogicCell {
+                        walkable,
+                        height,
+                        step_type,
+                    }),
+                );
+            }
+        }
+        Arc::new(map)
+    }
+
+    #[test]
+    fn test_can_move() {
+        let view = WorldView::new(make_map(), Vector2Di::new(1, 1), Direction::NORTH);
+        assert!(!view.can_move(Direction::NORTH), "wall in the north");
+        assert!(view.can_move(Direction::SOUTH));
+        assert!(view.can_move(Direction::WEST));
+    }
+
+    #[test]
+    fn test_can_move_respects_height() {
+        let view = WorldView::new(make_map(), Vector2Di::new(2, 2), Direction::EAST);
+        assert!(
+            !view.can_move(Direction::EAST),
+            "higher cell without stairs"
+        );
+    }
+
+    #[test]
+    fn test_cell_ahead() {
+        let view = WorldView::new(make_map(), Vector2Di::new(1, 2), Direction::NORTH);
+        let ahead = view.cell_ahead();
+        assert_eq!(ahead.cell, Vector2Di::new(1, 1));
+        assert!(ahead.walkable);
+        assert!(!ahead.stairs);
+
+        let view = WorldView::new(make_map(), Vector2Di::new(1, 1), Direction::NORTH);
+        assert!(!view.cell_ahead().walkable);
+    }
+
+    #[test]
+    fn test_is_stairs_ahead() {
+        let view = WorldView::new(make_map(), Vector2Di::new(1, 1), Direction::EAST);
+        assert!(view.is_stairs_ahead());
+
+        let view = WorldView::new(make_map(), Vector2Di::new(1, 1), Direction::SOUTH);
+        assert!(!view.is_stairs_ahead());
+    }
+
+    #[test]
+    fn test_height_at() {
+        let view = WorldView::new(make_map(), Vector2Di::new(2, 2), Direction::EAST);
+        assert_eq!(view.height_at(0, 0), 0);
+        assert_eq!(view.height_at(1, 0), 1);
+        assert_eq!(view.height_at(10, 10), 0, "outside of the map");
+    }
+}
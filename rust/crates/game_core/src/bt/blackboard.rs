@@ -1,62 +1,646 @@
 use platform::types::Vector2D;
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, RwLock};
 
+use crate::bt::trace::BlackboardDiff;
+use crate::save::remote::Vector2DDef;
+use crate::save::{BlackboardSave, NodeStateSave};
 use crate::NodeStatus;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BlackboardValue {
     Bool(bool),
     Int(i32),
     Float(f32),
     String(String),
-    Vector(Vector2D),
+    Vector(#[serde(with = "Vector2DDef")] Vector2D),
     NodeState(NodeStatus),
 }
 
+/// Rust types stored in the blackboard through the typed keys
+pub trait BlackboardType: Sized {
+    fn into_value(self) -> BlackboardValue;
+    fn from_value(value: &BlackboardValue) -> Option<Self>;
+}
+
+macro_rules! blackboard_type {
+    ($type:ty, $variant:ident) => {
+        impl BlackboardType for $type {
+            fn into_value(self) -> BlackboardValue {
+                BlackboardValue::$variant(self)
+            }
+
+            fn from_value(value: &BlackboardValue) -> Option<Self> {
+                match value {
+                    BlackboardValue::$variant(val) => Some(val.clone()),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+blackboard_type!(bool, Bool);
+blackboard_type!(i32, Int);
+blackboard_type!(f32, Float);
+blackboard_type!(String, String);
+blackboard_type!(Vector2D, Vector);
+blackboard_type!(NodeStatus, NodeState);
+
+/// Typed key of the character memory, e.g. `Key::<Vector2D>::new("target_pos")`.
+/// A value of another type under the same name reads as None.
+pub struct Key<T> {
+    name: Cow<'static, str>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Key<T> {
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            name: name.into(),
+            _type: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T> Clone for Key<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone())
+    }
+}
+
+impl<T> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key({})", self.name)
+    }
+}
+
+/// Typed key of the internal state of a node, e.g. the index of the running child.
+/// Declared as a constant: `const INDEX: NodeKey<i32> = NodeKey::new("idx");`
+pub struct NodeKey<T> {
+    name: &'static str,
+    index: Option<usize>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> NodeKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            index: None,
+            _type: PhantomData,
+        }
+    }
+
+    /// One of several values under the same name, e.g. the status of every child
+    pub const fn at(&self, index: usize) -> Self {
+        Self {
+            name: self.name,
+            index: Some(index),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for NodeKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeKey<T> {}
+
+// Address of a node state value: no strings are built on the tick,
+// the name is owned only when the state is loaded from a save
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct NodeSlot {
+    scope: u32,
+    node: usize,
+    name: Cow<'static, str>,
+    index: Option<usize>,
+}
+
+impl fmt::Display for NodeSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}.{}[{}]", self.node, self.name, index),
+            None => write!(f, "{}.{}", self.node, self.name),
+        }
+    }
+}
+
+// A key changed while the journal is kept: a key of the memory or a node state
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum JournalKey {
+    Memory(String),
+    Node(NodeSlot),
+}
+
+impl fmt::Display for JournalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalKey::Memory(key) => write!(f, "{}", key),
+            JournalKey::Node(slot) => write!(f, "{}", slot),
+        }
+    }
+}
+
+// Values of the changed keys before their first change, see Blackboard::start_journal
+type Journal = Arc<Mutex<HashMap<JournalKey, Option<BlackboardValue>>>>;
+
+/*
+ * Copy-on-write map, the storage of every part of the blackboard.
+ * `snapshot()` hands out the current map without copying, a write copies the map
+ * only while such a snapshot is still in use. The lock is held only for a single
+ * read or write and the readers don't block each other, so parallel ticks and a copy
+ * of the values (for a save or a trace) don't wait for each other.
+ */
+#[derive(Debug)]
+struct CowMap<K, V> {
+    map: RwLock<Arc<HashMap<K, V>>>,
+}
+
+impl<K, V> Default for CowMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: RwLock::new(Arc::new(HashMap::new())),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> CowMap<K, V> {
+    fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.read().ok()?.get(key).cloned()
+    }
+
+    fn insert(&self, key: K, value: V) {
+        if let Ok(mut map) = self.map.write() {
+            Arc::make_mut(&mut map).insert(key, value);
+        }
+    }
+
+    fn remove<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Ok(mut map) = self.map.write() {
+            // no copy if the key is not there
+            if map.contains_key(key) {
+                Arc::make_mut(&mut map).remove(key);
+            }
+        }
+    }
+
+    fn retain(&self, mut keep: impl FnMut(&K) -> bool) {
+        if let Ok(mut map) = self.map.write() {
+            if !map.keys().all(&mut keep) {
+                Arc::make_mut(&mut map).retain(|key, _| keep(key));
+            }
+        }
+    }
+
+    /// The current values, not changed by later writes
+    fn snapshot(&self) -> Arc<HashMap<K, V>> {
+        match self.map.read() {
+            Ok(map) => map.clone(),
+            Err(_) => Arc::new(HashMap::new()),
+        }
+    }
+
+    fn replace(&self, values: HashMap<K, V>) {
+        if let Ok(mut map) = self.map.write() {
+            *map = Arc::new(values);
+        }
+    }
+}
+
+/*
+ * Memory shared by a group of NPCs (squad memory).
+ * Only the keys listed on creation are shared, e.g. "player_pos": one guard notices
+ * the goblin and the others go to the same place. The other keys stay private.
+ */
+#[derive(Debug, Default)]
+pub struct SquadMemory {
+    keys: HashSet<String>,
+    data: CowMap<String, BlackboardValue>,
+}
+
+impl SquadMemory {
+    pub fn new(keys: &[&str]) -> Self {
+        Self {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            data: CowMap::default(),
+        }
+    }
+
+    pub fn shares(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<BlackboardValue> {
+        self.data.get(key)
+    }
+
+    pub fn set(&self, key: &str, value: BlackboardValue) {
+        self.data.insert(key.to_string(), value);
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.data.remove(key);
+    }
+
+    /// The current values, not changed by later writes
+    pub fn entries(&self) -> Arc<HashMap<String, BlackboardValue>> {
+        self.data.snapshot()
+    }
+
+    pub fn clear(&self) {
+        self.data.replace(HashMap::new());
+    }
+}
+
+/*
+ * Memory of a character used by its behaviour tree.
+ * The handle is cheap to clone: the snapshot given to the tree shares the memory
+ * of the character. There are three parts:
+ *  - the character memory: values written and read by the nodes, e.g. "target_pos"
+ *  - the node state: counters and indices of the nodes, by node id and the scope
+ *    of the tree, so two trees ticked with the same blackboard don't mix their state
+ *  - the squad memory: the keys shared with other NPCs, see SquadMemory
+ * All parts are copy-on-write maps (see CowMap): a read or a write holds the lock
+ * of one map only for that value.
+ */
 #[derive(Clone, Default, Debug)]
 pub struct Blackboard {
-    data: Arc<RwLock<HashMap<String, BlackboardValue>>>,
+    data: Arc<CowMap<String, BlackboardValue>>,
+    nodes: Arc<CowMap<NodeSlot, BlackboardValue>>,
+    squad: Option<Arc<SquadMemory>>,
+    scope: u32, // the tree which owns the node state, see BehaviourTree::scope
+    journal: Option<Journal>, // kept by the handle of a traced tick only
 }
 
 impl Blackboard {
     pub fn new() -> Self {
-        Self {
-            data: Arc::new(RwLock::new(HashMap::new())),
-        }
+        Self::default()
+    }
+
+    /// Shares the keys of the squad with the other members
+    pub fn join_squad(&mut self, squad: Arc<SquadMemory>) {
+        self.squad = Some(squad);
+    }
+
+    pub fn squad(&self) -> Option<&Arc<SquadMemory>> {
+        self.squad.as_ref()
+    }
+
+    /// Sets the scope of the node state, done for every tick by the owner of the tree
+    pub fn set_scope(&mut self, scope: u32) {
+        self.scope = scope;
+    }
+
+    pub fn scope(&self) -> u32 {
+        self.scope
+    }
+
+    fn squad_for(&self, key: &str) -> Option<&Arc<SquadMemory>> {
+        self.squad.as_ref().filter(|squad| squad.shares(key))
     }
 
     //Helper to write data
     pub fn set(&self, key: &str, value: BlackboardValue) {
-        if let Ok(mut data) = self.data.write() {
-            data.insert(key.to_string(), value);
+        self.note(|| JournalKey::Memory(key.to_string()));
+        if let Some(squad) = self.squad_for(key) {
+            squad.set(key, value);
+        } else {
+            self.data.insert(key.to_string(), value);
         }
     }
 
     // Helper to read data (returns a clone of the value)
     pub fn get(&self, key: &str) -> Option<BlackboardValue> {
-        if let Ok(data) = self.data.read() {
-            data.get(key).cloned()
-        } else {
-            None
+        if let Some(squad) = self.squad_for(key) {
+            return squad.get(key);
         }
+        self.data.get(key)
     }
 
     // Helper: check if a key exist
     pub fn has(&self, key: &str) -> bool {
-        if let Ok(data) = self.data.read() {
-            data.contains_key(key)
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.note(|| JournalKey::Memory(key.to_string()));
+        if let Some(squad) = self.squad_for(key) {
+            squad.remove(key);
         } else {
-            false
+            self.data.remove(key);
         }
     }
 
     pub fn get_vector(&self, key: &str) -> Option<Vector2D> {
-        let value = self.get(key)?;
+        Vector2D::from_value(&self.get(key)?)
+    }
+
+    pub fn read<T: BlackboardType>(&self, key: &Key<T>) -> Option<T> {
+        T::from_value(&self.get(key.name())?)
+    }
+
+    pub fn write<T: BlackboardType>(&self, key: &Key<T>, value: T) {
+        self.set(key.name(), value.into_value());
+    }
+
+    fn slot<T>(&self, node: usize, key: &NodeKey<T>) -> NodeSlot {
+        NodeSlot {
+            scope: self.scope,
+            node,
+            name: Cow::Borrowed(key.name),
+            index: key.index,
+        }
+    }
+
+    /// Reads the internal state of the node with the id `node`
+    pub fn node_state<T: BlackboardType>(&self, node: usize, key: &NodeKey<T>) -> Option<T> {
+        T::from_value(&self.nodes.get(&self.slot(node, key))?)
+    }
+
+    pub fn set_node_state<T: BlackboardType>(&self, node: usize, key: &NodeKey<T>, value: T) {
+        self.note(|| JournalKey::Node(self.slot(node, key)));
+        self.nodes.insert(self.slot(node, key), value.into_value());
+    }
+
+    /// Forgets the state of the nodes with the ids `nodes` in the current scope,
+    /// e.g. of a subtree which was interrupted and has to start again
+    pub fn clear_node_states(&self, nodes: Range<usize>) {
+        let cleared = |slot: &NodeSlot| slot.scope == self.scope && nodes.contains(&slot.node);
+        if self.journal.is_some() {
+            for slot in self.nodes.snapshot().keys().filter(|slot| cleared(slot)) {
+                self.note(|| JournalKey::Node(slot.clone()));
+            }
+        }
+        self.nodes.retain(|slot| !cleared(slot));
+    }
+
+    /// Notes the keys changed through this handle from now on, see `changes`.
+    /// A traced tick gets the changes without copying the whole blackboard.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Journal::default());
+    }
 
-        match value {
-            BlackboardValue::Vector(vector) => Some(vector),
-            _ => None,
+    /// The keys changed since `start_journal`, sorted by key.
+    /// A key set back to its value is not listed.
+    pub fn changes(&self) -> Vec<BlackboardDiff> {
+        let noted = match self.journal.as_ref().map(|journal| journal.lock()) {
+            Some(Ok(journal)) => journal.clone(),
+            _ => return Vec::new(),
+        };
+        let mut changes: Vec<BlackboardDiff> = noted
+            .into_iter()
+            .map(|(key, before)| BlackboardDiff {
+                after: self.current(&key),
+                key: key.to_string(),
+                before,
+            })
+            .filter(|diff| diff.before != diff.after)
+            .collect();
+        changes.sort_by(|a, b| a.key.cmp(&b.key));
+        changes
+    }
+
+    fn current(&self, key: &JournalKey) -> Option<BlackboardValue> {
+        match key {
+            JournalKey::Memory(key) => self.get(key),
+            JournalKey::Node(slot) => self.nodes.get(slot),
+        }
+    }
+
+    // Keeps the value of the key before its first change, only while the journal is kept
+    fn note(&self, key: impl FnOnce() -> JournalKey) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let key = key();
+        if let Ok(mut journal) = journal.lock() {
+            if let Entry::Vacant(entry) = journal.entry(key) {
+                let before = self.current(entry.key());
+                entry.insert(before);
+            }
+        }
+    }
+
+    /// Copy of all values, e.g. to compare the blackboard before and after a tick.
+    /// The node state of the current scope is listed as "<node id>.<name>".
+    pub fn entries(&self) -> HashMap<String, BlackboardValue> {
+        let mut entries = self.data.snapshot().as_ref().clone();
+        if let Some(squad) = &self.squad {
+            entries.extend(
+                squad
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+        entries.extend(
+            self.nodes
+                .snapshot()
+                .iter()
+                .filter(|(slot, _)| slot.scope == self.scope)
+                .map(|(slot, value)| (slot.to_string(), value.clone())),
+        );
+        entries
+    }
+
+    /// Values to be written to a save game: the character memory, the squad keys
+    /// and the node state of the current scope
+    pub fn save(&self) -> BlackboardSave {
+        let mut memory: BTreeMap<String, BlackboardValue> = self
+            .data
+            .snapshot()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if let Some(squad) = &self.squad {
+            memory.extend(
+                squad
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
         }
+
+        let mut nodes: Vec<NodeStateSave> = self
+            .nodes
+            .snapshot()
+            .iter()
+            .filter(|(slot, _)| slot.scope == self.scope)
+            .map(|(slot, value)| NodeStateSave {
+                node: slot.node,
+                name: slot.name.to_string(),
+                index: slot.index,
+                value: value.clone(),
+            })
+            .collect();
+        nodes.sort_by(|a, b| (a.node, &a.name, a.index).cmp(&(b.node, &b.name, b.index)));
+
+        BlackboardSave { memory, nodes }
+    }
+
+    /// Replaces the character memory and the node state of the current scope with the saved ones.
+    /// The memory is changed in place, so the copies of this blackboard see the loaded values.
+    pub fn restore(&self, save: &BlackboardSave) {
+        self.data.replace(HashMap::new());
+        for (key, value) in &save.memory {
+            self.set(key, value.clone());
+        }
+
+        self.nodes.replace(
+            save.nodes
+                .iter()
+                .map(|state| {
+                    let slot = NodeSlot {
+                        scope: self.scope,
+                        node: state.node,
+                        name: Cow::Owned(state.name.clone()),
+                        index: state.index,
+                    };
+                    (slot, state.value.clone())
+                })
+                .collect(),
+        );
+    }
+
+    /// Forgets everything, including the values shared with the squad.
+    /// The memory is replaced, so the snapshots taken before don't see the new values
+    /// and a tick still running with them can't write into the new memory.
+    pub fn clear(&mut self) {
+        self.data = Arc::default();
+        self.nodes = Arc::default();
+        if let Some(squad) = &self.squad {
+            squad.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: NodeKey<i32> = NodeKey::new("idx");
+
+    #[test]
+    fn test_typed_keys() {
+        let bb = Blackboard::new();
+        let target = Key::<Vector2D>::new("target_pos");
+        let alert = Key::<bool>::new("target_pos");
+
+        bb.write(&target, Vector2D::new(1.0, 2.0));
+        assert_eq!(bb.read(&target), Some(Vector2D::new(1.0, 2.0)));
+        // same name, another type
+        assert_eq!(bb.read(&alert), None);
+        assert_eq!(bb.get_vector("target_pos"), Some(Vector2D::new(1.0, 2.0)));
+
+        bb.remove("target_pos");
+        assert!(!bb.has("target_pos"));
+    }
+
+    #[test]
+    fn test_node_state_is_scoped() {
+        let mut first = Blackboard::new();
+        first.set_scope(1);
+        let mut second = first.clone();
+        second.set_scope(2);
+
+        first.set_node_state(1, &INDEX, 3);
+        first.set_node_state(1, &INDEX.at(2), 5);
+        assert_eq!(first.node_state(1, &INDEX), Some(3));
+        assert_eq!(first.node_state(2, &INDEX), None);
+        // the same node id of another tree
+        assert_eq!(second.node_state(1, &INDEX), None);
+
+        // the node state is not mixed with the character memory
+        assert!(!first.has("1.idx"));
+        let entries = first.entries();
+        assert_eq!(entries.get("1.idx"), Some(&BlackboardValue::Int(3)));
+        assert_eq!(entries.get("1.idx[2]"), Some(&BlackboardValue::Int(5)));
+        assert!(second.entries().is_empty());
+    }
+
+    #[test]
+    fn test_map_is_copied_only_on_write() {
+        let map = CowMap::default();
+        map.insert("a".to_string(), 1);
+        let before = map.snapshot();
+        map.remove("b"); // no such key
+        assert!(Arc::ptr_eq(&before, &map.snapshot()));
+
+        map.insert("b".to_string(), 2);
+        assert_eq!(before.len(), 1);
+        assert_eq!(map.get("b"), Some(2));
+    }
+
+    #[test]
+    fn test_journal_lists_the_changes() {
+        let character = Blackboard::new();
+        character.set("timer", BlackboardValue::Int(1));
+
+        let mut traced = character.clone();
+        traced.start_journal();
+        traced.set("target", BlackboardValue::Bool(true));
+        traced.set("timer", BlackboardValue::Int(2));
+        traced.set("timer", BlackboardValue::Int(1)); // set back
+        traced.set_node_state(3, &INDEX, 4);
+
+        let keys: Vec<_> = traced.changes().into_iter().map(|diff| diff.key).collect();
+        assert_eq!(keys, vec!["3.idx", "target"]);
+        // the other handles keep no journal
+        assert!(character.changes().is_empty());
+    }
+
+    #[test]
+    fn test_squad_shares_only_its_keys() {
+        let squad = Arc::new(SquadMemory::new(&["player_pos"]));
+        let mut guard = Blackboard::new();
+        guard.join_squad(squad.clone());
+        let mut archer = Blackboard::new();
+        archer.join_squad(squad.clone());
+
+        guard.set(
+            "player_pos",
+            BlackboardValue::Vector(Vector2D::new(3.0, 4.0)),
+        );
+        guard.set("target_pos", BlackboardValue::Vector(Vector2D::ZERO));
+        assert_eq!(
+            archer.get_vector("player_pos"),
+            Some(Vector2D::new(3.0, 4.0))
+        );
+        assert!(!archer.has("target_pos"));
+
+        // a copy of the squad memory keeps its values
+        let before = squad.entries();
+        archer.set("player_pos", BlackboardValue::Bool(false));
+        assert_eq!(
+            before.get("player_pos"),
+            Some(&BlackboardValue::Vector(Vector2D::new(3.0, 4.0)))
+        );
+
+        guard.clear();
+        assert!(!archer.has("player_pos"));
     }
 }
@@ -1,5 +1,7 @@
-use crate::StateRequest;
+use crate::{NPCCharacterLogic, StateRequest};
 use platform::types::{Direction, Vector2D};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum BTCommand {
@@ -10,3 +12,41 @@ pub enum BTCommand {
     SnapToCell,
     Custom(String),
 }
+
+/// Handler of a `BTCommand::Custom` command.
+/// Gets the character and the argument of the command: "alert:3" -> "3", "alert" -> ""
+pub type CustomHandler = Arc<dyn Fn(&mut NPCCharacterLogic, &str) + Send + Sync>;
+
+/*
+ * Handlers of the game specific commands, e.g. `BTCommand::Custom("alert:3")`.
+ * A handler is registered by the command name, the part before the first ':'.
+ * The handlers are shared between the NPCs of a World.
+ */
+#[derive(Clone, Default)]
+pub struct CustomCommands {
+    handlers: HashMap<String, CustomHandler>,
+}
+
+impl CustomCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F>(&mut self, name: &str, handler: F)
+    where
+        F: Fn(&mut NPCCharacterLogic, &str) + Send + Sync + 'static,
+    {
+        self.handlers.insert(name.to_string(), Arc::new(handler));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    /// Finds the handler of the command, returns it with the argument of the command
+    pub fn resolve<'a>(&self, command: &'a str) -> Option<(CustomHandler, &'a str)> {
+        let (name, argument) = command.split_once(':').unwrap_or((command, ""));
+        let handler = self.handlers.get(name)?;
+        Some((handler.clone(), argument))
+    }
+}
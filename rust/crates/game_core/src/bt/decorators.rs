@@ -0,0 +1,419 @@
+use crate::bt::blackboard::NodeKey;
+use crate::bt::result::BTResult;
+use crate::bt::{BTNode, BoxBTNode, NodeStatus};
+use crate::character::snapshot::CharacterSnapshot;
+use std::ops::Range;
+
+/*
+ * Decorators - nodes with a single child changing its result.
+ * The child is kept in a Vec, so BehaviourTree::new assigns its id through children_mut().
+ * Counters and timers are kept in the Blackboard by node id, the tree itself is shared
+ * between the characters.
+ */
+
+const COUNT: NodeKey<i32> = NodeKey::new("count");
+const TIMER: NodeKey<f32> = NodeKey::new("timer");
+
+/// Inverts the result of the child: SUCCESS becomes FAILURE and vice versa
+pub struct Inverter {
+    children: Vec<BoxBTNode>,
+    id: usize,
+}
+
+impl Inverter {
+    pub fn new(child: BoxBTNode) -> Self {
+        Self {
+            children: vec![child],
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for Inverter {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        let status = match status {
+            NodeStatus::SUCCESS => NodeStatus::FAILURE,
+            NodeStatus::FAILURE => NodeStatus::SUCCESS,
+            NodeStatus::RUNNING => NodeStatus::RUNNING,
+        };
+        (status, result)
+    }
+}
+
+/// Succeeds even if the child fails, e.g. for an optional step of a Sequence
+pub struct Succeeder {
+    children: Vec<BoxBTNode>,
+    id: usize,
+}
+
+impl Succeeder {
+    pub fn new(child: BoxBTNode) -> Self {
+        Self {
+            children: vec![child],
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for Succeeder {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        if status == NodeStatus::RUNNING {
+            return (status, result);
+        }
+        (NodeStatus::SUCCESS, result)
+    }
+}
+
+/// Runs the child until it succeeds `times` times, the child is ticked once per tick.
+/// Fails as soon as the child fails. With `times == 0` the child is repeated forever.
+pub struct Repeater {
+    children: Vec<BoxBTNode>,
+    times: u32,
+    id: usize,
+}
+
+impl Repeater {
+    pub fn new(child: BoxBTNode, times: u32) -> Self {
+        Self {
+            children: vec![child],
+            times,
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for Repeater {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let bb = &snapshot.blackboard;
+
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        match status {
+            NodeStatus::RUNNING => (NodeStatus::RUNNING, result),
+            NodeStatus::FAILURE => {
+                bb.set_node_state(self.id, &COUNT, 0);
+                (NodeStatus::FAILURE, result)
+            }
+            NodeStatus::SUCCESS => {
+                let count = bb.node_state(self.id, &COUNT).unwrap_or(0) as u32 + 1;
+                if self.times != 0 && count >= self.times {
+                    bb.set_node_state(self.id, &COUNT, 0);
+                    return (NodeStatus::SUCCESS, result);
+                }
+                bb.set_node_state(self.id, &COUNT, count as i32);
+                (NodeStatus::RUNNING, result)
+            }
+        }
+    }
+}
+
+/// Ticks the child again after a failure, up to `attempts` times.
+/// With `attempts == 0` the child is retried until it succeeds.
+pub struct RetryUntilSuccess {
+    children: Vec<BoxBTNode>,
+    attempts: u32,
+    id: usize,
+}
+
+impl RetryUntilSuccess {
+    pub fn new(child: BoxBTNode, attempts: u32) -> Self {
+        Self {
+            children: vec![child],
+            attempts,
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for RetryUntilSuccess {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let bb = &snapshot.blackboard;
+
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        match status {
+            NodeStatus::RUNNING => (NodeStatus::RUNNING, result),
+            NodeStatus::SUCCESS => {
+                bb.set_node_state(self.id, &COUNT, 0);
+                (NodeStatus::SUCCESS, result)
+            }
+            NodeStatus::FAILURE => {
+                let failed = bb.node_state(self.id, &COUNT).unwrap_or(0) as u32 + 1;
+                if self.attempts != 0 && failed >= self.attempts {
+                    bb.set_node_state(self.id, &COUNT, 0);
+                    return (NodeStatus::FAILURE, result);
+                }
+                bb.set_node_state(self.id, &COUNT, failed as i32);
+                (NodeStatus::RUNNING, result)
+            }
+        }
+    }
+}
+
+/// Fails if the child keeps running longer than `limit` seconds
+pub struct Timeout {
+    children: Vec<BoxBTNode>,
+    limit: f32,
+    id: usize,
+    subtree: Range<usize>, // ids of this node and of its descendants
+}
+
+impl Timeout {
+    pub fn new(child: BoxBTNode, limit: f32) -> Self {
+        Self {
+            children: vec![child],
+            limit,
+            id: 0,
+            subtree: 0..0,
+        }
+    }
+}
+
+impl BTNode for Timeout {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn set_subtree(&mut self, ids: Range<usize>) {
+        self.subtree = ids;
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let bb = &snapshot.blackboard;
+
+        let elapsed = bb.node_state(self.id, &TIMER).unwrap_or(0.0) + delta;
+
+        if elapsed > self.limit {
+            // the child starts from the beginning next time: its timers, indices...
+            bb.clear_node_states(self.subtree.clone());
+            bb.set_node_state(self.id, &TIMER, 0.0);
+            return (NodeStatus::FAILURE, BTResult::empty());
+        }
+
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        if status == NodeStatus::RUNNING {
+            bb.set_node_state(self.id, &TIMER, elapsed);
+        } else {
+            bb.set_node_state(self.id, &TIMER, 0.0);
+        }
+        (status, result)
+    }
+}
+
+/// After the child succeeded, fails for `cooldown` seconds without ticking the child.
+/// The time counts down while the node is ticked.
+pub struct Cooldown {
+    children: Vec<BoxBTNode>,
+    cooldown: f32,
+    id: usize,
+}
+
+impl Cooldown {
+    pub fn new(child: BoxBTNode, cooldown: f32) -> Self {
+        Self {
+            children: vec![child],
+            cooldown,
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for Cooldown {
+    fn reset(&mut self) {
+        self.children[0].reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let bb = &snapshot.blackboard;
+
+        let remaining = bb.node_state(self.id, &TIMER).unwrap_or(0.0);
+        if remaining > 0.0 {
+            bb.set_node_state(self.id, &TIMER, remaining - delta);
+            return (NodeStatus::FAILURE, BTResult::empty());
+        }
+
+        let (status, result) = self.children[0].tick(snapshot, delta);
+        if status == NodeStatus::SUCCESS {
+            bb.set_node_state(self.id, &TIMER, self.cooldown);
+        }
+        (status, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::nodes::Sequence;
+    use crate::bt::tree::BehaviourTree;
+    use crate::bt::wait::Wait;
+    use crate::test_utils::test_bt::{run, snapshot, Scripted};
+    use NodeStatus::*;
+
+    #[test]
+    fn test_inverter_and_succeeder() {
+        let inverter = Inverter::new(Scripted::boxed("a", vec![SUCCESS, FAILURE, RUNNING]));
+        assert_eq!(
+            run(&inverter, &snapshot(), 3),
+            vec![FAILURE, SUCCESS, RUNNING]
+        );
+
+        let succeeder = Succeeder::new(Scripted::boxed("a", vec![FAILURE, RUNNING]));
+        assert_eq!(run(&succeeder, &snapshot(), 2), vec![SUCCESS, RUNNING]);
+    }
+
+    #[test]
+    fn test_repeater() {
+        let repeater = Repeater::new(Scripted::boxed("a", vec![SUCCESS]), 3);
+        assert_eq!(
+            run(&repeater, &snapshot(), 4),
+            vec![RUNNING, RUNNING, SUCCESS, RUNNING]
+        );
+
+        let repeater = Repeater::new(Scripted::boxed("a", vec![SUCCESS, FAILURE]), 3);
+        assert_eq!(run(&repeater, &snapshot(), 2), vec![RUNNING, FAILURE]);
+    }
+
+    #[test]
+    fn test_retry_until_success() {
+        let retry =
+            RetryUntilSuccess::new(Scripted::boxed("a", vec![FAILURE, FAILURE, SUCCESS]), 0);
+        assert_eq!(run(&retry, &snapshot(), 3), vec![RUNNING, RUNNING, SUCCESS]);
+
+        let retry = RetryUntilSuccess::new(Scripted::boxed("a", vec![FAILURE]), 2);
+        assert_eq!(run(&retry, &snapshot(), 2), vec![RUNNING, FAILURE]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut timeout = Timeout::new(Box::new(Wait::new(1.0)), 0.25);
+        timeout.set_id(1);
+        timeout.children_mut().unwrap()[0].set_id(2);
+        assert_eq!(
+            run(&timeout, &snapshot(), 3),
+            vec![RUNNING, RUNNING, FAILURE]
+        );
+    }
+
+    #[test]
+    fn test_timeout_resets_the_child() {
+        // ids: 1 Timeout, 2 Sequence, 3 "a", 4 Wait
+        let sequence = Sequence::new(vec![
+            Scripted::boxed("a", vec![SUCCESS]),
+            Box::new(Wait::new(1.0)),
+        ]);
+        let tree = BehaviourTree::new(Box::new(Timeout::new(Box::new(sequence), 0.25)));
+        let snapshot = snapshot();
+        assert_eq!(
+            run(tree.root.as_ref(), &snapshot, 3),
+            vec![RUNNING, RUNNING, FAILURE]
+        );
+        // no index of the Sequence and no timer of the Wait are left
+        let entries = snapshot.blackboard.entries();
+        assert!(!entries.contains_key("2.idx"));
+        assert!(!entries.contains_key("4.timer"));
+
+        // the Sequence starts again from its first child
+        run(tree.root.as_ref(), &snapshot, 1);
+        assert_eq!(Scripted::ticks(&snapshot, "a"), 2);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let cooldown = Cooldown::new(Scripted::boxed("a", vec![SUCCESS]), 0.25);
+        let snapshot = snapshot();
+        assert_eq!(
+            run(&cooldown, &snapshot, 5),
+            vec![SUCCESS, FAILURE, FAILURE, FAILURE, SUCCESS]
+        );
+        // the child is not ticked during the cooldown
+        assert_eq!(Scripted::ticks(&snapshot, "a"), 2);
+    }
+}
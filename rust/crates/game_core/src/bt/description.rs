@@ -0,0 +1,238 @@
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+
+use platform::types::Vector2D;
+
+use crate::bt::blackboard::BlackboardValue;
+use crate::bt::registry::BuildError;
+
+/// Parameter of a node in the tree description.
+/// Written in RON without a variant name: `2.0`, `"target_pos"`, `[(1, 11), (5, 10)]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Points(Vec<(f32, f32)>),
+}
+
+/// A node of the tree description: the node type registered in the `NodeRegistry`,
+/// its parameters and children
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub node: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, ParamValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeDescription>,
+}
+
+impl NodeDescription {
+    pub fn new(node: &str) -> Self {
+        Self {
+            node: node.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_param(mut self, name: &str, value: ParamValue) -> Self {
+        self.params.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<NodeDescription>) -> Self {
+        self.children = children;
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&ParamValue> {
+        self.params.get(name)
+    }
+
+    pub fn string(&self, name: &str) -> Result<&str, BuildError> {
+        match self.required(name)? {
+            ParamValue::String(value) => Ok(value),
+            _ => Err(self.wrong_param(name, "a string")),
+        }
+    }
+
+    /// Reads a number, integers are accepted as well: `delay: 2`
+    pub fn float(&self, name: &str) -> Result<f32, BuildError> {
+        match self.required(name)? {
+            ParamValue::Float(value) => Ok(*value as f32),
+            ParamValue::Int(value) => Ok(*value as f32),
+            _ => Err(self.wrong_param(name, "a number")),
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Result<i64, BuildError> {
+        match self.required(name)? {
+            ParamValue::Int(value) => Ok(*value),
+            _ => Err(self.wrong_param(name, "an integer")),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Result<bool, BuildError> {
+        match self.required(name)? {
+            ParamValue::Bool(value) => Ok(*value),
+            _ => Err(self.wrong_param(name, "a bool")),
+        }
+    }
+
+    pub fn points(&self, name: &str) -> Result<Vec<Vector2D>, BuildError> {
+        match self.required(name)? {
+            ParamValue::Points(points) => {
+                Ok(points.iter().map(|(x, y)| Vector2D::new(*x, *y)).collect())
+            }
+            _ => Err(self.wrong_param(name, "a list of points")),
+        }
+    }
+
+    /// Reads a parameter as a blackboard value, e.g. for the Condition node
+    pub fn value(&self, name: &str) -> Result<BlackboardValue, BuildError> {
+        match self.required(name)? {
+            ParamValue::Bool(value) => Ok(BlackboardValue::Bool(*value)),
+            ParamValue::Int(value) => Ok(BlackboardValue::Int(*value as i32)),
+            ParamValue::Float(value) => Ok(BlackboardValue::Float(*value as f32)),
+            ParamValue::String(value) => Ok(BlackboardValue::String(value.clone())),
+            ParamValue::Points(_) => Err(self.wrong_param(name, "a single value")),
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<&ParamValue, BuildError> {
+        self.param(name).ok_or_else(|| BuildError::MissingParam {
+            node: self.node.clone(),
+            param: name.to_string(),
+        })
+    }
+
+    fn wrong_param(&self, name: &str, expected: &str) -> BuildError {
+        BuildError::WrongParam {
+            node: self.node.clone(),
+            param: name.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+}
+
+/*
+ * Serializable description of a behaviour tree.
+ * Level designers write it in RON, the NodeRegistry builds the BehaviourTree from it:
+ * (
+ *     name: "patrol",
+ *     root: (
+ *         node: "Selector",
+ *         children: [
+ *             (node: "Sequence", children: [
+ *                 (node: "NextWaypoint", params: { "key": "target_pos" }),
+ *                 (node: "Wait", params: { "delay": 2.0 }),
+ *                 (node: "IsAtTarget", params: { "key": "target_pos" }),
+ *             ]),
+ *             (node: "FollowPath", params: { "key": "target_pos" }),
+ *         ],
+ *     ),
+ * )
+ */
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TreeDescription {
+    #[serde(default)]
+    pub name: String,
+    pub root: NodeDescription,
+}
+
+impl TreeDescription {
+    /// The patrol behaviour: pick the next patrol point, look around for `delay` seconds
+    /// and go to the point with the `walk` node ("MoveToTarget" or "FollowPath").
+    /// The patrol points are taken from the build context.
+    pub fn patrol(delay: f32, walk: &str) -> Self {
+        let key = || ParamValue::String("target_pos".to_string());
+        Self {
+            name: "patrol".to_string(),
+            root: NodeDescription::new("Selector").with_children(vec![
+                NodeDescription::new("Sequence").with_children(vec![
+                    NodeDescription::new("NextWaypoint").with_param("key", key()),
+                    NodeDescription::new("Wait")
+                        .with_param("delay", ParamValue::Float(delay as f64)),
+                    NodeDescription::new("IsAtTarget").with_param("key", key()),
+                ]),
+                NodeDescription::new(walk).with_param("key", key()),
+            ]),
+        }
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(filename)?;
+        let ron_string = to_string_pretty(self, PrettyConfig::default())?;
+        file.write_all(ron_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(filename)?;
+        Self::from_ron(&content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATROL: &str = r#"
+        (
+            name: "guard",
+            root: (
+                node: "Selector",
+                children: [
+                    (node: "Sequence", children: [
+                        (node: "NextWaypoint", params: { "key": "target_pos", "route": [(1, 11), (5, 10)] }),
+                        (node: "Wait", params: { "delay": 2 }),
+                        (node: "IsAtTarget", params: { "key": "target_pos" }),
+                    ]),
+                    (node: "MoveToTarget", params: { "key": "target_pos" }),
+                ],
+            ),
+        )
+    "#;
+
+    #[test]
+    fn test_parse_description() {
+        let tree = TreeDescription::from_ron(PATROL).unwrap();
+        assert_eq!(tree.name, "guard");
+        assert_eq!(tree.root.node, "Selector");
+        assert_eq!(tree.root.children.len(), 2);
+
+        let waypoint = &tree.root.children[0].children[0];
+        assert_eq!(waypoint.string("key").unwrap(), "target_pos");
+        assert_eq!(
+            waypoint.points("route").unwrap(),
+            vec![Vector2D::new(1.0, 11.0), Vector2D::new(5.0, 10.0)]
+        );
+        // an integer is a valid number
+        assert_eq!(tree.root.children[0].children[1].float("delay"), Ok(2.0));
+        assert!(matches!(
+            waypoint.float("key"),
+            Err(BuildError::WrongParam { .. })
+        ));
+        assert!(matches!(
+            waypoint.string("delay"),
+            Err(BuildError::MissingParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tree = TreeDescription::patrol(2.0, "FollowPath");
+        let text = to_string_pretty(&tree, PrettyConfig::default()).unwrap();
+        assert_eq!(TreeDescription::from_ron(&text).unwrap(), tree);
+    }
+}
@@ -1,4 +1,6 @@
 use super::CharacterSnapshot;
+use crate::bt::result::BTResult;
+use crate::bt::trace::BtTracer;
 use crate::{bt::BTRef, character::CharacterId};
 
 pub struct BTJob {
@@ -8,3 +10,17 @@ pub struct BTJob {
     pub delta: f32,
     pub generation: u32,
 }
+
+impl BTJob {
+    /// Ticks the tree, the trace is recorded if the tracer is enabled
+    pub fn run(self, tracer: Option<&BtTracer>) -> BTResult {
+        match tracer {
+            Some(tracer) if tracer.is_enabled() => {
+                let (result, trace) = self.bt.tick_traced(self.snapshot, self.delta);
+                tracer.record(self.character_id, trace);
+                result
+            }
+            _ => self.bt.tick(&self.snapshot, self.delta),
+        }
+    }
+}
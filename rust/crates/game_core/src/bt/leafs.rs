@@ -1,23 +1,33 @@
+use crate::ai::perception::EntityKind;
+use crate::bt::blackboard::{BlackboardValue, Key, NodeKey};
 use crate::bt::command::BTCommand;
 use crate::bt::result::BTResult;
-use crate::bt::{blackboard::BlackboardValue, BTNode, NodeStatus};
+use crate::bt::{BTNode, NodeStatus};
 use crate::character::request::StateRequest;
 use crate::character::snapshot::CharacterSnapshot;
+use crate::map::LogicMap;
 //use platform::log_debug;
 //use platform::logger::LogType;
 //use platform::shared::logger_global::log;
-use platform::types::{Direction, Vector2D};
+use platform::types::{Direction, Vector2D, Vector2Di};
 // use platform::{log, log_info};
+use std::sync::Arc;
 
+// Index of the current waypoint of NextWaypoint
+const WAYPOINT: NodeKey<i32> = NodeKey::new("nxw");
+
+/// Looks for the scripted goblin among the characters the NPC perceives.
+/// Writes the position of the goblin to the blackboard and succeeds,
+/// a seen goblin is preferred to a heard one. Fails if the goblin is not noticed.
 pub struct FindTarget {
-    target_key: String, // "target_pos"
+    target_key: Key<Vector2D>, // "target_pos"
     id: usize,
 }
 
 impl FindTarget {
     pub fn new(key: &str) -> Self {
         Self {
-            target_key: key.to_string(),
+            target_key: Key::new(key.to_string()),
             id: 0,
         }
     }
@@ -35,16 +45,23 @@ impl BTNode for FindTarget {
     fn reset(&mut self) {}
 
     fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
-        //TODO: placeholder!
-        //
-        let player_found = true;
-        let player_position = Vector2D::new(100.0, 200.0);
-
-        if player_found {
+        let target = snapshot
+            .perceived
+            .iter()
+            .filter(|entity| entity.kind == EntityKind::Player)
+            .min_by(|a, b| {
+                // seen first, then the closest one
+                b.seen.cmp(&a.seen).then(
+                    snapshot
+                        .position
+                        .distance_to(a.position)
+                        .total_cmp(&snapshot.position.distance_to(b.position)),
+                )
+            });
+
+        if let Some(target) = target {
             // write to memory
-            snapshot
-                .blackboard
-                .set(&self.target_key, BlackboardValue::Vector(player_position));
+            snapshot.blackboard.write(&self.target_key, target.position);
             return (NodeStatus::SUCCESS, BTResult::empty());
         }
 
@@ -53,14 +70,14 @@ impl BTNode for FindTarget {
 }
 
 pub struct MoveToTarget {
-    target_key: String,
+    target_key: Key<Vector2D>,
     id: usize,
 }
 
 impl MoveToTarget {
     pub fn new(key: &str) -> Self {
         Self {
-            target_key: key.to_string(),
+            target_key: Key::new(key.to_string()),
             id: 0,
         }
     }
@@ -82,9 +99,8 @@ impl BTNode for MoveToTarget {
 
         let mut commands = vec![];
         //1. read from memory (blackboard) the target position
-        let target_pos = match bb.get(&self.target_key) {
-            Some(BlackboardValue::Vector(v)) => v,
-            _ => return (NodeStatus::FAILURE, BTResult { commands }), //no target set, or wrong value type
+        let Some(target_pos) = bb.read(&self.target_key) else {
+            return (NodeStatus::FAILURE, BTResult { commands }); //no target set, or wrong value type
         };
 
         // check if the character has arrived to the to target pos
@@ -129,7 +145,7 @@ impl BTNode for MoveToTarget {
 
 pub struct NextWaypoint {
     waypoints: Vec<Vector2D>,
-    target_key: String,
+    target_key: Key<Vector2D>,
     tile_size: f32,
     id: usize,
 }
@@ -138,7 +154,7 @@ impl NextWaypoint {
     pub fn new(waypoints: Vec<Vector2D>, key: &str, tile_size: f32) -> Self {
         Self {
             waypoints,
-            target_key: key.to_string(),
+            target_key: Key::new(key.to_string()),
             tile_size,
             id: 0,
         }
@@ -158,18 +174,13 @@ impl BTNode for NextWaypoint {
     fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
         let bb = &snapshot.blackboard;
 
-        let key = format!("{}.nxw", self.id);
-
         let result = BTResult::empty();
 
         if self.waypoints.is_empty() {
             return (NodeStatus::FAILURE, result);
         }
 
-        let mut current_index = match bb.get(&key) {
-            Some(BlackboardValue::Int(val)) => val as usize,
-            _ => 0,
-        };
+        let mut current_index = bb.node_state(self.id, &WAYPOINT).unwrap_or(0) as usize;
 
         current_index = (current_index + 1) % self.waypoints.len();
 
@@ -177,15 +188,15 @@ impl BTNode for NextWaypoint {
         let half_tile = Vector2D::new(self.tile_size / 2.0, self.tile_size / 2.0);
         let next_pos = self.waypoints[current_index] * self.tile_size + half_tile;
 
-        bb.set(&self.target_key, BlackboardValue::Vector(next_pos));
+        bb.write(&self.target_key, next_pos);
 
-        bb.set(&key, BlackboardValue::Int(current_index as i32));
+        bb.set_node_state(self.id, &WAYPOINT, current_index as i32);
         (NodeStatus::SUCCESS, result)
     }
 }
 
 pub struct IsAtTarget {
-    target_key: String,
+    target_key: Key<Vector2D>,
     threshold: f32,
     id: usize,
 }
@@ -193,7 +204,7 @@ pub struct IsAtTarget {
 impl IsAtTarget {
     pub fn new(key: &str) -> Self {
         Self {
-            target_key: key.to_string(),
+            target_key: Key::new(key.to_string()),
             threshold: 16.0,
             id: 0,
         }
@@ -212,9 +223,8 @@ impl BTNode for IsAtTarget {
     fn reset(&mut self) {}
 
     fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
-        let target_pos = match snapshot.blackboard.get(&self.target_key) {
-            Some(BlackboardValue::Vector(v)) => v,
-            _ => return (NodeStatus::FAILURE, BTResult::empty()),
+        let Some(target_pos) = snapshot.blackboard.read(&self.target_key) else {
+            return (NodeStatus::FAILURE, BTResult::empty());
         };
 
         let dist = snapshot.position.distance_to(target_pos);
@@ -265,14 +275,14 @@ impl BTNode for IsAtTarget {
 }
 
 pub struct WalkToTarget {
-    target_key: String,
+    target_key: Key<Vector2D>,
     id: usize,
 }
 
 impl WalkToTarget {
     pub fn new(key: &str) -> Self {
         Self {
-            target_key: key.to_string(),
+            target_key: Key::new(key.to_string()),
             id: 0,
         }
     }
@@ -289,11 +299,8 @@ impl BTNode for WalkToTarget {
 
     fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
         //1. read target
-        let target_pos = match snapshot.blackboard.get(&self.target_key) {
-            Some(BlackboardValue::Vector(v)) => v,
-            _ => {
-                return (NodeStatus::FAILURE, BTResult::empty());
-            }
+        let Some(target_pos) = snapshot.blackboard.read(&self.target_key) else {
+            return (NodeStatus::FAILURE, BTResult::empty());
         };
 
         //2. check FSM state:
@@ -315,3 +322,234 @@ impl BTNode for WalkToTarget {
 
     fn reset(&mut self) {}
 }
+
+/*
+ * Walks to the target cell along the path found on the logic map, around walls and closed doors
+ * and over the stairs. The path is searched again before every step, so the character
+ * reacts on doors opened or closed during the walk.
+ * Every step is a WalkTo to the next cell of the path, with a turn first if the character
+ * doesn't face the step direction.
+ * Fails if there is no target or the target can't be reached.
+ */
+pub struct FollowPath {
+    map: Arc<LogicMap>,
+    target_key: Key<Vector2D>,
+    id: usize,
+}
+
+impl FollowPath {
+    pub fn new(map: Arc<LogicMap>, key: &str) -> Self {
+        Self {
+            map,
+            target_key: Key::new(key.to_string()),
+            id: 0,
+        }
+    }
+}
+
+impl BTNode for FollowPath {
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
+        let Some(target_pos) = snapshot.blackboard.read(&self.target_key) else {
+            return (NodeStatus::FAILURE, BTResult::empty());
+        };
+
+        // the character is still walking or turning
+        if !snapshot.is_idle {
+            return (NodeStatus::RUNNING, BTResult::empty());
+        }
+
+        let current = snapshot.cell_position;
+        let target = self.map.get_cell_position(target_pos);
+        if current == target {
+            return (
+                NodeStatus::SUCCESS,
+                BTResult {
+                    commands: vec![BTCommand::SnapToCell],
+                },
+            );
+        }
+
+        let Some(next) = self
+            .map
+            .find_path(current, target)
+            .and_then(|path| path.first().copied())
+        else {
+            return (NodeStatus::FAILURE, BTResult::empty());
+        };
+
+        let direction = step_direction(current, next);
+        let command = if snapshot.direction != direction {
+            BTCommand::ChangeState(StateRequest::Turn(direction))
+        } else {
+            BTCommand::ChangeState(StateRequest::WalkTo(self.map.get_screen_position(next)))
+        };
+
+        (
+            NodeStatus::RUNNING,
+            BTResult {
+                commands: vec![command],
+            },
+        )
+    }
+}
+
+// Direction of a path step. A step over the stairs changes both coordinates,
+// the stairs are always entered moving east or west.
+fn step_direction(from: Vector2Di, to: Vector2Di) -> Direction {
+    if to.x > from.x {
+        Direction::EAST
+    } else if to.x < from.x {
+        Direction::WEST
+    } else if to.y > from.y {
+        Direction::SOUTH
+    } else {
+        Direction::NORTH
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConditionOp {
+    Exists,
+    Equal,
+    NotEqual,
+    Less,    // numbers only, Int and Float can be compared with each other
+    Greater, // numbers only
+}
+
+/// Checks a value in the blackboard: SUCCESS if the condition holds, FAILURE otherwise.
+/// Used as a guard in a Sequence: `Sequence [Condition("alert" == true), Chase]`
+pub struct Condition {
+    key: String,
+    op: ConditionOp,
+    value: BlackboardValue,
+    id: usize,
+}
+
+impl Condition {
+    pub fn new(key: &str, op: ConditionOp, value: BlackboardValue) -> Self {
+        Self {
+            key: key.to_string(),
+            op,
+            value,
+            id: 0,
+        }
+    }
+
+    pub fn exists(key: &str) -> Self {
+        Self::new(key, ConditionOp::Exists, BlackboardValue::Bool(true))
+    }
+
+    fn as_number(value: &BlackboardValue) -> Option<f32> {
+        match value {
+            BlackboardValue::Int(val) => Some(*val as f32),
+            BlackboardValue::Float(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    fn holds(&self, current: Option<BlackboardValue>) -> bool {
+        let Some(current) = current else {
+            return false;
+        };
+
+        let compare =
+            || Condition::as_number(&current)?.partial_cmp(&Condition::as_number(&self.value)?);
+
+        match self.op {
+            ConditionOp::Exists => true,
+            ConditionOp::Equal => current == self.value,
+            ConditionOp::NotEqual => current != self.value,
+            ConditionOp::Less => compare() == Some(std::cmp::Ordering::Less),
+            ConditionOp::Greater => compare() == Some(std::cmp::Ordering::Greater),
+        }
+    }
+}
+
+impl BTNode for Condition {
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
+        if self.holds(snapshot.blackboard.get(&self.key)) {
+            (NodeStatus::SUCCESS, BTResult::empty())
+        } else {
+            (NodeStatus::FAILURE, BTResult::empty())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::perception::PerceivedEntity;
+    use crate::test_utils::test_bt::snapshot;
+
+    #[test]
+    fn test_find_target_prefers_seen_goblin() {
+        let mut snapshot = snapshot();
+        let find = FindTarget::new("target_pos");
+        assert_eq!(find.tick(&snapshot, 0.1).0, NodeStatus::FAILURE);
+
+        let entity = |id, kind, x, seen| PerceivedEntity {
+            id,
+            kind,
+            cell: Vector2Di::new(x, 0),
+            position: Vector2D::new(x as f32 * 64.0 + 32.0, 32.0),
+            seen,
+            heard: !seen,
+        };
+        snapshot.perceived = vec![
+            entity(2, EntityKind::Npc, 1, true),
+            entity(3, EntityKind::Player, 1, false),
+            entity(4, EntityKind::Player, 4, true),
+        ];
+        assert_eq!(find.tick(&snapshot, 0.1).0, NodeStatus::SUCCESS);
+        assert_eq!(
+            snapshot.blackboard.get_vector("target_pos"),
+            Some(Vector2D::new(288.0, 32.0))
+        );
+    }
+
+    #[test]
+    fn test_condition() {
+        let snapshot = snapshot();
+        let alert = Condition::new("alert", ConditionOp::Equal, BlackboardValue::Bool(true));
+        let close = Condition::new("distance", ConditionOp::Less, BlackboardValue::Int(3));
+        let status = |node: &Condition| node.tick(&snapshot, 0.1).0;
+
+        // a missing value fails any condition
+        assert_eq!(status(&alert), NodeStatus::FAILURE);
+        assert_eq!(status(&Condition::exists("alert")), NodeStatus::FAILURE);
+
+        snapshot
+            .blackboard
+            .set("alert", BlackboardValue::Bool(true));
+        snapshot
+            .blackboard
+            .set("distance", BlackboardValue::Float(2.5));
+        assert_eq!(status(&alert), NodeStatus::SUCCESS);
+        assert_eq!(status(&Condition::exists("alert")), NodeStatus::SUCCESS);
+        assert_eq!(status(&close), NodeStatus::SUCCESS);
+
+        // not a number
+        let wrong = Condition::new("alert", ConditionOp::Greater, BlackboardValue::Int(0));
+        assert_eq!(status(&wrong), NodeStatus::FAILURE);
+    }
+}
@@ -3,14 +3,21 @@
  */
 pub mod blackboard;
 pub mod command;
+pub mod decorators;
+pub mod description;
 pub mod job;
 pub mod leafs;
 pub mod nodes;
+pub mod registry;
 pub mod result;
+pub mod trace;
 pub mod tree;
 pub mod wait;
 
-pub use blackboard::Blackboard;
+pub use blackboard::{Blackboard, Key, NodeKey, SquadMemory};
+pub use description::{NodeDescription, ParamValue, TreeDescription};
+pub use registry::{BuildContext, BuildError, NodeRegistry};
+pub use trace::{BtTracer, TickTrace};
 
 use crate::{
     bt::{
@@ -23,13 +30,15 @@ use crate::{
 
 pub use tree::{BTRef, BehaviourTree};
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum NodeStatus {
     RUNNING,
     SUCCESS,
     FAILURE,
 }
 
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::sync::Arc;
 
 /*
@@ -42,18 +51,26 @@ pub trait BTNode: Send + Sync {
     fn set_id(&mut self, id: usize);
     fn id(&self) -> usize;
 
+    /// Name of the node shown in the traces, the type name by default
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
     fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
         None
     }
+
+    /// Called once the ids of the subtree are assigned: `ids` are the ids of the node
+    /// and of all its descendants, see BehaviourTree::new
+    fn set_subtree(&mut self, _ids: Range<usize>) {}
 }
 
 pub type BoxBTNode = Box<dyn BTNode>;
 
 pub fn build_default_tree() -> BTRef {
-    Arc::new(BehaviourTree {
-        root: Box::new(Selector::new(vec![
-            Box::new(FindTarget::new("target_pos")),
-            Box::new(MoveToTarget::new("target_pos")),
-        ])),
-    })
+    Arc::new(BehaviourTree::new(Box::new(Selector::new(vec![
+        Box::new(FindTarget::new("target_pos")),
+        Box::new(MoveToTarget::new("target_pos")),
+    ]))))
 }
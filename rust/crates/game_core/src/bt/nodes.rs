@@ -1,10 +1,16 @@
-use crate::bt::blackboard::BlackboardValue;
+use crate::bt::blackboard::NodeKey;
 use crate::bt::result::BTResult;
 use crate::bt::{BoxBTNode, NodeStatus};
 use crate::character::snapshot::CharacterSnapshot;
 
 use super::BTNode;
 
+// Node state: the index of the running child, the order seed and the results of the children
+const INDEX: NodeKey<i32> = NodeKey::new("idx");
+const SELECTED: NodeKey<i32> = NodeKey::new("sel");
+const SEED: NodeKey<i32> = NodeKey::new("seed");
+const CHILD_STATUS: NodeKey<NodeStatus> = NodeKey::new("par");
+
 /*
  * / Sequence Node - all children must succeed
  */
@@ -46,12 +52,7 @@ impl BTNode for Sequence {
 
         let bb = &snapshot.blackboard;
 
-        let key = format!("{}.idx", self.id);
-
-        let mut index = match bb.get(&key) {
-            Some(BlackboardValue::Int(val)) => val as usize,
-            _ => 0,
-        };
+        let mut index = bb.node_state(self.id, &INDEX).unwrap_or(0) as usize;
 
         while index < self.children.len() {
             let (status, result) = self.children[index].tick(snapshot, delta);
@@ -61,18 +62,18 @@ impl BTNode for Sequence {
             match status {
                 NodeStatus::SUCCESS => index += 1,
                 NodeStatus::RUNNING => {
-                    bb.set(&key, BlackboardValue::Int(index as i32));
+                    bb.set_node_state(self.id, &INDEX, index as i32);
                     return (status, combined_result);
                 }
                 NodeStatus::FAILURE => {
-                    bb.set(&key, BlackboardValue::Int(0));
+                    bb.set_node_state(self.id, &INDEX, 0);
                     return (status, combined_result);
                 }
             }
         }
 
         // reset index
-        bb.set(&key, BlackboardValue::Int(0));
+        bb.set_node_state(self.id, &INDEX, 0);
         (NodeStatus::SUCCESS, combined_result)
     }
 }
@@ -110,12 +111,7 @@ impl BTNode for Selector {
     fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
         let bb = &snapshot.blackboard;
 
-        let key = format!("{}.sel", self.id);
-
-        let mut index = match bb.get(&key) {
-            Some(BlackboardValue::Int(val)) => val as usize,
-            _ => 0,
-        };
+        let mut index = bb.node_state(self.id, &SELECTED).unwrap_or(0) as usize;
 
         while index < self.children.len() {
             let (status, result) = self.children[index].tick(snapshot, delta);
@@ -126,18 +122,18 @@ impl BTNode for Selector {
                     continue;
                 }
                 NodeStatus::RUNNING => {
-                    bb.set(&key, BlackboardValue::Int(index as i32));
+                    bb.set_node_state(self.id, &SELECTED, index as i32);
                     return (NodeStatus::RUNNING, result);
                 }
                 NodeStatus::SUCCESS => {
-                    bb.set(&key, BlackboardValue::Int(0));
+                    bb.set_node_state(self.id, &SELECTED, 0);
                     return (NodeStatus::SUCCESS, result);
                 }
             }
         }
 
         // all failed
-        bb.set(&key, BlackboardValue::Int(0));
+        bb.set_node_state(self.id, &SELECTED, 0);
         (NodeStatus::FAILURE, BTResult::empty())
     }
 
@@ -148,3 +144,278 @@ impl BTNode for Selector {
         }
     }
 }
+
+/// How many children of a Parallel node have to finish with the same status
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParallelPolicy {
+    RequireOne,
+    RequireAll,
+}
+
+/*
+ * Parallel Node - ticks all children every tick.
+ * A finished child is not ticked again until the Parallel node itself finishes,
+ * its status is kept in the Blackboard.
+ * The failure policy is checked first. If all children finished without meeting
+ * any policy, the node fails.
+ */
+pub struct Parallel {
+    children: Vec<BoxBTNode>,
+    success_policy: ParallelPolicy,
+    failure_policy: ParallelPolicy,
+    id: usize,
+}
+
+impl Parallel {
+    pub fn new(
+        children: Vec<BoxBTNode>,
+        success_policy: ParallelPolicy,
+        failure_policy: ParallelPolicy,
+    ) -> Self {
+        Self {
+            children,
+            success_policy,
+            failure_policy,
+            id: 0,
+        }
+    }
+
+    fn is_met(policy: ParallelPolicy, count: usize, total: usize) -> bool {
+        match policy {
+            ParallelPolicy::RequireOne => count > 0,
+            ParallelPolicy::RequireAll => count == total,
+        }
+    }
+}
+
+impl BTNode for Parallel {
+    fn reset(&mut self) {
+        for child in &mut self.children {
+            child.reset();
+        }
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let mut combined_result = BTResult::empty();
+        let bb = &snapshot.blackboard;
+
+        let mut successes = 0;
+        let mut failures = 0;
+        for (index, child) in self.children.iter().enumerate() {
+            let key = CHILD_STATUS.at(index);
+
+            let status = match bb.node_state(self.id, &key) {
+                Some(status) if status != NodeStatus::RUNNING => status,
+                _ => {
+                    let (status, result) = child.tick(snapshot, delta);
+                    combined_result.commands.extend(result.commands);
+                    bb.set_node_state(self.id, &key, status);
+                    status
+                }
+            };
+
+            match status {
+                NodeStatus::SUCCESS => successes += 1,
+                NodeStatus::FAILURE => failures += 1,
+                NodeStatus::RUNNING => (),
+            }
+        }
+
+        let total = self.children.len();
+        let status = if Self::is_met(self.failure_policy, failures, total) {
+            NodeStatus::FAILURE
+        } else if Self::is_met(self.success_policy, successes, total) {
+            NodeStatus::SUCCESS
+        } else if successes + failures == total {
+            NodeStatus::FAILURE
+        } else {
+            return (NodeStatus::RUNNING, combined_result);
+        };
+
+        // finished: the children run again on the next tick
+        for index in 0..total {
+            bb.set_node_state(self.id, &CHILD_STATUS.at(index), NodeStatus::RUNNING);
+        }
+        (status, combined_result)
+    }
+}
+
+/*
+ * Random Selector - a Selector trying the children in a random order.
+ * The order is shuffled with a seed kept in the Blackboard and changed after every run,
+ * so the same character makes the same choices when the level is replayed.
+ */
+pub struct RandomSelector {
+    children: Vec<BoxBTNode>,
+    id: usize,
+}
+
+impl RandomSelector {
+    pub fn new(children: Vec<BoxBTNode>) -> Self {
+        Self { children, id: 0 }
+    }
+
+    // Fisher-Yates shuffle with a xorshift generator
+    fn order(&self, seed: u32) -> (Vec<usize>, u32) {
+        let mut state = seed.max(1);
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        let mut order: Vec<usize> = (0..self.children.len()).collect();
+        for i in (1..order.len()).rev() {
+            let j = next() as usize % (i + 1);
+            order.swap(i, j);
+        }
+        (order, next())
+    }
+}
+
+impl BTNode for RandomSelector {
+    fn reset(&mut self) {
+        for child in &mut self.children {
+            child.reset();
+        }
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        Some(&mut self.children)
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let bb = &snapshot.blackboard;
+
+        let seed = match bb.node_state(self.id, &SEED) {
+            Some(val) => val as u32,
+            None => snapshot.id.wrapping_mul(31).wrapping_add(self.id as u32),
+        };
+        let (order, next_seed) = self.order(seed);
+
+        let mut index = bb.node_state(self.id, &SELECTED).unwrap_or(0) as usize;
+
+        let mut status = NodeStatus::FAILURE;
+        let mut result = BTResult::empty();
+        while index < order.len() {
+            (status, result) = self.children[order[index]].tick(snapshot, delta);
+            match status {
+                NodeStatus::FAILURE => index += 1,
+                NodeStatus::RUNNING => {
+                    bb.set_node_state(self.id, &SELECTED, index as i32);
+                    bb.set_node_state(self.id, &SEED, seed as i32);
+                    return (NodeStatus::RUNNING, result);
+                }
+                NodeStatus::SUCCESS => break,
+            }
+        }
+
+        // the run is over, the next one uses another order
+        bb.set_node_state(self.id, &SELECTED, 0);
+        bb.set_node_state(self.id, &SEED, next_seed as i32);
+        if status == NodeStatus::SUCCESS {
+            (status, result)
+        } else {
+            (NodeStatus::FAILURE, BTResult::empty())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::BehaviourTree;
+    use crate::test_utils::test_bt::{run, snapshot, Scripted};
+    use NodeStatus::*;
+
+    #[test]
+    fn test_parallel_require_all() {
+        let parallel = BehaviourTree::new(Box::new(Parallel::new(
+            vec![
+                Scripted::boxed("a", vec![SUCCESS]),
+                Scripted::boxed("b", vec![RUNNING, RUNNING, SUCCESS]),
+            ],
+            ParallelPolicy::RequireAll,
+            ParallelPolicy::RequireOne,
+        )));
+        let snapshot = snapshot();
+        assert_eq!(
+            run(parallel.root.as_ref(), &snapshot, 3),
+            vec![RUNNING, RUNNING, SUCCESS]
+        );
+        // the finished child waits for the others
+        assert_eq!(Scripted::ticks(&snapshot, "a"), 1);
+        assert_eq!(Scripted::ticks(&snapshot, "b"), 3);
+    }
+
+    #[test]
+    fn test_parallel_require_one() {
+        let parallel = BehaviourTree::new(Box::new(Parallel::new(
+            vec![
+                Scripted::boxed("a", vec![RUNNING, FAILURE]),
+                Scripted::boxed("b", vec![RUNNING]),
+            ],
+            ParallelPolicy::RequireOne,
+            ParallelPolicy::RequireOne,
+        )));
+        assert_eq!(
+            run(parallel.root.as_ref(), &snapshot(), 2),
+            vec![RUNNING, FAILURE]
+        );
+    }
+
+    #[test]
+    fn test_random_selector_is_reproducible() {
+        let build = || {
+            BehaviourTree::new(Box::new(RandomSelector::new(vec![
+                Scripted::boxed("a", vec![SUCCESS]),
+                Scripted::boxed("b", vec![SUCCESS]),
+                Scripted::boxed("c", vec![SUCCESS]),
+            ])))
+        };
+        let picks = |tree: &BehaviourTree| {
+            let snapshot = snapshot();
+            run(tree.root.as_ref(), &snapshot, 30);
+            ["a", "b", "c"].map(|name| Scripted::ticks(&snapshot, name))
+        };
+
+        let first = picks(&build());
+        assert_eq!(first, picks(&build()));
+        assert_eq!(first.iter().sum::<usize>(), 30);
+        // every child gets its turn
+        assert!(first.iter().all(|ticks| *ticks > 0));
+    }
+
+    #[test]
+    fn test_random_selector_falls_back() {
+        let selector = BehaviourTree::new(Box::new(RandomSelector::new(vec![
+            Scripted::boxed("a", vec![FAILURE]),
+            Scripted::boxed("b", vec![FAILURE]),
+        ])));
+        let snapshot = snapshot();
+        assert_eq!(run(selector.root.as_ref(), &snapshot, 1), vec![FAILURE]);
+        assert_eq!(Scripted::ticks(&snapshot, "a"), 1);
+        assert_eq!(Scripted::ticks(&snapshot, "b"), 1);
+    }
+}
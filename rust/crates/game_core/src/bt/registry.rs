@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use platform::types::Vector2D;
+
+use crate::bt::decorators::{Cooldown, Inverter, Repeater, RetryUntilSuccess, Succeeder, Timeout};
+use crate::bt::description::{NodeDescription, TreeDescription};
+use crate::bt::leafs::{
+    Condition, ConditionOp, FindTarget, FollowPath, IsAtTarget, MoveToTarget, NextWaypoint,
+    WalkToTarget,
+};
+use crate::bt::nodes::{Parallel, ParallelPolicy, RandomSelector, Selector, Sequence};
+use crate::bt::wait::Wait;
+use crate::bt::{BTRef, BehaviourTree, BoxBTNode};
+use crate::map::LogicMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    UnknownNode(String),
+    MissingParam {
+        node: String,
+        param: String,
+    },
+    WrongParam {
+        node: String,
+        param: String,
+        expected: String,
+    },
+    /// A leaf node has children
+    UnexpectedChildren(String),
+    /// A decorator needs exactly one child
+    ExpectedOneChild(String),
+    /// The node needs the logic map, but the build context has none
+    MissingMap(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UnknownNode(node) => write!(f, "Unknown node type: {}", node),
+            BuildError::MissingParam { node, param } => {
+                write!(f, "{}: missing parameter '{}'", node, param)
+            }
+            BuildError::WrongParam {
+                node,
+                param,
+                expected,
+            } => write!(f, "{}: parameter '{}' must be {}", node, param, expected),
+            BuildError::UnexpectedChildren(node) => {
+                write!(f, "{}: leaf node can't have children", node)
+            }
+            BuildError::ExpectedOneChild(node) => {
+                write!(f, "{}: decorator must have exactly one child", node)
+            }
+            BuildError::MissingMap(node) => write!(f, "{}: the logic map is not set", node),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Data of the character the tree is built for
+#[derive(Default, Clone)]
+pub struct BuildContext {
+    pub map: Option<Arc<LogicMap>>,
+    /// Patrol points (in cells), used by NextWaypoint without the "route" parameter
+    pub patrol: Vec<Vector2D>,
+}
+
+impl BuildContext {
+    pub fn new(map: Option<Arc<LogicMap>>, patrol: Vec<Vector2D>) -> Self {
+        Self { map, patrol }
+    }
+
+    fn map(&self, desc: &NodeDescription) -> Result<Arc<LogicMap>, BuildError> {
+        self.map
+            .clone()
+            .ok_or_else(|| BuildError::MissingMap(desc.node.clone()))
+    }
+
+    fn tile_size(&self) -> f32 {
+        self.map.as_ref().map_or(64.0, |map| map.get_cell_size())
+    }
+}
+
+type NodeFactory = Box<
+    dyn Fn(&NodeDescription, Vec<BoxBTNode>, &BuildContext) -> Result<BoxBTNode, BuildError>
+        + Send
+        + Sync,
+>;
+
+/*
+ * Builds behaviour trees from their descriptions.
+ * Every node type is created by the factory registered under its name.
+ * `NodeRegistry::default()` knows all nodes of game_core,
+ * the game can register its own nodes or replace the existing ones.
+ */
+pub struct NodeRegistry {
+    factories: HashMap<String, NodeFactory>,
+}
+
+impl NodeRegistry {
+    /// Registry without any node
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers a node which takes the built children
+    pub fn register_composite<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&NodeDescription, Vec<BoxBTNode>, &BuildContext) -> Result<BoxBTNode, BuildError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    /// Registers a node without children
+    pub fn register_leaf<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&NodeDescription, &BuildContext) -> Result<BoxBTNode, BuildError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.register_composite(name, move |desc, children, context| {
+            if !children.is_empty() {
+                return Err(BuildError::UnexpectedChildren(desc.node.clone()));
+            }
+            factory(desc, context)
+        });
+    }
+
+    /// Registers a node with exactly one child
+    pub fn register_decorator<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&NodeDescription, BoxBTNode, &BuildContext) -> Result<BoxBTNode, BuildError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.register_composite(name, move |desc, mut children, context| {
+            if children.len() != 1 {
+                return Err(BuildError::ExpectedOneChild(desc.node.clone()));
+            }
+            factory(desc, children.remove(0), context)
+        });
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn build_node(
+        &self,
+        desc: &NodeDescription,
+        context: &BuildContext,
+    ) -> Result<BoxBTNode, BuildError> {
+        let factory = self
+            .factories
+            .get(&desc.node)
+            .ok_or_else(|| BuildError::UnknownNode(desc.node.clone()))?;
+
+        let children = desc
+            .children
+            .iter()
+            .map(|child| self.build_node(child, context))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        factory(desc, children, context)
+    }
+
+    /// Builds the tree, node ids are assigned by `BehaviourTree::new`
+    pub fn build(
+        &self,
+        desc: &TreeDescription,
+        context: &BuildContext,
+    ) -> Result<BTRef, BuildError> {
+        let root = self.build_node(&desc.root, context)?;
+        Ok(Arc::new(BehaviourTree::new(root)))
+    }
+}
+
+impl Default for NodeRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register_composite("Sequence", |_, children, _| {
+            Ok(Box::new(Sequence::new(children)))
+        });
+        registry.register_composite("Selector", |_, children, _| {
+            Ok(Box::new(Selector::new(children)))
+        });
+
+        registry.register_composite("RandomSelector", |_, children, _| {
+            Ok(Box::new(RandomSelector::new(children)))
+        });
+        registry.register_composite("Parallel", |desc, children, _| {
+            let success = optional(desc, "success", parallel_policy)?;
+            let failure = optional(desc, "failure", parallel_policy)?;
+            Ok(Box::new(Parallel::new(
+                children,
+                success.unwrap_or(ParallelPolicy::RequireAll),
+                failure.unwrap_or(ParallelPolicy::RequireOne),
+            )))
+        });
+
+        registry.register_decorator("Inverter", |_, child, _| Ok(Box::new(Inverter::new(child))));
+        registry.register_decorator("Succeeder", |_, child, _| {
+            Ok(Box::new(Succeeder::new(child)))
+        });
+        registry.register_decorator("Repeater", |desc, child, _| {
+            let times = optional(desc, "times", count)?;
+            Ok(Box::new(Repeater::new(child, times.unwrap_or(0))))
+        });
+        registry.register_decorator("RetryUntilSuccess", |desc, child, _| {
+            let attempts = optional(desc, "attempts", count)?;
+            Ok(Box::new(RetryUntilSuccess::new(
+                child,
+                attempts.unwrap_or(0),
+            )))
+        });
+        registry.register_decorator("Timeout", |desc, child, _| {
+            Ok(Box::new(Timeout::new(child, desc.float("limit")?)))
+        });
+        registry.register_decorator("Cooldown", |desc, child, _| {
+            Ok(Box::new(Cooldown::new(child, desc.float("time")?)))
+        });
+
+        registry.register_leaf("Condition", |desc, _| {
+            let key = desc.string("key")?;
+            let op = match desc.string("op")? {
+                "exists" => return Ok(Box::new(Condition::exists(key))),
+                "==" => ConditionOp::Equal,
+                "!=" => ConditionOp::NotEqual,
+                "<" => ConditionOp::Less,
+                ">" => ConditionOp::Greater,
+                _ => {
+                    return Err(BuildError::WrongParam {
+                        node: desc.node.clone(),
+                        param: "op".to_string(),
+                        expected: "one of \"exists\", \"==\", \"!=\", \"<\", \">\"".to_string(),
+                    })
+                }
+            };
+            Ok(Box::new(Condition::new(key, op, desc.value("value")?)))
+        });
+        registry.register_leaf("Wait", |desc, _| {
+            Ok(Box::new(Wait::new(desc.float("delay")?)))
+        });
+        registry.register_leaf("FindTarget", |desc, _| {
+            Ok(Box::new(FindTarget::new(desc.string("key")?)))
+        });
+        registry.register_leaf("MoveToTarget", |desc, _| {
+            Ok(Box::new(MoveToTarget::new(desc.string("key")?)))
+        });
+        registry.register_leaf("IsAtTarget", |desc, _| {
+            Ok(Box::new(IsAtTarget::new(desc.string("key")?)))
+        });
+        registry.register_leaf("WalkToTarget", |desc, _| {
+            Ok(Box::new(WalkToTarget::new(desc.string("key")?)))
+        });
+        registry.register_leaf("NextWaypoint", |desc, context| {
+            let route = match desc.param("route") {
+                Some(_) => desc.points("route")?,
+                None => context.patrol.clone(),
+            };
+            let tile_size = match desc.param("tile_size") {
+                Some(_) => desc.float("tile_size")?,
+                None => context.tile_size(),
+            };
+            Ok(Box::new(NextWaypoint::new(
+                route,
+                desc.string("key")?,
+                tile_size,
+            )))
+        });
+        registry.register_leaf("FollowPath", |desc, context| {
+            Ok(Box::new(FollowPath::new(
+                context.map(desc)?,
+                desc.string("key")?,
+            )))
+        });
+
+        registry
+    }
+}
+
+// Reads the parameter if it is set
+fn optional<T>(
+    desc: &NodeDescription,
+    name: &str,
+    read: impl Fn(&NodeDescription, &str) -> Result<T, BuildError>,
+) -> Result<Option<T>, BuildError> {
+    desc.param(name).map(|_| read(desc, name)).transpose()
+}
+
+fn count(desc: &NodeDescription, name: &str) -> Result<u32, BuildError> {
+    u32::try_from(desc.int(name)?).map_err(|_| BuildError::WrongParam {
+        node: desc.node.clone(),
+        param: name.to_string(),
+        expected: "a positive integer".to_string(),
+    })
+}
+
+fn parallel_policy(desc: &NodeDescription, name: &str) -> Result<ParallelPolicy, BuildError> {
+    match desc.string(name)? {
+        "one" => Ok(ParallelPolicy::RequireOne),
+        "all" => Ok(ParallelPolicy::RequireAll),
+        _ => Err(BuildError::WrongParam {
+            node: desc.node.clone(),
+            param: name.to_string(),
+            expected: "\"one\" or \"all\"".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::blackboard::BlackboardValue;
+    use crate::bt::description::ParamValue;
+    use crate::bt::result::BTResult;
+    use crate::bt::{BTNode, NodeStatus};
+    use crate::character::snapshot::CharacterSnapshot;
+    use crate::test_utils::test_bt::snapshot;
+
+    #[test]
+    fn test_build_patrol_tree() {
+        let registry = NodeRegistry::default();
+        let context =
+            BuildContext::new(None, vec![Vector2D::new(0.0, 0.0), Vector2D::new(3.0, 0.0)]);
+        let tree = registry
+            .build(&TreeDescription::patrol(0.5, "MoveToTarget"), &context)
+            .unwrap();
+
+        // NextWaypoint picks the second patrol point, Wait keeps the tree running
+        let snapshot = snapshot();
+        tree.tick(&snapshot, 0.1);
+        assert_eq!(
+            snapshot.blackboard.get("target_pos"),
+            Some(BlackboardValue::Vector(Vector2D::new(224.0, 32.0)))
+        );
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let registry = NodeRegistry::default();
+        let context = BuildContext::default();
+
+        let unknown = TreeDescription {
+            name: String::new(),
+            root: NodeDescription::new("Dance"),
+        };
+        assert_eq!(
+            registry.build(&unknown, &context).err(),
+            Some(BuildError::UnknownNode("Dance".to_string()))
+        );
+
+        // FollowPath needs the map
+        assert_eq!(
+            registry
+                .build(&TreeDescription::patrol(1.0, "FollowPath"), &context)
+                .err(),
+            Some(BuildError::MissingMap("FollowPath".to_string()))
+        );
+
+        let leaf_with_children = TreeDescription {
+            name: String::new(),
+            root: NodeDescription::new("Wait")
+                .with_param("delay", ParamValue::Float(1.0))
+                .with_children(vec![NodeDescription::new("Sequence")]),
+        };
+        assert_eq!(
+            registry.build(&leaf_with_children, &context).err(),
+            Some(BuildError::UnexpectedChildren("Wait".to_string()))
+        );
+    }
+
+    struct Succeed {
+        id: usize,
+    }
+
+    impl BTNode for Succeed {
+        fn reset(&mut self) {}
+        fn tick(&self, _snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
+            (NodeStatus::SUCCESS, BTResult::empty())
+        }
+        fn set_id(&mut self, id: usize) {
+            self.id = id;
+        }
+        fn id(&self) -> usize {
+            self.id
+        }
+    }
+
+    #[test]
+    fn test_custom_node() {
+        let mut registry = NodeRegistry::default();
+        registry.register_leaf("Succeed", |_, _| Ok(Box::new(Succeed { id: 0 })));
+        assert!(registry.contains("Succeed"));
+
+        let desc = TreeDescription {
+            name: String::new(),
+            root: NodeDescription::new("Sequence").with_children(vec![
+                NodeDescription::new("Succeed"),
+                NodeDescription::new("Succeed"),
+            ]),
+        };
+        let tree = registry.build(&desc, &BuildContext::default()).unwrap();
+        let (status, _) = tree.root.tick(&snapshot(), 0.1);
+        assert_eq!(status, NodeStatus::SUCCESS);
+    }
+
+    #[test]
+    fn test_build_decorators() {
+        let desc = TreeDescription::from_ron(
+            r#"(
+                root: (
+                    node: "Parallel",
+                    params: { "success": "one", "failure": "all" },
+                    children: [
+                        (node: "Sequence", children: [
+                            (node: "Condition", params: { "key": "alert", "op": "==", "value": true }),
+                            (node: "Cooldown", params: { "time": 1.5 }, children: [
+                                (node: "Wait", params: { "delay": 0.5 }),
+                            ]),
+                        ]),
+                        (node: "Inverter", children: [
+                            (node: "Condition", params: { "key": "alert", "op": "exists" }),
+                        ]),
+                    ],
+                ),
+            )"#,
+        )
+        .unwrap();
+        let tree = NodeRegistry::default()
+            .build(&desc, &BuildContext::default())
+            .unwrap();
+
+        // no alert: the Inverter succeeds
+        let (status, _) = tree.root.tick(&snapshot(), 0.1);
+        assert_eq!(status, NodeStatus::SUCCESS);
+
+        let snapshot = snapshot();
+        snapshot
+            .blackboard
+            .set("alert", BlackboardValue::Bool(true));
+        let (status, _) = tree.root.tick(&snapshot, 0.1);
+        assert_eq!(status, NodeStatus::RUNNING);
+
+        let two_children = TreeDescription {
+            name: String::new(),
+            root: NodeDescription::new("Inverter").with_children(vec![
+                NodeDescription::new("Sequence"),
+                NodeDescription::new("Sequence"),
+            ]),
+        };
+        assert_eq!(
+            NodeRegistry::default()
+                .build(&two_children, &BuildContext::default())
+                .err(),
+            Some(BuildError::ExpectedOneChild("Inverter".to_string()))
+        );
+    }
+}
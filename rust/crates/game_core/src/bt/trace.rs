@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::bt::blackboard::BlackboardValue;
+use crate::bt::result::BTResult;
+use crate::bt::{BTNode, BoxBTNode, NodeStatus};
+use crate::character::snapshot::CharacterSnapshot;
+use crate::character::CharacterId;
+
+// Number of ticks kept per character by default, about two seconds of a 60 fps game
+const DEFAULT_CAPACITY: usize = 120;
+
+/// Status of a node visited during one tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeTrace {
+    pub id: usize,
+    pub name: &'static str,
+    pub depth: usize, // 0 for the root
+    pub status: NodeStatus,
+}
+
+/// Nodes visited during the current tick, in the order they were entered
+pub type NodeTraceLog = Arc<Mutex<Vec<NodeTrace>>>;
+
+/// Change of a blackboard key during one tick, `None` if the key didn't exist
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlackboardDiff {
+    pub key: String,
+    pub before: Option<BlackboardValue>,
+    pub after: Option<BlackboardValue>,
+}
+
+/// Everything a behaviour tree did for one character in one tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickTrace {
+    pub tick: u64, // number of the traced tick of the character
+    pub status: NodeStatus,
+    pub nodes: Vec<NodeTrace>,
+    pub blackboard: Vec<BlackboardDiff>,
+}
+
+impl TickTrace {
+    /// Status of the node in this tick, None if the node was not visited.
+    /// A node visited several times (e.g. by a Repeater) reports its last status.
+    pub fn status_of(&self, id: usize) -> Option<NodeStatus> {
+        self.nodes
+            .iter()
+            .rev()
+            .find(|node| node.id == id)
+            .map(|node| node.status)
+    }
+}
+
+/*
+ * Text dump of a tick:
+ * tick 12: RUNNING
+ *   #1 Selector RUNNING
+ *     #2 Sequence FAILURE
+ *       #3 NextWaypoint SUCCESS
+ *   target_pos: None -> Vector(..)
+ */
+impl fmt::Display for TickTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tick {}: {:?}", self.tick, self.status)?;
+        for node in &self.nodes {
+            writeln!(
+                f,
+                "{}#{} {} {:?}",
+                "  ".repeat(node.depth + 1),
+                node.id,
+                node.name,
+                node.status
+            )?;
+        }
+        for diff in &self.blackboard {
+            writeln!(f, "  {}: {:?} -> {:?}", diff.key, diff.before, diff.after)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Collects the traces of behaviour tree ticks, separately for every character.
+ * Only the last `capacity` ticks of a character are kept.
+ * Tracing is disabled by default: the trees are ticked without recording anything.
+ * The tracer is shared between the World, its NPCs and the BtScheduler,
+ * so the traces are collected in all BT execution modes.
+ */
+#[derive(Debug)]
+pub struct BtTracer {
+    enabled: AtomicBool,
+    capacity: usize,
+    histories: Mutex<HashMap<CharacterId, History>>,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    next_tick: u64,
+    ticks: VecDeque<TickTrace>,
+}
+
+impl BtTracer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            capacity,
+            histories: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Stores the trace of the character's tick and numbers it
+    pub fn record(&self, character_id: CharacterId, mut trace: TickTrace) {
+        let mut histories = self.histories.lock().unwrap();
+        let history = histories.entry(character_id).or_default();
+
+        trace.tick = history.next_tick;
+        history.next_tick += 1;
+        if history.ticks.len() >= self.capacity {
+            history.ticks.pop_front();
+        }
+        history.ticks.push_back(trace);
+    }
+
+    /// The latest traced tick of the character
+    pub fn last(&self, character_id: CharacterId) -> Option<TickTrace> {
+        let histories = self.histories.lock().unwrap();
+        histories.get(&character_id)?.ticks.back().cloned()
+    }
+
+    /// The kept ticks of the character, the oldest first
+    pub fn history(&self, character_id: CharacterId) -> Vec<TickTrace> {
+        let histories = self.histories.lock().unwrap();
+        histories
+            .get(&character_id)
+            .map(|history| history.ticks.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Ids of the traced characters, sorted
+    pub fn characters(&self) -> Vec<CharacterId> {
+        let mut ids: Vec<CharacterId> = self.histories.lock().unwrap().keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn clear(&self) {
+        self.histories.lock().unwrap().clear();
+    }
+}
+
+impl Default for BtTracer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// Wraps every node of a BehaviourTree and records its status into the trace log
+/// of the snapshot. Without the log it only forwards the tick: the trees are shared
+/// and built once, the tracing is switched on and off while they run.
+pub(crate) struct Traced {
+    node: BoxBTNode,
+    depth: usize,
+}
+
+impl Traced {
+    pub(crate) fn new(node: BoxBTNode, depth: usize) -> Self {
+        Self { node, depth }
+    }
+}
+
+impl BTNode for Traced {
+    fn reset(&mut self) {
+        self.node.reset();
+    }
+
+    fn set_id(&mut self, id: usize) {
+        self.node.set_id(id);
+    }
+
+    fn id(&self) -> usize {
+        self.node.id()
+    }
+
+    fn name(&self) -> &'static str {
+        self.node.name()
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<BoxBTNode>> {
+        self.node.children_mut()
+    }
+
+    fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
+        let Some(log) = &snapshot.trace else {
+            return self.node.tick(snapshot, delta);
+        };
+
+        // the node is entered before its children, its status is known after them
+        let index = {
+            let mut log = log.lock().unwrap();
+            log.push(NodeTrace {
+                id: self.node.id(),
+                name: self.node.name(),
+                depth: self.depth,
+                status: NodeStatus::RUNNING,
+            });
+            log.len() - 1
+        };
+
+        let (status, result) = self.node.tick(snapshot, delta);
+        log.lock().unwrap()[index].status = status;
+        (status, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::nodes::{Selector, Sequence};
+    use crate::bt::BehaviourTree;
+    use crate::test_utils::test_bt::{snapshot, Scripted};
+    use NodeStatus::*;
+
+    fn make_tree() -> BehaviourTree {
+        BehaviourTree::new(Box::new(Selector::new(vec![
+            Box::new(Sequence::new(vec![
+                Scripted::boxed("a", vec![SUCCESS]),
+                Scripted::boxed("b", vec![FAILURE]),
+            ])),
+            Scripted::boxed("c", vec![RUNNING]),
+        ])))
+    }
+
+    #[test]
+    fn test_tick_traced() {
+        let tree = make_tree();
+        let (_, trace) = tree.tick_traced(snapshot(), 0.1);
+
+        assert_eq!(trace.status, RUNNING);
+        let visited: Vec<_> = trace
+            .nodes
+            .iter()
+            .map(|node| (node.id, node.name, node.depth, node.status))
+            .collect();
+        assert_eq!(
+            visited,
+            vec![
+                (1, "Selector", 0, RUNNING),
+                (2, "Sequence", 1, FAILURE),
+                (3, "Scripted", 2, SUCCESS),
+                (4, "Scripted", 2, FAILURE),
+                (5, "Scripted", 1, RUNNING),
+            ]
+        );
+        assert_eq!(trace.status_of(2), Some(FAILURE));
+
+        // the child indices of the composites and the tick counters of the Scripted nodes
+        let keys: Vec<_> = trace.blackboard.iter().map(|diff| &diff.key).collect();
+        assert_eq!(
+            keys,
+            vec!["1.sel", "2.idx", "a.ticks", "b.ticks", "c.ticks"]
+        );
+        assert_eq!(trace.blackboard[2].before, None);
+        assert_eq!(trace.blackboard[2].after, Some(BlackboardValue::Int(1)));
+
+        // a plain tick records nothing
+        assert!(snapshot().trace.is_none());
+    }
+
+    #[test]
+    fn test_tracer_keeps_last_ticks() {
+        let tree = make_tree();
+        let snapshot = snapshot();
+        let tracer = BtTracer::new(2);
+        for _ in 0..3 {
+            let (_, trace) = tree.tick_traced(snapshot.clone(), 0.1);
+            tracer.record(7, trace);
+        }
+
+        let ticks: Vec<u64> = tracer.history(7).iter().map(|trace| trace.tick).collect();
+        assert_eq!(ticks, vec![1, 2]);
+        assert_eq!(tracer.last(7).map(|trace| trace.tick), Some(2));
+        assert_eq!(tracer.characters(), vec![7]);
+        assert!(tracer.last(1).is_none());
+
+        let text = tracer.last(7).unwrap().to_string();
+        assert!(text.starts_with("tick 2: RUNNING\n  #1 Selector RUNNING\n"));
+    }
+}
@@ -1,15 +1,21 @@
 use super::BoxBTNode;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
 use super::result::BTResult;
 use crate::bt::nodes::Sequence;
+use crate::bt::trace::{NodeTraceLog, TickTrace, Traced};
 use crate::bt::wait::Wait;
 use crate::character::snapshot::CharacterSnapshot;
 
 pub struct BehaviourTree {
     pub root: BoxBTNode,
+    scope: u32,
 }
 
+// Scope 0 is the default scope of a Blackboard which is not attached to a tree
+static NEXT_SCOPE: AtomicU32 = AtomicU32::new(1);
+
 pub type BTRef = Arc<BehaviourTree>;
 
 impl BehaviourTree {
@@ -18,28 +24,66 @@ impl BehaviourTree {
         result
     }
 
+    /// Ticks the tree and records the status of every visited node
+    /// and the changes of the blackboard. Only the changed keys are copied.
+    pub fn tick_traced(
+        &self,
+        mut snapshot: CharacterSnapshot,
+        delta: f32,
+    ) -> (BTResult, TickTrace) {
+        let log = NodeTraceLog::default();
+        snapshot.trace = Some(log.clone());
+        snapshot.blackboard.start_journal();
+
+        let (status, result) = self.root.tick(&snapshot, delta);
+
+        let nodes = std::mem::take(&mut *log.lock().unwrap());
+        let blackboard = snapshot.blackboard.changes();
+        (
+            result,
+            TickTrace {
+                tick: 0,
+                status,
+                nodes,
+                blackboard,
+            },
+        )
+    }
+
     pub fn default() -> Self {
-        BehaviourTree {
-            root: Box::new(Sequence::new(vec![Box::new(Wait::new(1.0))])),
-        }
+        BehaviourTree::new(Box::new(Sequence::new(vec![Box::new(Wait::new(1.0))])))
     }
 
-    pub fn new(mut root: BoxBTNode) -> Self {
+    /// Assigns the ids to the nodes (depth-first, the root is 1),
+    /// wraps them to be traced and gives the tree its own scope
+    pub fn new(root: BoxBTNode) -> Self {
         let mut counter = 1;
 
-        BehaviourTree::assigns_ids(&mut root, &mut counter);
+        let root = BehaviourTree::prepare(root, &mut counter, 0);
+
+        let scope = NEXT_SCOPE.fetch_add(1, Ordering::Relaxed);
+        BehaviourTree { root, scope }
+    }
 
-        BehaviourTree { root }
+    /// Namespace of the node states of this tree in the Blackboard,
+    /// so node ids of different trees don't collide
+    pub fn scope(&self) -> u32 {
+        self.scope
     }
 
-    fn assigns_ids(node: &mut BoxBTNode, counter: &mut usize) {
-        node.set_id(*counter);
+    fn prepare(mut node: BoxBTNode, counter: &mut usize, depth: usize) -> BoxBTNode {
+        let id = *counter;
+        node.set_id(id);
         *counter += 1;
 
         if let Some(children) = node.children_mut() {
-            for child in children {
-                BehaviourTree::assigns_ids(child, counter);
-            }
+            *children = std::mem::take(children)
+                .into_iter()
+                .map(|child| BehaviourTree::prepare(child, counter, depth + 1))
+                .collect();
         }
+        node.set_subtree(id..*counter);
+
+        Box::new(Traced::new(node, depth))
     }
 }
@@ -1,7 +1,9 @@
 use crate::bt::result::BTResult;
-use crate::bt::{blackboard::BlackboardValue, BTNode, NodeStatus};
+use crate::bt::{blackboard::NodeKey, BTNode, NodeStatus};
 use crate::character::snapshot::CharacterSnapshot;
 
+const TIMER: NodeKey<f32> = NodeKey::new("timer");
+
 pub struct Wait {
     delay: f32,
     id: usize,
@@ -26,21 +28,17 @@ impl BTNode for Wait {
 
     fn tick(&self, snapshot: &CharacterSnapshot, delta: f32) -> (NodeStatus, BTResult) {
         let bb = &snapshot.blackboard;
-        let key = format!("{}.timer", self.id);
 
-        let mut current_time = match bb.get(&key) {
-            Some(BlackboardValue::Float(val)) => val,
-            _ => 0.0,
-        };
+        let mut current_time = bb.node_state(self.id, &TIMER).unwrap_or(0.0);
 
         current_time += delta;
 
         if current_time > self.delay {
-            bb.set(&key, BlackboardValue::Float(0.0));
+            bb.set_node_state(self.id, &TIMER, 0.0);
             return (NodeStatus::SUCCESS, BTResult::empty());
         }
 
-        bb.set(&key, BlackboardValue::Float(current_time));
+        bb.set_node_state(self.id, &TIMER, current_time);
         (NodeStatus::RUNNING, BTResult::empty())
     }
 }
@@ -1,9 +1,10 @@
 use crate::bt::Blackboard;
+use crate::character::inventory::Inventory;
 use crate::character::request::StateRequest;
 use crate::character::snapshot::CharacterSnapshot;
 use crate::character::CharacterId;
 use crate::fsm::FSM;
-use crate::fsm::{IdleState, RunState, TurnState, WaitState, WalkState};
+use crate::fsm::{AnimateState, IdleState, RunState, SteerState, TurnState, WaitState, WalkState};
 use crate::StateType;
 use platform::logger::LogType;
 use platform::types::Vector2Di;
@@ -14,12 +15,14 @@ use std::sync::{Arc, Mutex};
 use platform::log_debug;
 
 use crate::map::{LogicMap, StepType};
+use crate::save::CharacterSave;
 
 pub struct CharacterLogic {
     pub direction: Direction,
     pub start_direction: Direction,
     pub speed: f32,
     pub current_speed: f32,
+    heading: Option<Vector2D>, // free movement direction overriding `direction`, set by SteerState
     state: Option<Box<dyn FSM>>, // the active state machine, accessible only by Main Thread
 
     pending_request: Arc<Mutex<Option<StateRequest>>>, // the request buffer, thread safe
@@ -35,6 +38,7 @@ pub struct CharacterLogic {
     prev_cell: Vector2Di,
     pub start_cell: Vector2Di, // initial coordinates, used during level reset.
     pub logic_map: Arc<LogicMap>,
+    pub inventory: Inventory, // items picked up from the map
 }
 
 impl CharacterLogic {
@@ -45,6 +49,7 @@ impl CharacterLogic {
             start_direction: Direction::SOUTH,
             speed: 100.0,
             current_speed: 0.0,
+            heading: None,
             state: None,
             pending_request: Arc::new(Mutex::new(Some(StateRequest::Idle))),
             animator,
@@ -53,6 +58,7 @@ impl CharacterLogic {
 
             prev_cell: Vector2Di::new(0, 0),
             start_cell: Vector2Di::new(0, 0),
+            inventory: Inventory::new(),
         }
     }
 
@@ -99,6 +105,10 @@ impl CharacterLogic {
         self.logic_map.get_cell_position(position)
     }
 
+    pub fn state_type(&self) -> Option<StateType> {
+        self.state.as_ref().map(|state| state.get_type())
+    }
+
     // check if the character is in the idle state
     pub fn is_idle(&self) -> bool {
         if let Some(val) = self.state.as_ref() {
@@ -112,6 +122,15 @@ impl CharacterLogic {
         self.current_speed = speed;
     }
 
+    pub fn set_heading(&mut self, heading: Option<Vector2D>) {
+        self.heading = heading;
+    }
+
+    // Velocity without the stairs: along the heading if it is set, otherwise along the direction
+    fn velocity(&self) -> Vector2D {
+        self.heading.unwrap_or(self.direction.to_vector()) * self.current_speed
+    }
+
     /*
      * Set and play animation by construction animation name using Character direction:
      * for example, animation is run, the direction is WEST, than animation name will be run_west
@@ -179,6 +198,8 @@ impl CharacterLogic {
             StateRequest::Turn(_) => StateType::TURN,
             StateRequest::WalkTo(_) => StateType::RUN,
             StateRequest::Wait(_) => StateType::WAIT,
+            StateRequest::MoveToward(_) => StateType::RUN,
+            StateRequest::PlayAnimation(_) => StateType::ANIMATE,
         };
 
         //2. validate transition rules
@@ -217,6 +238,8 @@ impl CharacterLogic {
             StateRequest::Turn(direction) => Box::new(TurnState::new(*direction)),
             StateRequest::WalkTo(target) => Box::new(WalkState::new(*target)),
             StateRequest::Wait(time) => Box::new(WaitState::new(*time)),
+            StateRequest::MoveToward(target) => Box::new(SteerState::new(*target)),
+            StateRequest::PlayAnimation(animation) => Box::new(AnimateState::new(animation)),
         }
     }
 
@@ -239,11 +262,16 @@ impl CharacterLogic {
             id: self.id,
             position: self.animator.get_position(),
             direction: self.direction,
-            velocity: self.current_speed * self.direction.to_vector(),
+            velocity: self.velocity(),
             is_idle: self.is_idle(),
+            state: self.state_type(),
             blackboard: self.blackboard.clone(),
             current_speed: self.current_speed,
             cell_position: self.get_cell_position(),
+            perceived: Vec::new(),
+            trace: None,
+            inventory: self.inventory.items().to_vec(),
+            frame: 0,
         }
     }
 
@@ -261,9 +289,10 @@ impl CharacterLogic {
     }
 
     fn get_effective_velocity(&self, logic_map: &LogicMap) -> Vector2D {
-        let base = self.direction.to_vector() * self.current_speed;
+        let base = self.velocity();
 
-        if self.current_speed == 0.0 {
+        // the stairs change only the movement along the grid directions
+        if self.current_speed == 0.0 || self.heading.is_some() {
             return base;
         }
 
@@ -326,6 +355,50 @@ impl CharacterLogic {
         self.prev_cell = pos;
     }
 
+    /// Position, FSM state with its progress, pending request, blackboard and inventory
+    pub fn save(&self) -> CharacterSave {
+        CharacterSave {
+            id: self.id,
+            position: self.get_position(),
+            prev_cell: self.prev_cell,
+            direction: self.direction,
+            current_speed: self.current_speed,
+            state: self.state.as_ref().map(|state| state.save()),
+            pending_request: self
+                .pending_request
+                .lock()
+                .ok()
+                .and_then(|pending| pending.clone()),
+            blackboard: self.blackboard.save(),
+            inventory: self.inventory.items().to_vec(),
+        }
+    }
+
+    /// Puts the character into the saved state. The state is not entered again:
+    /// its progress is kept, only its animation is restarted.
+    pub fn restore(&mut self, save: &CharacterSave) {
+        self.set_position(save.position);
+        self.prev_cell = save.prev_cell;
+        self.direction = save.direction;
+        self.current_speed = save.current_speed;
+        self.heading = None;
+
+        self.state = save.state.as_ref().map(|state| state.load());
+        if let Some(mut state) = self.state.take() {
+            state.resume(self);
+            self.state = Some(state);
+        }
+        if let Ok(mut pending) = self.pending_request.lock() {
+            *pending = save.pending_request.clone();
+        }
+
+        self.blackboard.restore(&save.blackboard);
+        self.inventory.clear();
+        for item in &save.inventory {
+            self.inventory.add(item);
+        }
+    }
+
     // Reset character to its initial state (position, FSM, BT blackboard)
     pub fn reset(&mut self) {
         // Restore direction
@@ -338,7 +411,10 @@ impl CharacterLogic {
         self.force_transition(StateRequest::Idle);
 
         // Clear BT execution state (Blackboard stores sequence/selector indices)
-        *self.blackboard = Blackboard::new();
+        self.blackboard.clear();
+
+        // Drop picked items, the map restores its objects separately
+        self.inventory.clear();
 
         // Clear any state request
         if let Ok(mut pending) = self.pending_request.lock() {
@@ -0,0 +1,40 @@
+/// Items carried by a character
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    items: Vec<String>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, item: &str) {
+        self.items.push(item.to_string());
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.items.iter().any(|i| i == item)
+    }
+
+    /// Returns how many items with the given name the character carries
+    pub fn count(&self, item: &str) -> usize {
+        self.items.iter().filter(|i| *i == item).count()
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -1,5 +1,6 @@
 pub mod character;
 pub mod command;
+pub mod inventory;
 pub mod npc_character;
 pub mod request;
 pub mod scripted_character;
@@ -1,14 +1,18 @@
-use crate::ai::worker::*;
-use crate::bt::command::BTCommand;
+use crate::ai::perception::{PerceivedEntity, Perception};
+use crate::ai::worker::{BtExecutionMode, BtScheduler};
+use crate::bt::blackboard::SquadMemory;
+use crate::bt::command::{BTCommand, CustomCommands};
 use crate::bt::job::BTJob;
 use crate::bt::result::BTResult;
+use crate::bt::trace::BtTracer;
 use crate::character::snapshot::CharacterSnapshot;
 use crate::map::LogicMap;
+use crate::save::CharacterSave;
 use crate::StateRequest;
 use platform::animator::Animator;
-use platform::log_debug;
 use platform::logger::LogType;
 use platform::types::{Direction, Vector2D, Vector2Di};
+use platform::{log_debug, log_warn};
 use std::sync::Arc;
 
 use crate::{
@@ -21,6 +25,12 @@ pub struct NPCCharacterLogic {
     base: CharacterLogic,
     pub bt: BTRef,
     generation: u32,
+    bt_mode: BtExecutionMode,
+    scheduler: Option<Arc<BtScheduler>>, // runs the BT jobs in the Async mode
+    tracer: Option<Arc<BtTracer>>,       // records the ticks run on the caller's thread
+    custom_commands: Arc<CustomCommands>, // handlers of BTCommand::Custom
+    perception: Perception,
+    perceived: Vec<PerceivedEntity>, // what the NPC noticed in the current frame
 }
 
 impl NPCCharacterLogic {
@@ -29,14 +39,57 @@ impl NPCCharacterLogic {
             base: CharacterLogic::new(id, animator),
             bt: Arc::new(BehaviourTree::default()),
             generation: 0,
+            bt_mode: BtExecutionMode::default(),
+            scheduler: None,
+            tracer: None,
+            custom_commands: Arc::new(CustomCommands::new()),
+            perception: Perception::default(),
+            perceived: Vec::new(),
         }
     }
 
     pub fn process(&mut self, delta: f32, logic_map: &Arc<LogicMap>) {
-        self.tick_ai(delta);
+        match self.bt_mode {
+            BtExecutionMode::Async => self.tick_ai(delta),
+            // a batch of one character: nothing to wait for
+            BtExecutionMode::Inline | BtExecutionMode::Batch => self.tick_ai_inline(delta),
+        }
         self.base.process(delta, logic_map);
     }
 
+    /// Processes the FSM only. Used when the behaviour tree result was computed
+    /// outside, e.g. by `tick_batch` for all characters of the frame.
+    pub fn process_state(&mut self, delta: f32, logic_map: &Arc<LogicMap>) {
+        self.base.process(delta, logic_map);
+    }
+
+    pub fn set_bt_mode(&mut self, mode: BtExecutionMode) {
+        self.bt_mode = mode;
+    }
+
+    pub fn bt_mode(&self) -> BtExecutionMode {
+        self.bt_mode
+    }
+
+    /// Registers the character with the scheduler running its BT jobs in the Async mode
+    pub fn set_scheduler(&mut self, scheduler: Arc<BtScheduler>) {
+        self.scheduler = Some(scheduler);
+    }
+
+    /// Sets the tracer for the Inline and Batch modes, in the Async mode
+    /// the ticks are recorded by the scheduler's tracer
+    pub fn set_tracer(&mut self, tracer: Arc<BtTracer>) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn tracer(&self) -> Option<&Arc<BtTracer>> {
+        self.tracer.as_ref()
+    }
+
+    pub fn set_custom_commands(&mut self, commands: Arc<CustomCommands>) {
+        self.custom_commands = commands;
+    }
+
     pub fn set_logic_map(&mut self, map: Arc<LogicMap>) {
         self.base.set_logic_map(map);
     }
@@ -54,7 +107,32 @@ impl NPCCharacterLogic {
     }
 
     pub fn snapshot(&self) -> CharacterSnapshot {
-        self.base.snapshot()
+        let mut snapshot = self.base.snapshot();
+        snapshot.perceived = self.perceived.clone();
+        snapshot.blackboard.set_scope(self.bt.scope());
+        snapshot
+    }
+
+    /// Shares the squad keys of the blackboard with the other NPCs of the squad
+    pub fn join_squad(&mut self, squad: Arc<SquadMemory>) {
+        self.base.blackboard.join_squad(squad);
+    }
+
+    pub fn perception(&self) -> &Perception {
+        &self.perception
+    }
+
+    pub fn set_perception(&mut self, perception: Perception) {
+        self.perception = perception;
+    }
+
+    /// Characters noticed in the current frame, passed to the behaviour tree in the snapshot
+    pub fn set_perceived(&mut self, perceived: Vec<PerceivedEntity>) {
+        self.perceived = perceived;
+    }
+
+    pub fn perceived(&self) -> &[PerceivedEntity] {
+        &self.perceived
     }
 
     // check if the character is in the idle state
@@ -89,11 +167,28 @@ impl NPCCharacterLogic {
     // Reset character to its initial state (position, FSM, BT blackboard)
     pub fn reset(&mut self) {
         self.base.reset();
+        self.perceived.clear();
         // Increment generation so any in-flight BT jobs from before this reset
         // are considered stale and their results will be discarded.
         self.generation = self.generation.wrapping_add(1);
     }
 
+    /// The node state is saved from the scope of the current tree
+    pub fn save(&self) -> CharacterSave {
+        let mut save = self.base.save();
+        save.blackboard = self.snapshot().blackboard.save();
+        save
+    }
+
+    /// Restores a saved NPC, its tree has to be built from the same description.
+    /// The results of the jobs submitted before are discarded.
+    pub fn restore(&mut self, save: &CharacterSave) {
+        self.base.blackboard.set_scope(self.bt.scope());
+        self.base.restore(save);
+        self.perceived.clear();
+        self.generation = self.generation.wrapping_add(1);
+    }
+
     pub fn set_start_cell(&mut self, cell: Vector2Di) {
         self.base.start_cell = cell;
     }
@@ -103,21 +198,36 @@ impl NPCCharacterLogic {
     }
 
     pub fn tick_ai(&mut self, delta: f32) {
-        if let Some(tx) = JOB_TX.get() {
-            let _ = tx.send(BTJob {
-                character_id: self.base.id,
-                snapshot: self.snapshot(),
-                bt: self.bt.clone(),
-                delta,
-                generation: self.generation,
-            });
-        }
+        // without a scheduler there is no worker to send the job to
+        let Some(scheduler) = self.scheduler.clone() else {
+            self.tick_ai_inline(delta);
+            return;
+        };
 
-        if let Some(result) = take_result(self.base.id, self.generation) {
+        scheduler.submit(self.bt_job(delta));
+
+        if let Some(result) = scheduler.take_result(self.base.id, self.generation) {
             self.process_commands(result);
         }
     }
 
+    /// Ticks the behaviour tree on the caller's thread and applies the result immediately
+    pub fn tick_ai_inline(&mut self, delta: f32) {
+        let result = self.bt_job(delta).run(self.tracer.as_deref());
+        self.process_commands(result);
+    }
+
+    /// Job for the behaviour tree tick of the current frame
+    pub fn bt_job(&self, delta: f32) -> BTJob {
+        BTJob {
+            character_id: self.base.id,
+            snapshot: self.snapshot(),
+            bt: self.bt.clone(),
+            delta,
+            generation: self.generation,
+        }
+    }
+
     pub fn process_commands(&mut self, result: BTResult) {
         for cmd in result.commands {
             self.apply(cmd);
@@ -138,8 +248,144 @@ impl NPCCharacterLogic {
             SnapToCell => {
                 self.snap_to_cell();
             }
+            MoveToward(target) => {
+                self.request_state(StateRequest::MoveToward(target));
+            }
+            PlayAnimation(animation) => {
+                self.request_state(StateRequest::PlayAnimation(animation));
+            }
+            Custom(command) => match self.custom_commands.resolve(&command) {
+                Some((handler, argument)) => handler(self, argument),
+                None => log_warn!(
+                    "Character[{}]: no handler for the custom command {:?}",
+                    self.base.id,
+                    command
+                ),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_world::flat_map;
+    use platform::Animator;
+    use std::sync::Mutex;
+
+    // Plays every animation for three frames and remembers the played names
+    struct TestAnimator {
+        position: Vector2D,
+        played: Arc<Mutex<Vec<String>>>,
+        frames_left: u32,
+    }
 
-            _ => (),
+    impl Animator for TestAnimator {
+        fn play(&mut self, name: &str) {
+            self.played.lock().unwrap().push(name.to_string());
+            self.frames_left = 3;
+        }
+        fn is_playing(&self) -> bool {
+            self.frames_left > 0
+        }
+        fn process(&mut self, _delta: f32) {
+            self.frames_left = self.frames_left.saturating_sub(1);
+        }
+        fn set_position(&mut self, position: Vector2D) {
+            self.position = position;
+        }
+        fn get_position(&self) -> Vector2D {
+            self.position
         }
+        fn get_global_position(&self) -> Vector2D {
+            self.position
+        }
+    }
+
+    /// Idle NPC at the cell (0,0) of a 4×4 flat map
+    fn make_npc() -> (NPCCharacterLogic, Arc<LogicMap>, Arc<Mutex<Vec<String>>>) {
+        crate::test_utils::test_init::ensure_init();
+        let map = Arc::new(flat_map(4, 4));
+
+        let played = Arc::new(Mutex::new(Vec::new()));
+        let animator = TestAnimator {
+            position: Vector2D::ZERO,
+            played: played.clone(),
+            frames_left: 0,
+        };
+        let mut npc = NPCCharacterLogic::new(1, Box::new(animator));
+        npc.set_logic_map(map.clone());
+        npc.set_cell_position(0, 0);
+        npc.try_transition(StateRequest::Idle).unwrap();
+        (npc, map, played)
+    }
+
+    // Processes the NPC until it is idle, returns the number of frames
+    fn run_until_idle(npc: &mut NPCCharacterLogic, map: &Arc<LogicMap>) -> usize {
+        for frame in 1..=100 {
+            npc.process_state(0.1, map);
+            if npc.is_idle() {
+                return frame;
+            }
+        }
+        panic!("the character is still busy");
+    }
+
+    #[test]
+    fn test_move_toward_goes_straight_to_the_point() {
+        let (mut npc, map, _) = make_npc();
+        let start = npc.get_position();
+        let target = Vector2D::new(150.0, 90.0);
+
+        npc.apply(BTCommand::MoveToward(target));
+        npc.process_state(0.1, &map);
+        // both coordinates change at once, the move is not bound to the grid
+        let position = npc.get_position();
+        assert!(position.x > start.x && position.y > start.y);
+        assert_eq!(npc.get_direction(), Direction::EAST);
+        assert!(npc.snapshot().velocity.y > 0.0);
+
+        run_until_idle(&mut npc, &map);
+        assert_eq!(npc.get_position(), target);
+        assert_eq!(npc.snapshot().velocity, Vector2D::ZERO);
+    }
+
+    #[test]
+    fn test_play_animation_blocks_until_finished() {
+        let (mut npc, map, played) = make_npc();
+
+        npc.apply(BTCommand::PlayAnimation("attack".to_string()));
+        npc.process_state(0.1, &map);
+        assert!(!npc.is_idle());
+        assert_eq!(played.lock().unwrap().last().unwrap(), "attack_south");
+
+        // other states can't interrupt the animation
+        let start = npc.get_position();
+        npc.apply(BTCommand::MoveToward(Vector2D::new(200.0, 32.0)));
+        assert_eq!(run_until_idle(&mut npc, &map), 4);
+        assert_eq!(npc.get_position(), start);
+    }
+
+    #[test]
+    fn test_custom_commands() {
+        let (mut npc, _, _) = make_npc();
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+
+        let mut commands = CustomCommands::new();
+        let received = alerts.clone();
+        commands.register("alert", move |npc, argument| {
+            received.lock().unwrap().push(argument.to_string());
+            npc.set_direction(Direction::NORTH);
+        });
+        assert!(commands.contains("alert"));
+        npc.set_custom_commands(Arc::new(commands));
+
+        npc.apply(BTCommand::Custom("alert:3".to_string()));
+        npc.apply(BTCommand::Custom("alert".to_string()));
+        // without a handler the command is only logged
+        npc.apply(BTCommand::Custom("dance".to_string()));
+
+        assert_eq!(*alerts.lock().unwrap(), vec!["3", ""]);
+        assert_eq!(npc.get_direction(), Direction::NORTH);
     }
 }
@@ -1,10 +1,15 @@
 use platform::types::{Direction, Vector2D};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::save::remote::{DirectionDef, Vector2DDef};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum StateRequest {
     Idle,
     Run,
-    Turn(Direction),
-    WalkTo(Vector2D),
+    Turn(#[serde(with = "DirectionDef")] Direction),
+    WalkTo(#[serde(with = "Vector2DDef")] Vector2D),
     Wait(f32),
+    MoveToward(#[serde(with = "Vector2DDef")] Vector2D), // straight to the point, not along the grid
+    PlayAnimation(String), // one-shot animation, the character waits until it ends
 }
@@ -1,32 +1,77 @@
+use crate::character::inventory::Inventory;
 use crate::character::request::StateRequest;
 use crate::character::snapshot::CharacterSnapshot;
 use crate::character::CharacterId;
 use crate::map::LogicMap;
 use crate::map::StepType;
+use crate::save::CharacterSave;
 use crate::CharacterLogic;
 use platform::types::{Direction, Vector2D, Vector2Di};
 use platform::Animator;
 use std::sync::Arc;
 
+// Time a speech bubble stays: a base time and a time per character, to read it
+const SPEECH_BASE_TIME: f32 = 1.0;
+const SPEECH_TIME_PER_CHAR: f32 = 0.05;
+const SPEECH_MAX_TIME: f32 = 5.0;
+
+/// Seconds a speech bubble with `text` is shown
+pub fn speech_duration(text: &str) -> f32 {
+    (SPEECH_BASE_TIME + text.chars().count() as f32 * SPEECH_TIME_PER_CHAR).min(SPEECH_MAX_TIME)
+}
+
+/// A speech bubble over the character, given with `say()` in the script
+#[derive(Debug, Clone, PartialEq)]
+pub struct Speech {
+    pub text: String,
+    pub remaining: f32, // seconds left until the bubble is hidden
+}
+
 pub struct ScriptedCharacterLogic {
     base: CharacterLogic,
+    speech: Option<Speech>,
 }
 
 impl ScriptedCharacterLogic {
     pub fn new(id: CharacterId, animator: Box<dyn Animator>) -> Self {
         ScriptedCharacterLogic {
             base: CharacterLogic::new(id, animator),
+            speech: None,
         }
     }
 
     pub fn process(&mut self, delta: f32, logic_map: &Arc<LogicMap>) {
         self.base.process(delta, logic_map);
+
+        if let Some(speech) = &mut self.speech {
+            speech.remaining -= delta;
+            if speech.remaining <= 0.0 {
+                self.speech = None;
+            }
+        }
+    }
+
+    /// Shows a speech bubble with `text` for `duration` seconds
+    pub fn say(&mut self, text: &str, duration: f32) {
+        self.speech = Some(Speech {
+            text: text.to_string(),
+            remaining: duration,
+        });
+    }
+
+    /// The speech bubble shown now, the host draws it over the character
+    pub fn speech(&self) -> Option<&Speech> {
+        self.speech.as_ref()
     }
 
     pub fn set_logic_map(&mut self, map: Arc<LogicMap>) {
         self.base.set_logic_map(map);
     }
 
+    pub fn get_logic_map(&self) -> Arc<LogicMap> {
+        self.base.logic_map.clone()
+    }
+
     pub fn set_cell_position(&mut self, i: i32, j: i32) -> Vector2Di {
         self.base.set_cell_position(i, j)
     }
@@ -35,6 +80,10 @@ impl ScriptedCharacterLogic {
         self.base.get_position()
     }
 
+    pub fn get_id(&self) -> CharacterId {
+        self.base.id
+    }
+
     pub fn snapshot(&self) -> CharacterSnapshot {
         self.base.snapshot()
     }
@@ -71,6 +120,41 @@ impl ScriptedCharacterLogic {
     // Reset character to its initial state (position, FSM, BT blackboard)
     pub fn reset(&mut self) {
         self.base.reset();
+        self.speech = None;
+    }
+
+    pub fn save(&self) -> CharacterSave {
+        self.base.save()
+    }
+
+    pub fn restore(&mut self, save: &CharacterSave) {
+        self.base.restore(save);
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.base.inventory
+    }
+
+    /// The cell in front of the character
+    pub fn cell_ahead(&self) -> Vector2Di {
+        self.get_cell_position() + self.get_direction().to_vector()
+    }
+
+    /// Picks the item lying on the cell in front of the character and puts it into the inventory.
+    /// Returns the name of the picked item, or None if there is nothing to pick.
+    pub fn pick(&mut self) -> Option<String> {
+        let item = self.base.logic_map.objects().take_item(self.cell_ahead())?;
+        self.base.inventory.add(&item);
+        Some(item)
+    }
+
+    /// Opens the door in front of the character.
+    /// Returns false if there is no closed door or the character does not carry its key.
+    pub fn open(&mut self) -> bool {
+        self.base
+            .logic_map
+            .objects()
+            .open_door(self.cell_ahead(), &self.base.inventory)
     }
 
     pub fn set_start_cell(&mut self, cell: Vector2Di) {
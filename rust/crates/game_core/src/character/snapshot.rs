@@ -1,4 +1,7 @@
+use crate::ai::perception::PerceivedEntity;
+use crate::bt::trace::NodeTraceLog;
 use crate::bt::Blackboard;
+use crate::fsm::StateType;
 use platform::types::{Direction, Vector2D, Vector2Di};
 
 pub type CharacterId = u32;
@@ -11,6 +14,11 @@ pub struct CharacterSnapshot {
     pub direction: Direction,
     pub velocity: Vector2D,
     pub is_idle: bool,
+    pub state: Option<StateType>, // None before the first state is entered
     pub blackboard: Box<Blackboard>,
     pub current_speed: f32,
+    pub perceived: Vec<PerceivedEntity>, // characters the NPC sees or hears, filled by the World
+    pub trace: Option<NodeTraceLog>,     // set by BehaviourTree::tick_traced only
+    pub inventory: Vec<String>,          // items carried by the character
+    pub frame: u64,                      // frame of the world, set by World::player_snapshot
 }
@@ -2,7 +2,10 @@ use platform::log_debug;
 use platform::logger::LogType;
 use platform::types::{Direction, Vector2Di};
 
-use crate::executor::ExecutorResult;
+use crate::api::sensing::WorldView;
+use crate::character::scripted_character::speech_duration;
+use crate::executor::{CommandSource, Debugger, ExecutorResult, GameEvent, Variables};
+use crate::save::{ExecutorSave, Observed, SourceStep};
 use crate::StateRequest;
 use crate::{
     api::commands::{ExecutionPlayerCommand, PlayerCommand},
@@ -15,6 +18,14 @@ use std::sync::Arc;
 pub struct CommandExecutor {
     commands: VecDeque<ExecutionPlayerCommand>,
     current: Option<ExecutionPlayerCommand>,
+    source: Option<Box<dyn CommandSource>>, // step-by-step producer, polled when the queue is empty
+    last_result: Option<bool>,              // outcome of the last dispatched command
+    executed: usize,                        // number of commands dispatched since the last reset
+    source_steps: Vec<SourceStep>,          // the polls of the source, to fast-forward it on load
+    observed: Observed,                     // given to the source since the last poll
+    debugger: Debugger,
+    events: Vec<GameEvent>, // for the source, since the last `observe`
+    walking: bool,          // a move was dispatched, `Arrived` follows when it ends
 }
 
 impl CommandExecutor {
@@ -22,13 +33,28 @@ impl CommandExecutor {
         CommandExecutor {
             commands: VecDeque::new(),
             current: None,
+            source: None,
+            last_result: None,
+            executed: 0,
+            source_steps: Vec::new(),
+            observed: Observed::default(),
+            debugger: Debugger::default(),
+            events: Vec::new(),
+            walking: false,
         }
     }
 
     /// Returns the source line number of the command currently being executed,
     /// or 0 if no command has been dispatched yet.
     pub fn current_line(&self) -> usize {
-        self.current.map_or(0, |cmd| cmd.line)
+        self.current.as_ref().map_or(0, |cmd| cmd.line)
+    }
+
+    /// Returns the source column of the command currently being executed,
+    /// or 0 if no command has been dispatched yet.
+    /// Distinguishes several commands written on the same line.
+    pub fn current_column(&self) -> usize {
+        self.current.as_ref().map_or(0, |cmd| cmd.column)
     }
 
     /// Resolves the target cell for a movement step.
@@ -53,16 +79,28 @@ impl CommandExecutor {
             return ExecutorResult::Running;
         }
 
+        // The previous command is done: resume the step-by-step source to get the next one
+        if self.commands.is_empty() {
+            self.poll_source(character);
+        }
+
         let Some(exec_cmd) = self.commands.front() else {
             return ExecutorResult::Empty;
         };
 
+        if self.debugger.should_pause(exec_cmd, self.executed) {
+            // highlight the line the script waits at
+            self.current = Some(exec_cmd.clone());
+            return ExecutorResult::Paused;
+        }
+
         let cmd = &exec_cmd.command;
 
         log_debug!(
-            "[CommandExecutor]: Command: {:?} (line {}) from commands: {:?}",
+            "[CommandExecutor]: Command: {:?} (line {}:{}) from commands: {:?}",
             cmd,
             exec_cmd.line,
+            exec_cmd.column,
             self.commands
         );
 
@@ -74,17 +112,23 @@ impl CommandExecutor {
                 // from IdleState
                 let _ = character.try_transition(StateRequest::Idle);
                 character.request_state(StateRequest::Turn(direction));
-                self.current = Some(*exec_cmd);
+                self.current = Some(exec_cmd.clone());
                 return ExecutorResult::Turn;
             }
             // Already facing the right direction - walk
+            let blocked = character.try_step(&direction).is_none();
             let target = CommandExecutor::resolve_target_cell(character, &direction);
             let screen_pos = logic_map.get_screen_position(target);
             if character
                 .try_transition(StateRequest::WalkTo(screen_pos))
                 .is_ok()
             {
-                self.current = self.commands.pop_front();
+                if blocked {
+                    self.events.push(GameEvent::Blocked(direction));
+                } else {
+                    self.walking = true;
+                }
+                self.dispatched(!blocked);
             }
             return ExecutorResult::Running;
         }
@@ -93,31 +137,242 @@ impl CommandExecutor {
         match cmd {
             PlayerCommand::SetPosition(position) => {
                 character.set_cell_position(position.x, position.y);
-                self.current = self.commands.pop_front();
+                self.dispatched(true);
             }
             PlayerCommand::Wait(time) => {
                 let _ = character.try_transition(StateRequest::Wait(*time));
-                self.current = self.commands.pop_front();
+                self.dispatched(true);
+            }
+            PlayerCommand::Pick => {
+                let picked = character.pick();
+                log_debug!("[CommandExecutor]: picked {:?}", picked);
+                self.dispatched(picked.is_some());
+                if let Some(item) = picked {
+                    self.events.push(GameEvent::ItemPicked(item));
+                }
+            }
+            PlayerCommand::Open => {
+                let opened = character.open();
+                self.dispatched(opened);
+            }
+            PlayerCommand::Say(text) => {
+                // the next command waits until the speech bubble is gone
+                let duration = speech_duration(text);
+                character.say(text, duration);
+                let _ = character.try_transition(StateRequest::Wait(duration));
+                self.dispatched(true);
             }
-            PlayerCommand::Pick => todo!(),
-            PlayerCommand::Open => todo!(),
             _ => unreachable!("Directional command reached non_directional match!"),
         }
 
         ExecutorResult::Running
     }
 
+    // The front command has been handed over to the character
+    fn dispatched(&mut self, result: bool) {
+        self.current = self.commands.pop_front();
+        self.last_result = Some(result);
+        self.executed += 1;
+    }
+
+    /// Adds an event of the world for the source, e.g. an NPC which has seen the character
+    pub fn push_event(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    /// Hands the time and the events since the last call to the source.
+    /// The World calls it every tick before `tick`.
+    pub fn observe(&mut self, frame: u64, dt: f32, character: &ScriptedCharacterLogic) {
+        // the character is idle again after a move: it has arrived
+        if self.walking && character.is_idle() {
+            self.walking = false;
+            self.events
+                .push(GameEvent::Arrived(character.get_cell_position()));
+        }
+
+        let events = std::mem::take(&mut self.events);
+        if let Some(source) = self.source.as_mut() {
+            source.observe(frame, dt, &events);
+            self.observed.frame = frame;
+            self.observed.dt += dt;
+            self.observed.events.extend(events);
+        }
+    }
+
     pub fn set_commands(&mut self, commands: Vec<ExecutionPlayerCommand>) {
         self.commands.extend(commands);
     }
 
+    /// Sets a step-by-step command source (e.g. a suspended script).
+    /// The source is asked for the next command only when the queue is empty
+    /// and the character is idle again, and gets the outcome of the previous command.
+    pub fn set_source(&mut self, source: Box<dyn CommandSource>) {
+        self.source = Some(source);
+        self.last_result = None;
+        self.source_steps.clear();
+        self.observed = Observed::default();
+    }
+
+    /// Attaches a restarted source after a save game was loaded: the source observes
+    /// the same events and is polled as before the save, with the same results and the
+    /// world the character saw then, and the commands are dropped. The source has to be
+    /// deterministic: if it gives another command, or finishes, the source is not attached.
+    pub fn resume_source(
+        &mut self,
+        mut source: Box<dyn CommandSource>,
+        save: &ExecutorSave,
+        character: &ScriptedCharacterLogic,
+    ) -> Result<(), String> {
+        let Some(steps) = &save.source else {
+            return Ok(());
+        };
+        for (index, step) in steps.iter().enumerate() {
+            let observed = &step.observed;
+            for frame in observed.frame..observed.frame + step.count as u64 {
+                source.observe(frame, observed.dt, &observed.events);
+                let world = WorldView::new(character.get_logic_map(), step.cell, step.direction);
+                let command = source.replay_command(step.result, world, character);
+                if command != step.command {
+                    return Err(format!(
+                        "The restarted source gave {:?} instead of {:?} at step {} of the save",
+                        command, step.command, index
+                    ));
+                }
+                if command.is_none() && !source.is_alive() {
+                    return Err(format!(
+                        "The restarted source finished at step {} of the save",
+                        index
+                    ));
+                }
+            }
+        }
+        // the events after the last step, e.g. for a script which waits for them
+        let observed = &save.observed;
+        source.observe(observed.frame, observed.dt, &observed.events);
+        self.source = Some(source);
+        self.source_steps = steps.clone();
+        self.observed = observed.clone();
+        Ok(())
+    }
+
+    /// Returns true while a step-by-step source is attached and has not finished
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Returns the outcome of the last dispatched command: `Some(false)` if a move was blocked
+    pub fn last_result(&self) -> Option<bool> {
+        self.last_result
+    }
+
+    /// Returns the number of commands dispatched since the last reset
+    pub fn executed_commands(&self) -> usize {
+        self.executed
+    }
+
+    /// Returns true if there is nothing left to execute: the queue is empty and no source is attached
+    pub fn is_finished(&self) -> bool {
+        self.commands.is_empty() && self.source.is_none()
+    }
+
+    fn poll_source(&mut self, character: &ScriptedCharacterLogic) {
+        let Some(source) = self.source.as_mut() else {
+            return;
+        };
+
+        let last_result = self.last_result.take();
+        let command = source.next_command(last_result, character);
+        let step = SourceStep {
+            observed: std::mem::take(&mut self.observed),
+            result: last_result,
+            cell: character.get_cell_position(),
+            direction: character.get_direction(),
+            command: command.clone(),
+            count: 1,
+        };
+        match self.source_steps.last_mut() {
+            Some(last) if last.repeated_by(&step) => last.count += 1,
+            _ => self.source_steps.push(step),
+        }
+        match command {
+            Some(cmd) => self.commands.push_back(cmd),
+            None if source.is_alive() => {} // waits for the next events
+            None => self.source = None,     // the source has finished
+        }
+    }
+
     pub fn get_current_command(&self) -> Option<ExecutionPlayerCommand> {
-        self.current
+        self.current.clone()
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Breakpoints are set on the debugger directly
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debugger.is_paused()
+    }
+
+    /// Pauses before the next command
+    pub fn pause(&mut self) {
+        self.debugger.pause();
+    }
+
+    /// Continues a paused run until the next breakpoint
+    pub fn resume(&mut self) {
+        self.debugger.resume(self.executed);
+    }
+
+    /// Performs the waiting command and pauses again before the next one
+    pub fn step_over(&mut self) {
+        self.debugger.step_over(self.executed);
+    }
+
+    /// Variables of the script at the command the executor waits at
+    pub fn variables(&mut self) -> Option<Variables> {
+        self.source.as_mut()?.variables()
     }
 
     pub fn reset(&mut self) {
         self.commands.clear();
         self.current = None;
+        self.source = None;
+        self.last_result = None;
+        self.executed = 0;
+        self.source_steps.clear();
+        self.observed = Observed::default();
+        self.debugger.reset();
+        self.events.clear();
+        self.walking = false;
+    }
+
+    /// The queue and the progress of the source, the source itself is attached again
+    /// with `resume_source`
+    pub fn save(&self) -> ExecutorSave {
+        ExecutorSave {
+            commands: self.commands.iter().cloned().collect(),
+            current: self.current.clone(),
+            last_result: self.last_result,
+            executed: self.executed,
+            source: self.source.as_ref().map(|_| self.source_steps.clone()),
+            observed: self.observed.clone(),
+        }
+    }
+
+    /// Restores the queue, the source is detached
+    pub fn restore(&mut self, save: &ExecutorSave) {
+        self.commands = save.commands.iter().cloned().collect();
+        self.current = save.current.clone();
+        self.last_result = save.last_result;
+        self.executed = save.executed;
+        self.source = None;
+        self.source_steps.clear();
+        self.observed = Observed::default();
     }
 
     // apply all commands in one shot
@@ -132,6 +387,12 @@ impl CommandExecutor {
                 PlayerCommand::Wait(time) => {
                     let _ = character.try_transition(StateRequest::Wait(time));
                 }
+                PlayerCommand::Pick => {
+                    character.pick();
+                }
+                PlayerCommand::Open => {
+                    character.open();
+                }
                 _ => (),
             }
         }
@@ -143,8 +404,11 @@ mod tests {
     use super::*;
     use crate::api::commands::{ExecutionPlayerCommand, PlayerCommand};
     use crate::map::logic_map::{LogicCell, LogicMap};
+    use crate::map::WorldObject;
     use platform::types::{Direction, Vector2D, Vector2Di};
     use platform::Animator;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     fn ensure_init() {
         crate::test_utils::test_init::ensure_init();
@@ -244,7 +508,7 @@ mod tests {
     }
 
     /// Advance simulation: alternate executor ticks and character process ticks
-    /// until the character is idle and the executor command queue is empty,
+    /// until the character is idle and the executor command queue and source are empty,
     /// or until the tick budget is exhausted (to prevent infinite loops in tests).
     fn run_until_idle_or_budget(
         executor: &mut CommandExecutor,
@@ -255,7 +519,7 @@ mod tests {
         for _ in 0..max_ticks {
             executor.tick(0.016, character, map);
             character.process(0.016, map);
-            if character.is_idle() && executor.commands.is_empty() {
+            if character.is_idle() && executor.commands.is_empty() && !executor.has_source() {
                 break;
             }
         }
@@ -286,18 +550,22 @@ mod tests {
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveNorth,
                 line: 1,
+                column: 1,
             },
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveNorth,
                 line: 2,
+                column: 1,
             },
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveNorth,
                 line: 3,
+                column: 1,
             },
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveEast,
                 line: 4,
+                column: 1,
             },
         ]);
 
@@ -328,10 +596,12 @@ mod tests {
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveNorth,
                 line: 1,
+                column: 1,
             },
             ExecutionPlayerCommand {
                 command: PlayerCommand::MoveEast,
                 line: 2,
+                column: 1,
             },
         ]);
 
@@ -356,6 +626,7 @@ mod tests {
         executor.set_commands(vec![ExecutionPlayerCommand {
             command: PlayerCommand::MoveNorth,
             line: 42,
+            column: 1,
         }]);
 
         // Tick once to dispatch the command and populate `current`
@@ -387,6 +658,7 @@ mod tests {
         executor.set_commands(vec![ExecutionPlayerCommand {
             command: PlayerCommand::MoveNorth,
             line: 1,
+            column: 1,
         }]);
         run_until_idle_or_budget(&mut executor, &mut character, &map, 500);
         assert_eq!(character.get_cell_position(), Vector2Di::new(1, 1));
@@ -404,6 +676,7 @@ mod tests {
         executor.set_commands(vec![ExecutionPlayerCommand {
             command: PlayerCommand::MoveEast,
             line: 1,
+            column: 1,
         }]);
         run_until_idle_or_budget(&mut executor, &mut character, &map, 500);
 
@@ -413,4 +686,316 @@ mod tests {
             "second script should execute from reset position"
         );
     }
+
+    // --- step-by-step source tests ---
+
+    /// Emulates the script `while (step_up()) {} step_right();`
+    /// and records the results it was resumed with.
+    struct ClimbThenTurnSource {
+        results: Rc<RefCell<Vec<Option<bool>>>>,
+        turned: bool,
+    }
+
+    impl CommandSource for ClimbThenTurnSource {
+        fn next_command(
+            &mut self,
+            last_result: Option<bool>,
+            _character: &ScriptedCharacterLogic,
+        ) -> Option<ExecutionPlayerCommand> {
+            self.results.borrow_mut().push(last_result);
+
+            let command = match (last_result, self.turned) {
+                (Some(false), false) => {
+                    self.turned = true;
+                    PlayerCommand::MoveEast
+                }
+                (_, false) => PlayerCommand::MoveNorth,
+                (_, true) => return None,
+            };
+
+            Some(ExecutionPlayerCommand {
+                command,
+                line: 1,
+                column: 1,
+            })
+        }
+    }
+
+    fn make_source() -> (ClimbThenTurnSource, Rc<RefCell<Vec<Option<bool>>>>) {
+        let results = Rc::new(RefCell::new(vec![]));
+        (
+            ClimbThenTurnSource {
+                results: results.clone(),
+                turned: false,
+            },
+            results,
+        )
+    }
+
+    #[test]
+    fn test_source_reacts_to_blocked_move() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.set_direction(Direction::NORTH);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let (source, results) = make_source();
+        let mut executor = CommandExecutor::new();
+        executor.set_source(Box::new(source));
+
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 1000);
+
+        assert_eq!(character.get_cell_position(), Vector2Di::new(2, 1));
+        assert_eq!(
+            *results.borrow(),
+            vec![None, Some(true), Some(false), Some(true)],
+            "source should be resumed with the outcome of every command"
+        );
+        assert!(!executor.has_source(), "finished source should be dropped");
+    }
+
+    #[test]
+    fn test_source_not_resumed_while_character_busy() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.set_direction(Direction::NORTH);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let (source, results) = make_source();
+        let mut executor = CommandExecutor::new();
+        executor.set_source(Box::new(source));
+
+        executor.tick(0.016, &mut character, &map);
+        assert!(!character.is_idle(), "first step should be in progress");
+
+        for _ in 0..5 {
+            character.process(0.016, &map);
+            executor.tick(0.016, &mut character, &map);
+        }
+
+        assert_eq!(
+            results.borrow().len(),
+            1,
+            "source must wait until the character is idle again"
+        );
+    }
+
+    #[test]
+    fn test_executor_reset_drops_source() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let (source, _results) = make_source();
+        let mut executor = CommandExecutor::new();
+        executor.set_source(Box::new(source));
+        executor.reset();
+
+        assert!(!executor.has_source());
+        assert_eq!(
+            executor.tick(0.016, &mut character, &map),
+            ExecutorResult::Empty
+        );
+    }
+
+    /// Emulates a script with `on_blocked() { step_right(); }` which has stepped up twice,
+    /// and records the events it was given
+    struct EventSource {
+        commands: VecDeque<ExecutionPlayerCommand>,
+        events: Rc<RefCell<Vec<GameEvent>>>,
+    }
+
+    impl CommandSource for EventSource {
+        fn next_command(
+            &mut self,
+            _last_result: Option<bool>,
+            _character: &ScriptedCharacterLogic,
+        ) -> Option<ExecutionPlayerCommand> {
+            self.commands.pop_front()
+        }
+
+        fn observe(&mut self, _frame: u64, _dt: f32, events: &[GameEvent]) {
+            for event in events {
+                if let GameEvent::Blocked(_) = event {
+                    self.commands.push_back(exec(PlayerCommand::MoveEast, 2));
+                }
+            }
+            self.events.borrow_mut().extend_from_slice(events);
+        }
+
+        fn is_alive(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_source_observes_the_events() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.set_direction(Direction::NORTH);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut executor = CommandExecutor::new();
+        executor.set_source(Box::new(EventSource {
+            commands: vec![exec(PlayerCommand::MoveNorth, 1); 2].into(),
+            events: events.clone(),
+        }));
+
+        for frame in 0..1000 {
+            executor.observe(frame, 0.016, &character);
+            executor.tick(0.016, &mut character, &map);
+            character.process(0.016, &map);
+        }
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                GameEvent::Arrived(Vector2Di::new(1, 1)),
+                GameEvent::Blocked(Direction::NORTH),
+                GameEvent::Arrived(Vector2Di::new(2, 1)),
+            ]
+        );
+        assert!(executor.has_source(), "a source waiting for events is kept");
+    }
+
+    // --- Pick / Open ---
+
+    /// 3×3 map with a key at (0,1) and a locked door at (2,1)
+    fn make_map_with_objects() -> Arc<LogicMap> {
+        let mut map = (*make_3x3_map()).clone();
+        map.objects_mut()
+            .place(Vector2Di::new(0, 1), WorldObject::Item("key".to_string()));
+        map.objects_mut().place(
+            Vector2Di::new(2, 1),
+            WorldObject::Door {
+                open: false,
+                key: Some("key".to_string()),
+            },
+        );
+        Arc::new(map)
+    }
+
+    fn exec(command: PlayerCommand, line: usize) -> ExecutionPlayerCommand {
+        ExecutionPlayerCommand {
+            command,
+            line,
+            column: 1,
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_pause_step_and_resume() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let mut executor = CommandExecutor::new();
+        executor.set_commands(
+            (1..=3)
+                .map(|line| ExecutionPlayerCommand {
+                    command: PlayerCommand::Wait(0.0),
+                    line,
+                    column: 1,
+                })
+                .collect(),
+        );
+        executor.debugger_mut().set_breakpoint(2);
+
+        // stops before the command on line 2
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 50);
+        assert!(executor.is_paused());
+        assert_eq!(executor.executed_commands(), 1);
+        assert_eq!(executor.current_line(), 2);
+        assert_eq!(
+            executor.tick(0.016, &mut character, &map),
+            ExecutorResult::Paused
+        );
+
+        // performs line 2 and stops before line 3
+        executor.step_over();
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 50);
+        assert!(executor.is_paused());
+        assert_eq!(executor.executed_commands(), 2);
+        assert_eq!(executor.current_line(), 3);
+
+        executor.resume();
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 50);
+        assert!(!executor.is_paused());
+        assert_eq!(executor.executed_commands(), 3);
+
+        // the breakpoints stay for the next run
+        executor.reset();
+        assert!(executor.debugger().has_breakpoint(2));
+    }
+
+    #[test]
+    fn test_pick_key_and_open_door() {
+        let map = make_map_with_objects();
+        let mut character = make_character(1, 1, &map);
+        character.set_direction(Direction::WEST);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let mut executor = CommandExecutor::new();
+        executor.set_commands(vec![exec(PlayerCommand::Pick, 1)]);
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 100);
+        assert_eq!(executor.last_result(), Some(true));
+        assert!(character.inventory().contains("key"));
+
+        // the door is closed: the move is blocked
+        executor.set_commands(vec![exec(PlayerCommand::MoveEast, 2)]);
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 1000);
+        assert_eq!(executor.last_result(), Some(false));
+        assert_eq!(character.get_cell_position(), Vector2Di::new(1, 1));
+
+        executor.set_commands(vec![
+            exec(PlayerCommand::Open, 3),
+            exec(PlayerCommand::MoveEast, 4),
+        ]);
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 1000);
+        assert_eq!(executor.last_result(), Some(true));
+        assert_eq!(character.get_cell_position(), Vector2Di::new(2, 1));
+    }
+
+    #[test]
+    fn test_say_shows_speech_until_done() {
+        let map = make_3x3_map();
+        let mut character = make_character(1, 2, &map);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let mut executor = CommandExecutor::new();
+        executor.set_commands(vec![
+            exec(PlayerCommand::Say("Hello".to_string()), 1),
+            exec(PlayerCommand::Wait(0.0), 2),
+        ]);
+        executor.tick(0.016, &mut character, &map);
+        character.process(0.016, &map);
+        assert_eq!(character.speech().unwrap().text, "Hello");
+
+        // the next command waits for the speech
+        executor.tick(0.016, &mut character, &map);
+        assert_eq!(executor.current_line(), 1);
+
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 200);
+        assert!(character.speech().is_none());
+        assert_eq!(executor.executed_commands(), 2);
+    }
+
+    #[test]
+    fn test_pick_and_open_fail_on_empty_cell() {
+        let map = make_map_with_objects();
+        let mut character = make_character(1, 2, &map);
+        character.set_direction(Direction::EAST);
+        character.try_transition(StateRequest::Idle).unwrap();
+
+        let mut executor = CommandExecutor::new();
+        executor.set_commands(vec![exec(PlayerCommand::Pick, 1)]);
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 100);
+        assert_eq!(executor.last_result(), Some(false));
+
+        executor.set_commands(vec![exec(PlayerCommand::Open, 2)]);
+        run_until_idle_or_budget(&mut executor, &mut character, &map, 100);
+        assert_eq!(executor.last_result(), Some(false));
+        assert!(character.inventory().is_empty());
+    }
 }
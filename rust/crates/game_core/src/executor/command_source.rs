@@ -0,0 +1,55 @@
+use crate::api::commands::ExecutionPlayerCommand;
+use crate::api::sensing::WorldView;
+use crate::executor::{GameEvent, Variables};
+use crate::ScriptedCharacterLogic;
+
+/// A producer of player commands which is driven by the `CommandExecutor`
+/// one command at a time, e.g. a suspended script.
+///
+/// The executor asks for the next command only after the previous one was
+/// performed and the character came back to Idle state, so the source can
+/// react to what actually happened in the world.
+pub trait CommandSource {
+    /// Returns the next command, or `None` when the source has finished
+    /// (or has nothing to do right now, see `is_alive`).
+    ///
+    /// `last_result` is the outcome of the previously executed command:
+    /// `Some(false)` if a move was blocked, `Some(true)` if it succeeded,
+    /// `None` on the first call.
+    /// `character` is the character the commands are performed on, so the source
+    /// can observe its current cell and direction.
+    fn next_command(
+        &mut self,
+        last_result: Option<bool>,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand>;
+
+    /// Like `next_command`, for a poll of a run before a save game, replayed after loading
+    /// it. `world` is the world around the character at that poll: a source which reads
+    /// the world has to answer from it, the character is where the save left it.
+    fn replay_command(
+        &mut self,
+        last_result: Option<bool>,
+        _world: WorldView,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        self.next_command(last_result, character)
+    }
+
+    /// Returns the variables of the source where it yielded the last command,
+    /// for the debugger. `None` if the source can't be inspected.
+    fn variables(&mut self) -> Option<Variables> {
+        None
+    }
+
+    /// Called by the executor every tick, before the source may be asked for a command.
+    /// `frame` is the frame of the world, `dt` the time of the tick and `events`
+    /// what happened to the character since the last call.
+    fn observe(&mut self, _frame: u64, _dt: f32, _events: &[GameEvent]) {}
+
+    /// Whether the source may give commands later after `next_command` returned `None`,
+    /// e.g. a script waiting for events. The executor keeps such a source.
+    fn is_alive(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeSet;
+
+use crate::api::commands::ExecutionPlayerCommand;
+
+/*
+ * Debugger of the CommandExecutor: breakpoints on source lines, pause, resume and step.
+ * The executor asks the debugger before it starts a command. While the debugger is paused
+ * the command waits at the front of the queue and the script waits in the API call which
+ * yielded it, so its variables can be inspected (see CommandSource::variables).
+ */
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    paused: bool,
+    released: Option<usize>, // the command with this number runs even on a breakpoint
+    stop_after: Option<usize>, // pause before the command after the one with this number
+}
+
+impl Debugger {
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.breakpoints.remove(&line);
+    }
+
+    pub fn toggle_breakpoint(&mut self, line: usize) {
+        if !self.breakpoints.remove(&line) {
+            self.breakpoints.insert(line);
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn has_breakpoint(&self, line: usize) -> bool {
+        self.breakpoints.contains(&line)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses before the next command, the command in progress is finished
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.stop_after = None;
+    }
+
+    /// Runs until the next breakpoint
+    pub fn resume(&mut self, executed: usize) {
+        self.paused = false;
+        self.released = Some(executed);
+        self.stop_after = None;
+    }
+
+    /// Runs the waiting command and pauses before the next one
+    pub fn step_over(&mut self, executed: usize) {
+        self.resume(executed);
+        self.stop_after = Some(executed);
+    }
+
+    /// Forgets the pause state, the breakpoints are kept for the next run
+    pub fn reset(&mut self) {
+        self.paused = false;
+        self.released = None;
+        self.stop_after = None;
+    }
+
+    /// Decides if the executor waits before starting `command`,
+    /// `executed` is the number of commands dispatched before it
+    pub fn should_pause(&mut self, command: &ExecutionPlayerCommand, executed: usize) -> bool {
+        if self.paused {
+            return true;
+        }
+        if self.stop_after.is_some_and(|after| executed > after) {
+            self.pause();
+            return true;
+        }
+        if self.released == Some(executed) {
+            return false;
+        }
+        if self.breakpoints.contains(&command.line) {
+            self.pause();
+            return true;
+        }
+        false
+    }
+}
+
+/// A variable of the script, with its value as the script would print it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+}
+
+/// The variables of a paused script
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variables {
+    pub globals: Vec<Variable>,
+    pub locals: Vec<Variable>, // of the functions the script is paused in
+}
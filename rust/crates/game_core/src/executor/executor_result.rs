@@ -1,7 +1,8 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExecutorResult {
     Running,
     NotIdle,
     Empty,
     Turn,
+    Paused, // the debugger waits before the next command
 }
@@ -0,0 +1,20 @@
+use platform::types::{Direction, Vector2Di};
+use serde::{Deserialize, Serialize};
+
+use crate::character::CharacterId;
+use crate::save::remote::{DirectionDef, Vector2DiDef};
+
+/// Something that happened to the scripted character, handed to the command source
+/// with `CommandSource::observe`. A script handles it in its `on_*` hooks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    Blocked(#[serde(with = "DirectionDef")] Direction), // a move was blocked: `on_blocked(direction)`
+    Arrived(#[serde(with = "Vector2DiDef")] Vector2Di), // a move has ended on the cell: `on_arrived(cell)`
+    ItemPicked(String),                                 // `on_item_picked(item)`
+    // an NPC has started to see the character: `on_seen_by(npc)`
+    SeenBy {
+        npc: CharacterId,
+        #[serde(with = "Vector2DiDef")]
+        cell: Vector2Di,
+    },
+}
@@ -1,4 +1,10 @@
 pub mod command_executor;
+pub mod command_source;
+pub mod debugger;
 pub mod executor_result;
+pub mod game_event;
 
+pub use command_source::CommandSource;
+pub use debugger::{Debugger, Variable, Variables};
 pub use executor_result::ExecutorResult;
+pub use game_event::GameEvent;
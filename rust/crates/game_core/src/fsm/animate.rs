@@ -0,0 +1,68 @@
+use super::{SavedState, StateType, FSM};
+use crate::character::request::StateRequest;
+use crate::CharacterLogic;
+
+/// Plays a one-shot animation in the facing direction ("attack" -> "attack_south")
+/// and keeps the character in place until the animation is finished
+pub struct AnimateState {
+    animation: String,
+    can_exit: bool,
+}
+
+impl AnimateState {
+    pub fn new(animation: &str) -> Self {
+        Self {
+            animation: animation.to_string(),
+            can_exit: false,
+        }
+    }
+
+    pub fn restored(animation: &str, can_exit: bool) -> Self {
+        Self {
+            animation: animation.to_string(),
+            can_exit,
+        }
+    }
+}
+
+impl FSM for AnimateState {
+    fn get_type(&self) -> StateType {
+        StateType::ANIMATE
+    }
+
+    fn can_transition_to(&self, state_type: StateType) -> bool {
+        state_type == StateType::IDLE
+    }
+
+    fn enter(&mut self, character: &mut CharacterLogic) {
+        character.set_current_speed(0.0);
+        character.play_animation_with_direction(&self.animation);
+    }
+
+    fn exit(&self, _character: &mut CharacterLogic) {}
+
+    fn update(&mut self, _delta: f32, character: &mut CharacterLogic) {
+        if !character.is_animation_playing() {
+            self.can_exit = true;
+            character.request_state(StateRequest::Idle);
+        }
+    }
+
+    fn can_exit(&self) -> bool {
+        self.can_exit
+    }
+
+    fn save(&self) -> SavedState {
+        SavedState::Animate {
+            animation: self.animation.clone(),
+            can_exit: self.can_exit,
+        }
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        // the animation starts over, unless it has already finished
+        if !self.can_exit {
+            character.play_animation_with_direction(&self.animation);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{StateType, FSM};
+use super::{SavedState, StateType, FSM};
 use crate::CharacterLogic;
 
 pub struct IdleState {
@@ -8,7 +8,12 @@ pub struct IdleState {
 impl IdleState {
     pub fn new() -> Self {
         Self {
-            allowed_transition: vec![StateType::RUN, StateType::TURN, StateType::WAIT],
+            allowed_transition: vec![
+                StateType::RUN,
+                StateType::TURN,
+                StateType::WAIT,
+                StateType::ANIMATE,
+            ],
         }
     }
 }
@@ -34,4 +39,12 @@ impl FSM for IdleState {
     fn can_exit(&self) -> bool {
         true
     }
+
+    fn save(&self) -> SavedState {
+        SavedState::Idle
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        character.play_animation_with_direction("stand");
+    }
 }
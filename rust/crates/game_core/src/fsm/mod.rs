@@ -1,3 +1,7 @@
+use platform::types::{Direction, Vector2D};
+use serde::{Deserialize, Serialize};
+
+use crate::save::remote::{DirectionDef, Vector2DDef};
 use crate::CharacterLogic;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -6,6 +10,20 @@ pub enum StateType {
     TURN,
     IDLE,
     WAIT,
+    ANIMATE,
+}
+
+impl StateType {
+    /// Name of the state for the scripts
+    pub fn name(self) -> &'static str {
+        match self {
+            StateType::RUN => "move", // walking, running and steering
+            StateType::TURN => "turn",
+            StateType::IDLE => "idle",
+            StateType::WAIT => "wait",
+            StateType::ANIMATE => "animate",
+        }
+    }
 }
 
 pub trait FSM {
@@ -17,16 +35,92 @@ pub trait FSM {
     fn update(&mut self, delta: f32, character: &mut CharacterLogic);
 
     fn can_exit(&self) -> bool;
+
+    fn save(&self) -> SavedState; // the state with its progress, for a save game
+    fn resume(&mut self, character: &mut CharacterLogic); // restarts the animation of a loaded state
+}
+
+/// A state of the FSM with its progress, e.g. the time left to wait
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SavedState {
+    Idle,
+    Run,
+    Turn {
+        #[serde(with = "DirectionDef")]
+        from: Direction,
+        #[serde(with = "DirectionDef")]
+        target: Direction,
+        can_exit: bool,
+    },
+    Walk {
+        #[serde(with = "Vector2DDef")]
+        target: Vector2D,
+        #[serde(with = "Vector2DDef")]
+        initial_dir: Vector2D,
+        can_exit: bool,
+    },
+    Wait {
+        remaining: f32,
+        can_exit: bool,
+    },
+    Steer {
+        #[serde(with = "Vector2DDef")]
+        target: Vector2D,
+        #[serde(with = "Vector2DDef")]
+        heading: Vector2D,
+        can_exit: bool,
+    },
+    Animate {
+        animation: String,
+        can_exit: bool,
+    },
+}
+
+impl SavedState {
+    /// Builds the state as it was saved, without entering it
+    pub fn load(&self) -> Box<dyn FSM> {
+        match self {
+            SavedState::Idle => Box::new(IdleState::new()),
+            SavedState::Run => Box::new(RunState::new()),
+            SavedState::Turn {
+                from,
+                target,
+                can_exit,
+            } => Box::new(TurnState::restored(*from, *target, *can_exit)),
+            SavedState::Walk {
+                target,
+                initial_dir,
+                can_exit,
+            } => Box::new(WalkState::restored(*target, *initial_dir, *can_exit)),
+            SavedState::Wait {
+                remaining,
+                can_exit,
+            } => Box::new(WaitState::restored(*remaining, *can_exit)),
+            SavedState::Steer {
+                target,
+                heading,
+                can_exit,
+            } => Box::new(SteerState::restored(*target, *heading, *can_exit)),
+            SavedState::Animate {
+                animation,
+                can_exit,
+            } => Box::new(AnimateState::restored(animation, *can_exit)),
+        }
+    }
 }
 
+pub mod animate;
 pub mod idle;
 pub mod run;
+pub mod steer;
 pub mod turn;
 pub mod wait;
 pub mod walk;
 
+pub use animate::AnimateState;
 pub use idle::IdleState;
 pub use run::RunState;
+pub use steer::SteerState;
 pub use turn::TurnState;
 pub use wait::WaitState;
 pub use walk::WalkState;
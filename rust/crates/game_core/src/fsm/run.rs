@@ -1,4 +1,4 @@
-use super::{StateType, FSM};
+use super::{SavedState, StateType, FSM};
 use crate::CharacterLogic;
 
 pub struct RunState {
@@ -34,4 +34,12 @@ impl FSM for RunState {
     fn can_exit(&self) -> bool {
         true
     }
+
+    fn save(&self) -> SavedState {
+        SavedState::Run
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        character.play_animation_with_direction("run");
+    }
 }
@@ -0,0 +1,95 @@
+use crate::character::request::StateRequest;
+use crate::fsm::{SavedState, StateType, FSM};
+use crate::CharacterLogic;
+use platform::types::Vector2D;
+
+/// Moves the character in a straight line to the target point, not bound to the grid
+/// directions. The character faces the closest direction to the target.
+pub struct SteerState {
+    target: Vector2D,
+    heading: Vector2D, // normalized direction from start to target; set in enter()
+    can_exit: bool,
+}
+
+impl SteerState {
+    pub fn new(target: Vector2D) -> Self {
+        Self {
+            target,
+            heading: Vector2D::ZERO,
+            can_exit: false,
+        }
+    }
+
+    pub fn restored(target: Vector2D, heading: Vector2D, can_exit: bool) -> Self {
+        Self {
+            target,
+            heading,
+            can_exit,
+        }
+    }
+}
+
+impl FSM for SteerState {
+    fn get_type(&self) -> StateType {
+        StateType::RUN
+    }
+
+    fn can_transition_to(&self, state_type: StateType) -> bool {
+        state_type == StateType::IDLE
+    }
+
+    fn enter(&mut self, character: &mut CharacterLogic) {
+        let position = character.get_position();
+        self.heading = (self.target - position).normalized();
+        if self.heading == Vector2D::ZERO {
+            // Already at target — exit immediately next update
+            self.can_exit = true;
+            character.request_state(StateRequest::Idle);
+            return;
+        }
+
+        character.direction = position.direction_to(self.target);
+        character.set_heading(Some(self.heading));
+        character.set_current_speed(character.speed);
+        character.play_animation_with_direction("run");
+    }
+
+    fn exit(&self, character: &mut CharacterLogic) {
+        character.set_heading(None);
+    }
+
+    fn update(&mut self, _delta: f32, character: &mut CharacterLogic) {
+        if self.can_exit {
+            return;
+        }
+        // process() has already moved the character along the heading this frame
+        let to_target = self.target - character.get_position();
+        let dot = to_target.x * self.heading.x + to_target.y * self.heading.y;
+
+        if dot <= 0.0 {
+            character.set_position(self.target);
+            character.set_current_speed(0.0);
+            self.can_exit = true;
+            character.request_state(StateRequest::Idle);
+        }
+    }
+
+    fn can_exit(&self) -> bool {
+        self.can_exit
+    }
+
+    fn save(&self) -> SavedState {
+        SavedState::Steer {
+            target: self.target,
+            heading: self.heading,
+            can_exit: self.can_exit,
+        }
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        if self.heading != Vector2D::ZERO {
+            character.set_heading(Some(self.heading));
+        }
+        character.play_animation_with_direction("run");
+    }
+}
@@ -1,9 +1,10 @@
-use super::{StateType, FSM};
+use super::{SavedState, StateType, FSM};
 use crate::CharacterLogic;
 use platform::types::Direction;
 
 pub struct TurnState {
     allowed_transition: Vec<StateType>,
+    from: Direction, // the direction before the turn; set in enter()
     target: Direction,
     can_exit: bool,
 }
@@ -13,9 +14,18 @@ impl TurnState {
         Self {
             allowed_transition: vec![StateType::RUN, StateType::TURN, StateType::IDLE],
             can_exit: false,
+            from: target,
             target,
         }
     }
+
+    pub fn restored(from: Direction, target: Direction, can_exit: bool) -> Self {
+        Self {
+            can_exit,
+            from,
+            ..Self::new(target)
+        }
+    }
 }
 
 impl FSM for TurnState {
@@ -34,6 +44,7 @@ impl FSM for TurnState {
             character.request_state(crate::StateRequest::Idle);
         } else {
             //start play turn animation to provided direction
+            self.from = character.direction;
             let animation = format!("turn_{}_{}", character.direction, self.target);
             character.play_animation(&animation);
             character.direction = self.target;
@@ -53,4 +64,22 @@ impl FSM for TurnState {
     fn can_exit(&self) -> bool {
         self.can_exit
     }
+
+    fn save(&self) -> SavedState {
+        SavedState::Turn {
+            from: self.from,
+            target: self.target,
+            can_exit: self.can_exit,
+        }
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        // the Idle state is already requested when the turn is over
+        if self.can_exit || self.from == self.target {
+            character.play_animation_with_direction("stand");
+        } else {
+            let animation = format!("turn_{}_{}", self.from, self.target);
+            character.play_animation(&animation);
+        }
+    }
 }
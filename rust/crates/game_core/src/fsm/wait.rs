@@ -1,4 +1,4 @@
-use super::{StateType, FSM};
+use super::{SavedState, StateType, FSM};
 use crate::character::request::StateRequest;
 use crate::CharacterLogic;
 
@@ -14,6 +14,13 @@ impl WaitState {
             can_exit: false,
         }
     }
+
+    pub fn restored(remaining_ms: f32, can_exit: bool) -> Self {
+        Self {
+            remaining_ms,
+            can_exit,
+        }
+    }
 }
 
 impl FSM for WaitState {
@@ -44,4 +51,15 @@ impl FSM for WaitState {
     fn can_exit(&self) -> bool {
         self.can_exit
     }
+
+    fn save(&self) -> SavedState {
+        SavedState::Wait {
+            remaining: self.remaining_ms,
+            can_exit: self.can_exit,
+        }
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        character.play_animation_with_direction("stand");
+    }
 }
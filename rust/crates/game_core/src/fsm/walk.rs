@@ -1,5 +1,5 @@
 use crate::character::request::StateRequest;
-use crate::fsm::{StateType, FSM};
+use crate::fsm::{SavedState, StateType, FSM};
 use crate::CharacterLogic;
 use platform::types::Vector2D;
 
@@ -17,6 +17,14 @@ impl WalkState {
             can_exit: false,
         }
     }
+
+    pub fn restored(target: Vector2D, initial_dir: Vector2D, can_exit: bool) -> Self {
+        Self {
+            target,
+            initial_dir,
+            can_exit,
+        }
+    }
 }
 
 impl FSM for WalkState {
@@ -64,4 +72,16 @@ impl FSM for WalkState {
     fn can_exit(&self) -> bool {
         self.can_exit
     }
+
+    fn save(&self) -> SavedState {
+        SavedState::Walk {
+            target: self.target,
+            initial_dir: self.initial_dir,
+            can_exit: self.can_exit,
+        }
+    }
+
+    fn resume(&mut self, character: &mut CharacterLogic) {
+        character.play_animation_with_direction("run");
+    }
 }
@@ -0,0 +1,377 @@
+use platform::types::Vector2Di;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::perception::{EntityKind, PerceivedEntity};
+use crate::level::objective::{Level, Objective};
+use crate::map::WorldObject;
+use crate::save::LevelSave;
+use crate::{CommandExecutor, ScriptedCharacterLogic};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LevelStatus {
+    InProgress,
+    Success,
+    Failed(String), // the reason shown to the player
+}
+
+/// State of one objective, used to show the progress to the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectiveProgress {
+    pub objective: Objective,
+    pub current: usize,
+    pub target: usize,
+    pub done: bool,
+    pub failed: bool,
+}
+
+/*
+ * Observes the scripted character and the command executor each tick
+ * and decides if the level is solved or lost.
+ * The level is solved once all goals are completed without violating a constraint.
+ * A completed goal stays completed (e.g. the character may leave the reached cell).
+ * The result is final: after Success or Failed the evaluator does not change its status until reset.
+ */
+pub struct LevelEvaluator {
+    objectives: Vec<Objective>,
+    completed: Vec<bool>,
+    elapsed: f32,
+    detected: bool,
+    status: LevelStatus,
+}
+
+impl LevelEvaluator {
+    pub fn new(objectives: Vec<Objective>) -> Self {
+        let completed = vec![false; objectives.len()];
+        Self {
+            objectives,
+            completed,
+            elapsed: 0.0,
+            detected: false,
+            status: LevelStatus::InProgress,
+        }
+    }
+
+    pub fn from_level(level: &Level) -> Self {
+        Self::new(level.objectives.clone())
+    }
+
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+
+    pub fn status(&self) -> &LevelStatus {
+        &self.status
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn reset(&mut self) {
+        self.completed.iter_mut().for_each(|done| *done = false);
+        self.elapsed = 0.0;
+        self.detected = false;
+        self.status = LevelStatus::InProgress;
+    }
+
+    pub fn save(&self) -> LevelSave {
+        LevelSave {
+            completed: self.completed.clone(),
+            elapsed: self.elapsed,
+            detected: self.detected,
+            status: self.status.clone(),
+        }
+    }
+
+    /// Restores the progress saved for the same objectives
+    pub fn restore(&mut self, save: &LevelSave) -> Result<(), String> {
+        if save.completed.len() != self.objectives.len() {
+            return Err(format!(
+                "The save has {} objectives, the level has {}",
+                save.completed.len(),
+                self.objectives.len()
+            ));
+        }
+        self.completed = save.completed.clone();
+        self.elapsed = save.elapsed;
+        self.detected = save.detected;
+        self.status = save.status.clone();
+        Ok(())
+    }
+
+    /// Marks the scripted character as spotted by an NPC
+    pub fn notify_detected(&mut self) {
+        self.detected = true;
+    }
+
+    /// Checks what an NPC standing on `npc` has perceived in this frame: the scripted
+    /// character is spotted if the NPC sees it no farther than `AvoidDetection::range` cells.
+    /// The NPC sees with its Perception, the same sight as for FindTarget and `on_seen_by`.
+    pub fn observe_npc(&mut self, npc: Vector2Di, perceived: &[PerceivedEntity]) {
+        let Some(range) = self
+            .objectives
+            .iter()
+            .find_map(|objective| match objective {
+                Objective::AvoidDetection { range } => Some(*range),
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        let spotted = perceived.iter().any(|entity| {
+            let offset = entity.cell - npc;
+            entity.kind == EntityKind::Player
+                && entity.seen
+                && offset.x.abs().max(offset.y.abs()) <= range
+        });
+        if spotted {
+            self.notify_detected();
+        }
+    }
+
+    pub fn tick(
+        &mut self,
+        delta: f32,
+        character: &ScriptedCharacterLogic,
+        executor: &CommandExecutor,
+    ) -> LevelStatus {
+        if self.status != LevelStatus::InProgress {
+            return self.status.clone();
+        }
+
+        self.elapsed += delta;
+
+        let progress = self.progress(character, executor);
+        for (completed, objective) in self.completed.iter_mut().zip(progress.iter()) {
+            *completed |= objective.done && !objective.objective.is_constraint();
+        }
+
+        if let Some(failed) = progress.iter().find(|p| p.failed) {
+            self.status = LevelStatus::Failed(failure_reason(&failed.objective));
+        } else if progress.iter().any(|p| !p.objective.is_constraint())
+            && progress
+                .iter()
+                .all(|p| p.objective.is_constraint() || p.done)
+        {
+            self.status = LevelStatus::Success;
+        }
+
+        self.status.clone()
+    }
+
+    /// Returns the current state of every objective
+    pub fn progress(
+        &self,
+        character: &ScriptedCharacterLogic,
+        executor: &CommandExecutor,
+    ) -> Vec<ObjectiveProgress> {
+        self.objectives
+            .iter()
+            .zip(self.completed.iter())
+            .map(|(objective, completed)| {
+                let (current, target, failed) = match objective {
+                    Objective::ReachCell { x, y } => {
+                        let reached = character.get_cell_position() == Vector2Di::new(*x, *y);
+                        (reached as usize, 1, false)
+                    }
+                    Objective::CollectItems { item, count } => {
+                        (character.inventory().count(item).min(*count), *count, false)
+                    }
+                    Objective::OpenDoor { x, y } => {
+                        let opened = matches!(
+                            character
+                                .get_logic_map()
+                                .objects()
+                                .get(Vector2Di::new(*x, *y)),
+                            Some(WorldObject::Door { open: true, .. })
+                        );
+                        (opened as usize, 1, false)
+                    }
+                    Objective::AvoidDetection { .. } => (0, 0, self.detected),
+                    Objective::MaxCommands(max) => {
+                        let executed = executor.executed_commands();
+                        (executed, *max, executed > *max)
+                    }
+                    Objective::TimeLimit(time) => {
+                        (self.elapsed as usize, *time as usize, self.elapsed > *time)
+                    }
+                };
+
+                ObjectiveProgress {
+                    objective: objective.clone(),
+                    current,
+                    target,
+                    done: *completed || (!failed && current >= target),
+                    failed,
+                }
+            })
+            .collect()
+    }
+}
+
+fn failure_reason(objective: &Objective) -> String {
+    match objective {
+        Objective::AvoidDetection { .. } => "You have been spotted!".to_string(),
+        Objective::MaxCommands(max) => format!("Too many commands: the limit is {}", max),
+        Objective::TimeLimit(time) => format!("Time is over: the limit is {} seconds", time),
+        _ => format!("Objective failed: {}", objective),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::commands::{ExecutionPlayerCommand, PlayerCommand};
+    use crate::map::logic_map::LogicCell;
+    use crate::map::{LogicMap, StepType};
+    use crate::test_utils::test_world::{animator, flat_map};
+    use platform::types::{Direction, Vector2D};
+    use std::sync::Arc;
+
+    /// 4×1 corridor, walkable, with a key at (2,0) and a wall at (3,0)
+    fn make_map() -> Arc<LogicMap> {
+        let mut map = flat_map(4, 1);
+        map.set_cell(
+            3,
+            0,
+            Some(LogicCell {
+                walkable: false,
+                height: 0,
+                step_type: StepType::None,
+            }),
+        );
+        map.objects_mut()
+            .place(Vector2Di::new(2, 0), WorldObject::Item("key".to_string()));
+        Arc::new(map)
+    }
+
+    fn make_character(map: &Arc<LogicMap>) -> ScriptedCharacterLogic {
+        crate::test_utils::test_init::ensure_init();
+        let mut ch = ScriptedCharacterLogic::new(1, animator());
+        ch.set_logic_map(map.clone());
+        ch.set_cell_position(0, 0);
+        ch
+    }
+
+    #[test]
+    fn test_success_after_all_goals() {
+        let map = make_map();
+        let mut character = make_character(&map);
+        let executor = CommandExecutor::new();
+        let mut evaluator = LevelEvaluator::new(vec![
+            Objective::ReachCell { x: 1, y: 0 },
+            Objective::CollectItems {
+                item: "key".to_string(),
+                count: 1,
+            },
+        ]);
+
+        assert_eq!(
+            evaluator.tick(0.1, &character, &executor),
+            LevelStatus::InProgress
+        );
+
+        character.set_cell_position(1, 0);
+        character.set_direction(Direction::EAST);
+        assert_eq!(
+            evaluator.tick(0.1, &character, &executor),
+            LevelStatus::InProgress
+        );
+        let progress = evaluator.progress(&character, &executor);
+        assert!(progress[0].done);
+        assert_eq!((progress[1].current, progress[1].target), (0, 1));
+
+        character.pick();
+        assert_eq!(
+            evaluator.tick(0.1, &character, &executor),
+            LevelStatus::Success
+        );
+    }
+
+    #[test]
+    fn test_time_limit_fails_level() {
+        let map = make_map();
+        let character = make_character(&map);
+        let executor = CommandExecutor::new();
+        let mut evaluator = LevelEvaluator::new(vec![
+            Objective::ReachCell { x: 2, y: 0 },
+            Objective::TimeLimit(1.0),
+        ]);
+
+        evaluator.tick(0.6, &character, &executor);
+        assert!(matches!(
+            evaluator.tick(0.6, &character, &executor),
+            LevelStatus::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_max_commands_fails_level() {
+        let map = make_map();
+        let mut character = make_character(&map);
+        character.try_transition(crate::StateRequest::Idle).unwrap();
+        let mut executor = CommandExecutor::new();
+        executor.set_commands(vec![
+            ExecutionPlayerCommand {
+                command: PlayerCommand::Wait(0.0),
+                line: 1,
+                column: 1,
+            };
+            2
+        ]);
+        let mut evaluator = LevelEvaluator::new(vec![
+            Objective::ReachCell { x: 2, y: 0 },
+            Objective::MaxCommands(1),
+        ]);
+
+        for _ in 0..10 {
+            executor.tick(0.016, &mut character, &map);
+            character.process(0.016, &map);
+        }
+        assert_eq!(executor.executed_commands(), 2);
+        assert!(matches!(
+            evaluator.tick(0.016, &character, &executor),
+            LevelStatus::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_spotted_within_range() {
+        let map = make_map();
+        let character = make_character(&map);
+        let executor = CommandExecutor::new();
+        let mut evaluator = LevelEvaluator::new(vec![
+            Objective::ReachCell { x: 2, y: 0 },
+            Objective::AvoidDetection { range: 2 },
+        ]);
+
+        let mut player = PerceivedEntity {
+            id: 1,
+            kind: EntityKind::Player,
+            cell: Vector2Di::new(3, 0),
+            position: Vector2D::ZERO,
+            seen: false,
+            heard: true,
+        };
+        // heard only
+        evaluator.observe_npc(Vector2Di::new(0, 0), std::slice::from_ref(&player));
+        // seen, but farther than the range
+        player.seen = true;
+        evaluator.observe_npc(Vector2Di::new(0, 0), std::slice::from_ref(&player));
+        assert_eq!(
+            evaluator.tick(0.1, &character, &executor),
+            LevelStatus::InProgress
+        );
+
+        evaluator.observe_npc(Vector2Di::new(1, 1), &[player]);
+        assert_eq!(
+            evaluator.tick(0.1, &character, &executor),
+            LevelStatus::Failed("You have been spotted!".to_string())
+        );
+
+        // the result is final until reset
+        evaluator.reset();
+        assert_eq!(evaluator.status(), &LevelStatus::InProgress);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod evaluator;
+pub mod objective;
+
+pub use evaluator::{LevelEvaluator, LevelStatus, ObjectiveProgress};
+pub use objective::{Level, Objective};
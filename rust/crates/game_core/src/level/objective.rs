@@ -0,0 +1,85 @@
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+
+use crate::bt::TreeDescription;
+
+/// A single objective of a level.
+/// Goals (`ReachCell`, `CollectItems`, `OpenDoor`) have to be completed to solve the level,
+/// constraints (`AvoidDetection`, `MaxCommands`, `TimeLimit`) fail the level once violated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    ReachCell {
+        x: i32,
+        y: i32,
+    },
+    CollectItems {
+        item: String,
+        count: usize,
+    },
+    OpenDoor {
+        x: i32,
+        y: i32,
+    },
+    /// Stay out of sight of the NPCs. The goblin is spotted when an NPC sees it (see Perception)
+    /// no farther than `range` cells
+    AvoidDetection {
+        range: i32,
+    },
+    /// Solve the level with at most the given number of executed commands
+    MaxCommands(usize),
+    /// Solve the level within the given time in seconds
+    TimeLimit(f32),
+}
+
+impl Objective {
+    pub fn is_constraint(&self) -> bool {
+        matches!(
+            self,
+            Objective::AvoidDetection { .. } | Objective::MaxCommands(_) | Objective::TimeLimit(_)
+        )
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::ReachCell { x, y } => write!(f, "Reach cell ({}, {})", x, y),
+            Objective::CollectItems { item, count } => write!(f, "Collect {} x {}", count, item),
+            Objective::OpenDoor { x, y } => write!(f, "Open the door at ({}, {})", x, y),
+            Objective::AvoidDetection { .. } => write!(f, "Do not get spotted"),
+            Objective::MaxCommands(max) => write!(f, "Use at most {} commands", max),
+            Objective::TimeLimit(time) => write!(f, "Finish within {} seconds", time),
+        }
+    }
+}
+
+/// Level description: the list of objectives, loaded from a RON file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    pub objectives: Vec<Objective>,
+    /// Behaviour trees of the NPCs by name, an NPC selects its tree with the "behaviour" meta
+    #[serde(default)]
+    pub behaviours: HashMap<String, TreeDescription>,
+}
+
+impl Level {
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(filename)?;
+        let ron_string = to_string_pretty(self, PrettyConfig::default())?;
+        file.write_all(ron_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(filename)?;
+        let level: Level = ron::from_str(&content)?;
+
+        Ok(level)
+    }
+}
@@ -9,8 +9,12 @@ pub mod bt;
 pub mod character;
 pub mod executor;
 pub mod fsm;
+pub mod level;
 pub mod map;
+pub mod replay;
+pub mod save;
 pub mod test_utils;
+pub mod world;
 
 /*
  * Public re-export
@@ -29,3 +33,6 @@ pub use bt::NodeStatus;
 
 //CommandExecutor
 pub use executor::command_executor::CommandExecutor;
+
+//Simulation
+pub use world::World;
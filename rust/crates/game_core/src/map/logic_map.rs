@@ -5,6 +5,8 @@ use std::io::Write;
 
 use platform::types::{Vector2D, Vector2Di};
 
+use crate::map::objects::ObjectLayer;
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub enum StepType {
     None,
@@ -25,6 +27,8 @@ pub struct LogicMap {
     pub width: usize,
     pub height: usize,
     cell_size: f32,
+    #[serde(default)]
+    objects: ObjectLayer, // items and doors placed on the map
 }
 
 impl LogicMap {
@@ -34,6 +38,7 @@ impl LogicMap {
             width,
             height,
             cell_size: 64.0,
+            objects: ObjectLayer::new(),
         }
     }
 
@@ -57,6 +62,14 @@ impl LogicMap {
         }
     }
 
+    pub fn objects(&self) -> &ObjectLayer {
+        &self.objects
+    }
+
+    pub fn objects_mut(&mut self) -> &mut ObjectLayer {
+        &mut self.objects
+    }
+
     pub fn is_walkable(&self, i: i32, j: i32) -> bool {
         let offset = match self.get_data_offset(i, j) {
             Some(val) => val,
@@ -81,6 +94,10 @@ impl LogicMap {
             return false;
         }
 
+        if self.objects.is_closed_door(to_position) {
+            return false;
+        }
+
         if self.get_step_type(from_position) != StepType::None
             && self.get_step_type(to_position) != StepType::None
         {
@@ -224,4 +241,25 @@ mod tests {
         let flat = Vector2Di::new(0, 0);
         assert!(map.get_step_type(flat) == StepType::None);
     }
+
+    #[test]
+    fn test_closed_door_blocks_movement() {
+        use crate::map::objects::WorldObject;
+
+        let mut map = make_step_map();
+        let from = Vector2Di::new(0, 2);
+        let door = Vector2Di::new(1, 2);
+        map.objects_mut().place(
+            door,
+            WorldObject::Door {
+                open: false,
+                key: None,
+            },
+        );
+        assert!(!map.is_walkable_from(from, door));
+
+        map.objects()
+            .open_door(door, &crate::character::inventory::Inventory::new());
+        assert!(map.is_walkable_from(from, door));
+    }
 }
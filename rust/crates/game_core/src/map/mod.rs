@@ -1,5 +1,8 @@
 pub mod logic_map;
+pub mod objects;
+pub mod pathfinding;
 
 pub use logic_map::LogicCell;
 pub use logic_map::LogicMap;
 pub use logic_map::StepType;
+pub use objects::{ObjectLayer, PlacedObject, WorldObject};
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+use platform::types::Vector2Di;
+
+use crate::character::inventory::Inventory;
+
+/// An object placed on a cell of the map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WorldObject {
+    /// An item lying on the ground, which can be picked up
+    Item(String),
+    /// A door or a gate. A closed door blocks the cell.
+    /// If `key` is set, the door can be opened only with that item in the inventory.
+    Door { open: bool, key: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacedObject {
+    pub x: i32,
+    pub y: i32,
+    pub object: WorldObject,
+}
+
+impl PlacedObject {
+    pub fn cell(&self) -> Vector2Di {
+        Vector2Di::new(self.x, self.y)
+    }
+}
+
+/*
+ * Objects lying on the map.
+ * The map is shared between characters as Arc<LogicMap>, so the current state of the objects
+ * is kept behind a RwLock: scripted characters pick items and open doors during the game.
+ * The initial placement is kept separately to restore the level on reset.
+ * Only the initial placement is serialized.
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<PlacedObject>", into = "Vec<PlacedObject>")]
+pub struct ObjectLayer {
+    initial: Vec<PlacedObject>,
+    current: RwLock<Vec<PlacedObject>>,
+}
+
+impl ObjectLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Places an object on the cell during the level setup.
+    /// Replaces an object which was already placed on that cell.
+    pub fn place(&mut self, cell: Vector2Di, object: WorldObject) {
+        self.initial.retain(|placed| placed.cell() != cell);
+        self.initial.push(PlacedObject {
+            x: cell.x,
+            y: cell.y,
+            object,
+        });
+        self.reset();
+    }
+
+    /// Restores the initial placement of the objects
+    pub fn reset(&self) {
+        if let Ok(mut current) = self.current.write() {
+            *current = self.initial.clone();
+        }
+    }
+
+    /// Replaces the current state of the objects, e.g. when a save game is loaded.
+    /// The initial placement is kept for the reset.
+    pub fn restore(&self, objects: Vec<PlacedObject>) {
+        if let Ok(mut current) = self.current.write() {
+            *current = objects;
+        }
+    }
+
+    /// Returns a copy of the object on the cell
+    pub fn get(&self, cell: Vector2Di) -> Option<WorldObject> {
+        let current = self.current.read().ok()?;
+        current
+            .iter()
+            .find(|placed| placed.cell() == cell)
+            .map(|placed| placed.object.clone())
+    }
+
+    /// Returns a copy of all objects in their current state
+    pub fn objects(&self) -> Vec<PlacedObject> {
+        self.current
+            .read()
+            .map(|current| current.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_closed_door(&self, cell: Vector2Di) -> bool {
+        matches!(self.get(cell), Some(WorldObject::Door { open: false, .. }))
+    }
+
+    /// Removes the item from the cell and returns its name.
+    /// Returns None if there is no item on the cell.
+    pub fn take_item(&self, cell: Vector2Di) -> Option<String> {
+        let mut current = self.current.write().ok()?;
+        let index = current.iter().position(|placed| {
+            placed.cell() == cell && matches!(placed.object, WorldObject::Item(_))
+        })?;
+        match current.remove(index).object {
+            WorldObject::Item(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Opens the closed door on the cell.
+    /// Returns false if there is no closed door, or the door needs a key which is not in the inventory.
+    pub fn open_door(&self, cell: Vector2Di, inventory: &Inventory) -> bool {
+        let Ok(mut current) = self.current.write() else {
+            return false;
+        };
+        let Some(placed) = current.iter_mut().find(|placed| placed.cell() == cell) else {
+            return false;
+        };
+
+        match &mut placed.object {
+            WorldObject::Door { open, key } if !*open => {
+                if key.as_ref().is_some_and(|key| !inventory.contains(key)) {
+                    return false;
+                }
+                *open = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Clone for ObjectLayer {
+    fn clone(&self) -> Self {
+        Self {
+            initial: self.initial.clone(),
+            current: RwLock::new(self.objects()),
+        }
+    }
+}
+
+impl From<Vec<PlacedObject>> for ObjectLayer {
+    fn from(initial: Vec<PlacedObject>) -> Self {
+        Self {
+            current: RwLock::new(initial.clone()),
+            initial,
+        }
+    }
+}
+
+impl From<ObjectLayer> for Vec<PlacedObject> {
+    fn from(layer: ObjectLayer) -> Self {
+        layer.initial
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_layer() -> ObjectLayer {
+        let mut layer = ObjectLayer::new();
+        layer.place(Vector2Di::new(1, 1), WorldObject::Item("key".to_string()));
+        layer.place(
+            Vector2Di::new(2, 1),
+            WorldObject::Door {
+                open: false,
+                key: Some("key".to_string()),
+            },
+        );
+        layer
+    }
+
+    #[test]
+    fn test_take_item_once() {
+        let layer = make_layer();
+        assert_eq!(
+            layer.take_item(Vector2Di::new(1, 1)),
+            Some("key".to_string())
+        );
+        assert_eq!(layer.take_item(Vector2Di::new(1, 1)), None);
+        // a door is not an item
+        assert_eq!(layer.take_item(Vector2Di::new(2, 1)), None);
+    }
+
+    #[test]
+    fn test_locked_door_needs_key() {
+        let layer = make_layer();
+        let mut inventory = Inventory::new();
+        assert!(layer.is_closed_door(Vector2Di::new(2, 1)));
+        assert!(!layer.open_door(Vector2Di::new(2, 1), &inventory));
+
+        inventory.add("key");
+        assert!(layer.open_door(Vector2Di::new(2, 1), &inventory));
+        assert!(!layer.is_closed_door(Vector2Di::new(2, 1)));
+        // already open
+        assert!(!layer.open_door(Vector2Di::new(2, 1), &inventory));
+    }
+
+    #[test]
+    fn test_reset_restores_initial_objects() {
+        let layer = make_layer();
+        layer.take_item(Vector2Di::new(1, 1));
+        layer.reset();
+        assert_eq!(
+            layer.get(Vector2Di::new(1, 1)),
+            Some(WorldObject::Item("key".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_serialize_keeps_initial_placement() {
+        let layer = make_layer();
+        layer.take_item(Vector2Di::new(1, 1));
+
+        let text = ron::to_string(&layer).unwrap();
+        let restored: ObjectLayer = ron::from_str(&text).unwrap();
+        assert_eq!(restored.objects().len(), 2);
+    }
+}
@@ -0,0 +1,290 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use platform::types::{Direction, Vector2Di};
+
+use crate::map::{LogicMap, StepType};
+use crate::CharacterLogic;
+
+// Order of the neighbour expansion, keeps the found path stable between runs
+const DIRECTIONS: [Direction; 4] = [
+    Direction::NORTH,
+    Direction::EAST,
+    Direction::SOUTH,
+    Direction::WEST,
+];
+
+/*
+ * A* pathfinding over the logic map.
+ * A step of the path is the same move a character makes with one Move command:
+ * the next cell has to be walkable from the current one (same height, no closed door),
+ * and a staircase is traversed in a single step up to the cell where it ends.
+ * The cost of a step is the number of cells it passes, so a long staircase is not a shortcut.
+ */
+impl LogicMap {
+    /// Follows the stairs starting at `cell` in the given direction.
+    /// Returns the cell where the stairs end, or `cell` itself if it has no stairs
+    /// or the stairs cannot be traversed in that direction (e.g. NORTH/SOUTH).
+    pub fn resolve_stairs(&self, cell: Vector2Di, direction: &Direction) -> Vector2Di {
+        let mut cell = cell;
+        loop {
+            let step_type = self.get_step_type(cell);
+            if step_type == StepType::None {
+                return cell;
+            }
+            match CharacterLogic::get_steps_offset_vector(step_type, direction) {
+                Some(offset) => cell = cell + offset,
+                None => return cell,
+            }
+        }
+    }
+
+    /// Finds the shortest path between two cells.
+    /// Returns the cells to step on, excluding `from` and ending with `to`:
+    /// an empty path if both cells are the same, None if `to` can't be reached.
+    pub fn find_path(&self, from: Vector2Di, to: Vector2Di) -> Option<Vec<Vector2Di>> {
+        if from == to {
+            return Some(vec![]);
+        }
+        if !self.is_in_bounds(from) || !self.is_walkable(to.x, to.y) {
+            return None;
+        }
+
+        let size = self.width * self.height;
+        let mut cost = vec![u32::MAX; size];
+        let mut came_from: Vec<Option<usize>> = vec![None; size];
+        let mut open = BinaryHeap::new();
+
+        let start = self.cell_index(from);
+        let goal = self.cell_index(to);
+        cost[start] = 0;
+        open.push(Reverse((distance(from, to), 0, start)));
+
+        while let Some(Reverse((_, current_cost, index))) = open.pop() {
+            if index == goal {
+                return Some(self.build_path(&came_from, goal));
+            }
+            // a shorter way to this cell was found after it was queued
+            if current_cost > cost[index] {
+                continue;
+            }
+
+            let cell = self.index_cell(index);
+            for direction in DIRECTIONS.iter() {
+                let Some(next) = self.path_step(cell, direction) else {
+                    continue;
+                };
+                let next_index = self.cell_index(next);
+                let next_cost = current_cost + distance(cell, next);
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    came_from[next_index] = Some(index);
+                    open.push(Reverse((
+                        next_cost + distance(next, to),
+                        next_cost,
+                        next_index,
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+
+    // Cell reached by one step from `cell`, or None if the step is blocked
+    fn path_step(&self, cell: Vector2Di, direction: &Direction) -> Option<Vector2Di> {
+        let next = cell + direction.to_vector();
+        if !self.is_walkable_from(cell, next) {
+            return None;
+        }
+
+        let target = self.resolve_stairs(next, direction);
+        // the stairs may end in front of a wall or a closed door
+        if target != next
+            && (!self.is_walkable(target.x, target.y) || self.objects().is_closed_door(target))
+        {
+            return None;
+        }
+        Some(target)
+    }
+
+    fn build_path(&self, came_from: &[Option<usize>], goal: usize) -> Vec<Vector2Di> {
+        let mut path = vec![self.index_cell(goal)];
+        let mut index = goal;
+        while let Some(previous) = came_from[index] {
+            path.push(self.index_cell(previous));
+            index = previous;
+        }
+        // drop the start cell
+        path.pop();
+        path.reverse();
+        path
+    }
+
+    fn is_in_bounds(&self, cell: Vector2Di) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.width as i32 && cell.y < self.height as i32
+    }
+
+    fn cell_index(&self, cell: Vector2Di) -> usize {
+        cell.y as usize * self.width + cell.x as usize
+    }
+
+    fn index_cell(&self, index: usize) -> Vector2Di {
+        Vector2Di::new((index % self.width) as i32, (index / self.width) as i32)
+    }
+}
+
+// Manhattan distance, never overestimates the cost of the remaining path
+fn distance(a: Vector2Di, b: Vector2Di) -> u32 {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::logic_map::LogicCell;
+    use crate::map::WorldObject;
+    use crate::test_utils::test_world::flat_map;
+
+    fn cell(height: i32, step_type: StepType) -> Option<LogicCell> {
+        Some(LogicCell {
+            walkable: true,
+            height,
+            step_type,
+        })
+    }
+
+    /// 5×4 flat map with a wall in column 2, open only at row 3:
+    /// ```text
+    /// . . # . .
+    /// . . # . .
+    /// . . # . .
+    /// . . . . .
+    /// ```
+    fn make_wall_map() -> LogicMap {
+        let mut map = flat_map(5, 4);
+        for j in 0..3 {
+            map.set_cell(
+                2,
+                j,
+                Some(LogicCell {
+                    walkable: false,
+                    height: 0,
+                    step_type: StepType::None,
+                }),
+            );
+        }
+        map
+    }
+
+    /// 6×2 map, the upper level (h=1) is reachable only by the stairs in column 2:
+    /// ```text
+    /// Row 0: flat(h=0) flat(h=0) step(h=1) flat(h=1) flat(h=1) flat(h=1)
+    /// Row 1: flat(h=0) flat(h=0) step(h=0) flat(h=1) flat(h=1) flat(h=1)
+    /// ```
+    fn make_stairs_map() -> LogicMap {
+        let mut map = LogicMap::new(6, 2);
+        for j in 0..2 {
+            for i in 0..6 {
+                let value = match (i, j) {
+                    (0 | 1, _) => cell(0, StepType::None),
+                    (2, 0) => cell(1, StepType::Left),
+                    (2, 1) => cell(0, StepType::Left),
+                    _ => cell(1, StepType::None),
+                };
+                map.set_cell(i, j, value);
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn test_straight_path() {
+        let map = make_wall_map();
+        let path = map
+            .find_path(Vector2Di::new(0, 0), Vector2Di::new(0, 2))
+            .unwrap();
+        assert_eq!(path, vec![Vector2Di::new(0, 1), Vector2Di::new(0, 2)]);
+
+        assert_eq!(
+            map.find_path(Vector2Di::new(1, 1), Vector2Di::new(1, 1)),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_path_around_wall() {
+        let map = make_wall_map();
+        let path = map
+            .find_path(Vector2Di::new(1, 0), Vector2Di::new(3, 0))
+            .unwrap();
+        // down to the gap, through it and up again
+        assert_eq!(path.len(), 8);
+        assert!(path.contains(&Vector2Di::new(2, 3)));
+        assert_eq!(path.last(), Some(&Vector2Di::new(3, 0)));
+        assert!(path.iter().all(|cell| map.is_walkable(cell.x, cell.y)));
+    }
+
+    #[test]
+    fn test_closed_door_blocks_path() {
+        let mut map = make_wall_map();
+        map.objects_mut().place(
+            Vector2Di::new(2, 3),
+            WorldObject::Door {
+                open: false,
+                key: None,
+            },
+        );
+        assert_eq!(
+            map.find_path(Vector2Di::new(1, 0), Vector2Di::new(3, 0)),
+            None
+        );
+        // a wall cell can't be the goal
+        assert_eq!(
+            map.find_path(Vector2Di::new(1, 0), Vector2Di::new(2, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_path_over_stairs() {
+        let map = make_stairs_map();
+
+        // the upper level is not walkable from the lower one without stairs
+        let path = map
+            .find_path(Vector2Di::new(0, 0), Vector2Di::new(4, 0))
+            .unwrap();
+        assert_eq!(
+            path,
+            vec![
+                Vector2Di::new(1, 0),
+                Vector2Di::new(1, 1),
+                Vector2Di::new(3, 0),
+                Vector2Di::new(4, 0),
+            ]
+        );
+
+        // and back down
+        let path = map
+            .find_path(Vector2Di::new(3, 0), Vector2Di::new(1, 1))
+            .unwrap();
+        assert_eq!(path, vec![Vector2Di::new(1, 1)]);
+    }
+
+    #[test]
+    fn test_resolve_stairs() {
+        let map = make_stairs_map();
+        assert_eq!(
+            map.resolve_stairs(Vector2Di::new(2, 1), &Direction::EAST),
+            Vector2Di::new(3, 0)
+        );
+        assert_eq!(
+            map.resolve_stairs(Vector2Di::new(2, 0), &Direction::WEST),
+            Vector2Di::new(1, 1)
+        );
+        assert_eq!(
+            map.resolve_stairs(Vector2Di::new(2, 1), &Direction::NORTH),
+            Vector2Di::new(2, 1)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::rc::Rc;
+
+use platform::types::Vector2D;
+
+use crate::api::commands::ExecutionPlayerCommand;
+use crate::api::sensing::WorldView;
+use crate::character::CharacterId;
+use crate::executor::{CommandSource, GameEvent, Variables};
+use crate::save::remote::Vector2DDef;
+use crate::{ScriptedCharacterLogic, World};
+
+/*
+ * Recording of a level run, to reproduce what happened on another machine.
+ * A frame is one call of World::step by the host. The script itself is not run again on replay:
+ * the commands it yielded are fed to the executor in the same order, so a replay doesn't
+ * depend on the script engine. The positions of the characters after every frame are kept
+ * to find the first frame where the replay diverges.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub script: Option<String>, // the code of the script, for the bug report
+    pub time_step: f32,
+    pub frames: Vec<FrameRecord>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub delta: f32,
+    pub commands: Vec<ExecutionPlayerCommand>, // yielded by the script during the frame
+    pub positions: Vec<CharacterPosition>,     // the player first, then the NPCs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CharacterPosition {
+    pub id: CharacterId,
+    #[serde(with = "Vector2DDef")]
+    pub position: Vector2D,
+}
+
+impl Recording {
+    /// Commands of the script in the order they were yielded
+    pub fn commands(&self) -> VecDeque<ExecutionPlayerCommand> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.commands.iter().cloned())
+            .collect()
+    }
+
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(filename)?;
+        let ron_string = to_string_pretty(self, PrettyConfig::default())?;
+        file.write_all(ron_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(filename)?;
+        let recording: Recording = ron::from_str(&content)?;
+
+        Ok(recording)
+    }
+}
+
+/// Positions of the player and the NPCs, in the order used by the recording
+pub fn character_positions(world: &World) -> Vec<CharacterPosition> {
+    let player = world.player().map(|player| CharacterPosition {
+        id: player.get_id(),
+        position: player.get_position(),
+    });
+    let npcs = world.npcs().iter().map(|npc| CharacterPosition {
+        id: npc.get_id(),
+        position: npc.get_position(),
+    });
+    player.into_iter().chain(npcs).collect()
+}
+
+// The commands yielded by the script since the last recorded frame
+type CommandLog = Rc<RefCell<Vec<ExecutionPlayerCommand>>>;
+
+/// Passes the commands of the wrapped source to the executor and writes them to the log
+struct RecordingSource {
+    source: Box<dyn CommandSource>,
+    log: CommandLog,
+}
+
+impl CommandSource for RecordingSource {
+    fn next_command(
+        &mut self,
+        last_result: Option<bool>,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        let command = self.source.next_command(last_result, character)?;
+        self.log.borrow_mut().push(command.clone());
+        Some(command)
+    }
+
+    // the commands of the run before the save are not recorded again
+    fn replay_command(
+        &mut self,
+        last_result: Option<bool>,
+        world: WorldView,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        self.source.replay_command(last_result, world, character)
+    }
+
+    fn variables(&mut self) -> Option<Variables> {
+        self.source.variables()
+    }
+
+    fn observe(&mut self, frame: u64, dt: f32, events: &[GameEvent]) {
+        self.source.observe(frame, dt, events);
+    }
+
+    fn is_alive(&self) -> bool {
+        self.source.is_alive()
+    }
+}
+
+// The commands the replay source yields, `replay` adds the commands of every frame
+type CommandQueue = Rc<RefCell<VecDeque<ExecutionPlayerCommand>>>;
+
+/// Yields the recorded commands of the script.
+/// A script waiting for events yields its commands in the frames it gets the events,
+/// so `replay` gives the source the commands of a frame just before the frame.
+pub struct ReplaySource {
+    commands: CommandQueue,
+}
+
+impl ReplaySource {
+    pub fn new(commands: VecDeque<ExecutionPlayerCommand>) -> Self {
+        Self {
+            commands: Rc::new(RefCell::new(commands)),
+        }
+    }
+
+    fn queue(&self) -> CommandQueue {
+        self.commands.clone()
+    }
+}
+
+impl CommandSource for ReplaySource {
+    fn next_command(
+        &mut self,
+        _last_result: Option<bool>,
+        _character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        self.commands.borrow_mut().pop_front()
+    }
+
+    // the commands of the next frames are still to come
+    fn is_alive(&self) -> bool {
+        true
+    }
+}
+
+/// Records a run frame by frame. The host attaches the script through `record_source`
+/// and calls `record_frame` after every frame.
+pub struct Recorder {
+    recording: Recording,
+    log: CommandLog,
+}
+
+impl Recorder {
+    pub fn new(script: Option<String>, time_step: f32) -> Self {
+        Self {
+            recording: Recording {
+                script,
+                time_step,
+                frames: Vec::new(),
+            },
+            log: CommandLog::default(),
+        }
+    }
+
+    /// Wraps the source of the script, so the commands it yields are recorded
+    pub fn record_source(&self, source: Box<dyn CommandSource>) -> Box<dyn CommandSource> {
+        Box::new(RecordingSource {
+            source,
+            log: self.log.clone(),
+        })
+    }
+
+    /// Records the frame after World::step with `delta`
+    pub fn record_frame(&mut self, delta: f32, world: &World) {
+        let commands = std::mem::take(&mut *self.log.borrow_mut());
+        self.recording.frames.push(FrameRecord {
+            delta,
+            commands,
+            positions: character_positions(world),
+        });
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/// The first frame where the replay doesn't match the recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub frame: usize,
+    pub expected: Vec<CharacterPosition>,
+    pub actual: Vec<CharacterPosition>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the replay diverges at frame {}", self.frame)?;
+        if self.expected.len() != self.actual.len() {
+            return write!(
+                f,
+                ": {} characters recorded, {} in the world",
+                self.expected.len(),
+                self.actual.len()
+            );
+        }
+        for (expected, actual) in self.expected.iter().zip(&self.actual) {
+            if expected != actual {
+                write!(
+                    f,
+                    "\n  character {}: recorded {:?}, replayed {:?}",
+                    expected.id, expected.position, actual.position
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Replays the recording in a world set up for the same level, as it was before the run.
+/// Returns the number of replayed frames, or the first frame where a character
+/// is not where it was recorded.
+pub fn replay(recording: &Recording, world: &mut World) -> Result<usize, Divergence> {
+    world.set_time_step(recording.time_step);
+    let source = ReplaySource::new(VecDeque::new());
+    let commands = source.queue();
+    world.set_source(Box::new(source));
+
+    for (index, frame) in recording.frames.iter().enumerate() {
+        commands.borrow_mut().extend(frame.commands.iter().cloned());
+        world.step(frame.delta);
+
+        let actual = character_positions(world);
+        if actual != frame.positions {
+            return Err(Divergence {
+                frame: index,
+                expected: frame.positions.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(recording.frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::commands::PlayerCommand;
+    use crate::test_utils::test_world::{animator, flat_map};
+    use crate::{NPCCharacterLogic, StateRequest};
+    use platform::types::Direction;
+
+    /// 5×5 flat map with the player at (1,1) facing east and an idle NPC at (4,4)
+    fn make_world() -> World {
+        crate::test_utils::test_init::ensure_init();
+        let mut world = World::new(flat_map(5, 5));
+
+        let index = world.add_npc(NPCCharacterLogic::new(1, animator()));
+        world.npcs_mut()[index].set_cell_position(4, 4);
+
+        let mut player = ScriptedCharacterLogic::new(2, animator());
+        player.set_logic_map(world.map().clone());
+        player.set_cell_position(1, 1);
+        player.set_direction(Direction::EAST);
+        player.try_transition(StateRequest::Idle).unwrap();
+        world.set_player(player);
+        world
+    }
+
+    fn command(command: PlayerCommand) -> ExecutionPlayerCommand {
+        ExecutionPlayerCommand {
+            command,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    // Runs the script with uneven frame times
+    fn record_run() -> Recording {
+        let mut world = make_world();
+        let mut recorder = Recorder::new(Some("step_right(); step_down();".into()), 0.02);
+        world.set_time_step(0.02);
+        let script = ReplaySource::new(
+            [PlayerCommand::MoveEast, PlayerCommand::MoveSouth]
+                .map(command)
+                .into(),
+        );
+        world.set_source(recorder.record_source(Box::new(script)));
+
+        for frame in 0..120 {
+            let delta = if frame % 3 == 0 { 0.035 } else { 0.015 };
+            world.step(delta);
+            recorder.record_frame(delta, &world);
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn test_replay_matches_the_recording() {
+        let recording = record_run();
+        assert_eq!(recording.commands().len(), 2);
+        let last = recording.frames.last().unwrap();
+        assert_ne!(last.positions[0], recording.frames[0].positions[0]);
+
+        let text = ron::to_string(&recording).unwrap();
+        let recording: Recording = ron::from_str(&text).unwrap();
+        assert_eq!(replay(&recording, &mut make_world()), Ok(120));
+    }
+
+    #[test]
+    fn test_replay_reports_the_first_divergence() {
+        let mut recording = record_run();
+        recording.frames[30].positions[0].position.x += 1.0;
+        recording.frames[40].positions[0].position.x += 1.0;
+
+        let divergence = replay(&recording, &mut make_world()).unwrap_err();
+        assert_eq!(divergence.frame, 30);
+        assert_eq!(divergence.actual[1], divergence.expected[1]);
+        assert!(divergence.to_string().contains("character 2"));
+    }
+}
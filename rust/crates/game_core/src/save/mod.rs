@@ -0,0 +1,171 @@
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+
+use platform::types::{Direction, Vector2D, Vector2Di};
+
+use crate::api::commands::ExecutionPlayerCommand;
+use crate::bt::blackboard::BlackboardValue;
+use crate::character::CharacterId;
+use crate::executor::GameEvent;
+use crate::fsm::SavedState;
+use crate::level::LevelStatus;
+use crate::map::PlacedObject;
+use crate::StateRequest;
+
+pub mod remote;
+
+use remote::{DirectionDef, Vector2DDef, Vector2DiDef};
+
+/// Version of the save format, a save of another version is not loaded
+pub const SAVE_VERSION: u32 = 2;
+
+/*
+ * State of a level in progress, written to RON when the game is closed mid-level.
+ * It holds only what changes during the game: the map, the characters and the trees
+ * are built from the level as usual, then the save is restored into that World.
+ * The step-by-step script can't be serialized: the save keeps its code and, for every
+ * time it was asked for a command, what it observed before, the result it was given,
+ * the cell and direction of the character and the command it gave. The host restarts
+ * the script and the executor fast-forwards it with the same inputs, the script has
+ * to give the same commands again (see CommandExecutor::resume_source).
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub frame: u64,
+    pub accumulator: f32,
+    pub objects: Vec<PlacedObject>, // the current state of the map objects
+    pub npcs: Vec<CharacterSave>,   // in the order of World::npcs
+    pub player: Option<CharacterSave>,
+    pub executor: ExecutorSave,
+    pub level: Option<LevelSave>,
+    pub level_running: bool,
+    pub seen_by: Vec<CharacterId>, // NPCs which saw the player in the last tick
+    #[serde(default)]
+    pub script: Option<String>, // code of the running script, set by the host
+}
+
+impl SaveGame {
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        to_string_pretty(self, PrettyConfig::default())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let save: SaveGame = ron::from_str(text)?;
+        if save.version != SAVE_VERSION {
+            return Err(format!(
+                "Unsupported save version {}, expected {}",
+                save.version, SAVE_VERSION
+            )
+            .into());
+        }
+        Ok(save)
+    }
+
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(filename)?;
+        file.write_all(self.to_ron()?.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(filename)?;
+        Self::from_ron(&content)
+    }
+}
+
+/// Position, FSM state and memory of a character
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterSave {
+    pub id: CharacterId,
+    #[serde(with = "Vector2DDef")]
+    pub position: Vector2D,
+    #[serde(with = "Vector2DiDef")]
+    pub prev_cell: Vector2Di,
+    #[serde(with = "DirectionDef")]
+    pub direction: Direction,
+    pub current_speed: f32,
+    pub state: Option<SavedState>,
+    pub pending_request: Option<StateRequest>, // requested in the last frame, not handled yet
+    pub blackboard: BlackboardSave,
+    pub inventory: Vec<String>,
+}
+
+/// Values of a Blackboard, the node state is saved for the scope of the character's tree
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlackboardSave {
+    pub memory: BTreeMap<String, BlackboardValue>, // including the squad keys
+    pub nodes: Vec<NodeStateSave>,
+}
+
+/// One value of the internal state of a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeStateSave {
+    pub node: usize,
+    pub name: String,
+    pub index: Option<usize>,
+    pub value: BlackboardValue,
+}
+
+/// Commands waiting in the CommandExecutor and the progress of its step-by-step source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutorSave {
+    pub commands: Vec<ExecutionPlayerCommand>,
+    pub current: Option<ExecutionPlayerCommand>, // the command of the highlighted line
+    pub last_result: Option<bool>,
+    pub executed: usize,
+    pub source: Option<Vec<SourceStep>>, // what the source was given, None without a source
+    pub observed: Observed, // observed by the source after the last step, not polled yet
+}
+
+/// What the step-by-step source observed between two polls
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Observed {
+    pub frame: u64, // of the last observe
+    pub dt: f32,    // since the previous poll
+    pub events: Vec<GameEvent>,
+}
+
+/// What the step-by-step source was given at a poll and what it answered.
+/// The polls of a source which waits in consecutive frames with the same inputs
+/// are kept as one step repeated `count` times, so the save doesn't grow while it waits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceStep {
+    pub observed: Observed,
+    pub result: Option<bool>, // passed to `next_command`
+    #[serde(with = "Vector2DiDef")]
+    pub cell: Vector2Di, // of the character, answers the sensing calls of the source
+    #[serde(with = "DirectionDef")]
+    pub direction: Direction,
+    pub command: Option<ExecutionPlayerCommand>, // given by the source
+    pub count: u32, // polls in the frames `observed.frame..observed.frame + count`
+}
+
+impl SourceStep {
+    /// Whether `step` is one more poll of this step: both only waited, with the same
+    /// inputs, and `step` is in the next frame
+    pub fn repeated_by(&self, step: &SourceStep) -> bool {
+        let waits = |step: &SourceStep| {
+            step.command.is_none() && step.result.is_none() && step.observed.events.is_empty()
+        };
+        waits(self)
+            && waits(step)
+            && step.observed.frame == self.observed.frame + self.count as u64
+            && step.observed.dt == self.observed.dt
+            && step.cell == self.cell
+            && step.direction == self.direction
+    }
+}
+
+/// Progress of the level objectives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelSave {
+    pub completed: Vec<bool>,
+    pub elapsed: f32,
+    pub detected: bool,
+    pub status: LevelStatus,
+}
@@ -0,0 +1,30 @@
+use platform::types::{Direction, Vector2D, Vector2Di};
+use serde::{Deserialize, Serialize};
+
+/*
+ * Serde definitions of the platform types, used with `#[serde(with = "...")]`.
+ * The platform crate stays free of dependencies.
+ */
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Vector2D")]
+pub struct Vector2DDef {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Vector2Di")]
+pub struct Vector2DiDef {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Direction")]
+pub enum DirectionDef {
+    NORTH,
+    SOUTH,
+    WEST,
+    EAST,
+}
@@ -1,10 +1,8 @@
 /// Shared test initialization utilities.
-/// Provides a single global `ensure_init()` so that `init_bt_system()`
-/// and `init_logger()` are called exactly once regardless of how many
-/// test modules use them.
+/// Provides a single global `ensure_init()` so that `init_logger()`
+/// is called exactly once regardless of how many test modules use it.
 #[cfg(test)]
 pub mod test_init {
-    use crate::ai::worker::init_bt_system;
     use platform::logger::{LogType, Logger};
     use platform::shared::logger_global::init_logger;
     use std::sync::Once;
@@ -18,7 +16,140 @@ pub mod test_init {
                 fn log(&self, _: LogType, _: &str) {}
             }
             init_logger(Box::new(NullLogger));
-            init_bt_system();
         });
     }
 }
+
+/// Helpers to test behaviour tree nodes without a character
+#[cfg(test)]
+pub mod test_bt {
+    use crate::bt::blackboard::BlackboardValue;
+    use crate::bt::result::BTResult;
+    use crate::bt::{BTNode, Blackboard, BoxBTNode, NodeStatus};
+    use crate::character::snapshot::CharacterSnapshot;
+    use crate::fsm::StateType;
+    use platform::types::{Direction, Vector2D, Vector2Di};
+
+    pub fn snapshot() -> CharacterSnapshot {
+        CharacterSnapshot {
+            id: 1,
+            position: Vector2D::ZERO,
+            cell_position: Vector2Di::ZERO,
+            direction: Direction::SOUTH,
+            velocity: Vector2D::ZERO,
+            is_idle: true,
+            state: Some(StateType::IDLE),
+            blackboard: Box::new(Blackboard::new()),
+            current_speed: 0.0,
+            perceived: Vec::new(),
+            trace: None,
+            inventory: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    /// Returns the given statuses one by one (the last one repeats).
+    /// Counts its ticks in the "<name>.ticks" key of the blackboard.
+    pub struct Scripted {
+        name: String,
+        statuses: Vec<NodeStatus>,
+        id: usize,
+    }
+
+    impl Scripted {
+        pub fn boxed(name: &str, statuses: Vec<NodeStatus>) -> BoxBTNode {
+            Box::new(Self {
+                name: name.to_string(),
+                statuses,
+                id: 0,
+            })
+        }
+
+        pub fn ticks(snapshot: &CharacterSnapshot, name: &str) -> usize {
+            match snapshot.blackboard.get(&format!("{}.ticks", name)) {
+                Some(BlackboardValue::Int(val)) => val as usize,
+                _ => 0,
+            }
+        }
+    }
+
+    impl BTNode for Scripted {
+        fn reset(&mut self) {}
+        fn set_id(&mut self, id: usize) {
+            self.id = id;
+        }
+        fn id(&self) -> usize {
+            self.id
+        }
+        fn tick(&self, snapshot: &CharacterSnapshot, _delta: f32) -> (NodeStatus, BTResult) {
+            let ticks = Scripted::ticks(snapshot, &self.name);
+            snapshot.blackboard.set(
+                &format!("{}.ticks", self.name),
+                BlackboardValue::Int(ticks as i32 + 1),
+            );
+            let status = self.statuses[ticks.min(self.statuses.len() - 1)];
+            (status, BTResult::empty())
+        }
+    }
+
+    /// Ticks the node `ticks` times with delta 0.1
+    pub fn run(node: &dyn BTNode, snapshot: &CharacterSnapshot, ticks: usize) -> Vec<NodeStatus> {
+        (0..ticks).map(|_| node.tick(snapshot, 0.1).0).collect()
+    }
+}
+
+/// Map and animator for the tests of the characters, the world and the levels.
+/// The other crates get them in their tests with the `test-utils` feature.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_world {
+    use crate::map::{LogicCell, LogicMap, StepType};
+    use platform::types::Vector2D;
+    use platform::Animator;
+
+    /// Map of `width`×`height` walkable cells of height 0, the tests change the cells they need
+    pub fn flat_map(width: usize, height: usize) -> LogicMap {
+        let mut map = LogicMap::new(width, height);
+        for j in 0..height {
+            for i in 0..width {
+                map.set_cell(
+                    i,
+                    j,
+                    Some(LogicCell {
+                        walkable: true,
+                        height: 0,
+                        step_type: StepType::None,
+                    }),
+                );
+            }
+        }
+        map
+    }
+
+    /// Animator which only keeps the position, its animations end at once
+    pub struct TestAnimator {
+        position: Vector2D,
+    }
+
+    impl Animator for TestAnimator {
+        fn play(&mut self, _name: &str) {}
+        fn is_playing(&self) -> bool {
+            false
+        }
+        fn process(&mut self, _delta: f32) {}
+        fn set_position(&mut self, position: Vector2D) {
+            self.position = position;
+        }
+        fn get_position(&self) -> Vector2D {
+            self.position
+        }
+        fn get_global_position(&self) -> Vector2D {
+            self.position
+        }
+    }
+
+    pub fn animator() -> Box<dyn Animator> {
+        Box::new(TestAnimator {
+            position: Vector2D::ZERO,
+        })
+    }
+}
@@ -0,0 +1,899 @@
+use std::sync::Arc;
+
+use crate::ai::perception::{EntityKind, Stimulus};
+use crate::ai::worker::{tick_batch, BtExecutionMode, BtScheduler};
+use crate::bt::command::CustomCommands;
+use crate::bt::trace::BtTracer;
+use crate::character::snapshot::CharacterSnapshot;
+use crate::character::CharacterId;
+use crate::executor::{CommandSource, ExecutorResult, GameEvent};
+use crate::level::{LevelEvaluator, LevelStatus};
+use crate::map::LogicMap;
+use crate::save::{SaveGame, SAVE_VERSION};
+use crate::{CommandExecutor, NPCCharacterLogic, ScriptedCharacterLogic};
+
+pub const DEFAULT_TIME_STEP: f32 = 1.0 / 60.0;
+
+/*
+ * Headless simulation of a level.
+ * Owns the map, the NPCs, the scripted character, the command executor and the level objectives,
+ * and advances all of them with a fixed time step.
+ * Behaviour trees are ticked inline or as a batch with a barrier (see BtExecutionMode),
+ * so the same input always produces the same result: levels can be run in tests without Godot.
+ * The Async mode is accepted too, but then NPCs depend on the worker thread timing.
+ *
+ * Order of one tick:
+ *  0. NPC perception: who sees or hears whom at the start of the tick, also for AvoidDetection
+ *  1. NPCs: behaviour tree + FSM
+ *  2. CommandExecutor hands the events of the scripted character to the script (its hooks)
+ *     and dispatches its next command
+ *  3. scripted character FSM
+ *  4. level objectives
+ */
+pub struct World {
+    map: Arc<LogicMap>,
+    npcs: Vec<NPCCharacterLogic>,
+    bt_mode: BtExecutionMode,
+    scheduler: Option<Arc<BtScheduler>>, // created for the Async mode, owned by this world
+    tracer: Arc<BtTracer>,               // shared with the NPCs and the scheduler
+    custom_commands: Arc<CustomCommands>, // handlers of BTCommand::Custom for all NPCs
+    player: Option<ScriptedCharacterLogic>,
+    executor: CommandExecutor,
+    executor_result: ExecutorResult,
+    level: Option<LevelEvaluator>,
+    level_running: bool, // objectives are evaluated from the script start until the level ends
+    seen_by: Vec<CharacterId>, // NPCs which saw the player in the last tick
+
+    time_step: f32,
+    accumulator: f32, // time not yet simulated, less than one time step
+    frame: u64,
+}
+
+impl World {
+    pub fn new(map: LogicMap) -> Self {
+        Self::from_arc(Arc::new(map))
+    }
+
+    pub fn from_arc(map: Arc<LogicMap>) -> Self {
+        World {
+            map,
+            npcs: Vec::new(),
+            bt_mode: BtExecutionMode::Inline,
+            scheduler: None,
+            tracer: Arc::new(BtTracer::default()),
+            custom_commands: Arc::new(CustomCommands::new()),
+            player: None,
+            executor: CommandExecutor::new(),
+            executor_result: ExecutorResult::Empty,
+            level: None,
+            level_running: false,
+            seen_by: Vec::new(),
+            time_step: DEFAULT_TIME_STEP,
+            accumulator: 0.0,
+            frame: 0,
+        }
+    }
+
+    pub fn map(&self) -> &Arc<LogicMap> {
+        &self.map
+    }
+
+    pub fn set_time_step(&mut self, time_step: f32) {
+        self.time_step = time_step;
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// Number of fixed steps simulated since the last reset
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Simulated time since the last reset, in seconds
+    pub fn time(&self) -> f32 {
+        self.frame as f32 * self.time_step
+    }
+
+    /// Sets how the behaviour trees of all NPCs are ticked. The default is `Inline`.
+    pub fn set_bt_mode(&mut self, mode: BtExecutionMode) {
+        self.bt_mode = mode;
+        if mode == BtExecutionMode::Async && self.scheduler.is_none() {
+            self.scheduler = Some(Arc::new(BtScheduler::with_tracer(self.tracer.clone())));
+        }
+        for npc in self.npcs.iter_mut() {
+            Self::configure_npc(npc, mode, &self.scheduler, &self.tracer);
+        }
+    }
+
+    fn configure_npc(
+        npc: &mut NPCCharacterLogic,
+        mode: BtExecutionMode,
+        scheduler: &Option<Arc<BtScheduler>>,
+        tracer: &Arc<BtTracer>,
+    ) {
+        npc.set_bt_mode(mode);
+        npc.set_tracer(tracer.clone());
+        if let Some(scheduler) = scheduler {
+            npc.set_scheduler(scheduler.clone());
+        }
+    }
+
+    pub fn bt_mode(&self) -> BtExecutionMode {
+        self.bt_mode
+    }
+
+    /// Traces of the NPC behaviour trees in all execution modes, disabled by default:
+    /// `world.tracer().set_enabled(true)`
+    pub fn tracer(&self) -> &Arc<BtTracer> {
+        &self.tracer
+    }
+
+    /// Sets the handlers of the custom behaviour tree commands of all NPCs
+    pub fn set_custom_commands(&mut self, commands: CustomCommands) {
+        self.custom_commands = Arc::new(commands);
+        for npc in self.npcs.iter_mut() {
+            npc.set_custom_commands(self.custom_commands.clone());
+        }
+    }
+
+    /// Adds an NPC, the world map, the BT execution mode and the custom command handlers
+    /// are assigned to it. Returns the index of the NPC in the world.
+    pub fn add_npc(&mut self, mut npc: NPCCharacterLogic) -> usize {
+        npc.set_logic_map(self.map.clone());
+        npc.set_custom_commands(self.custom_commands.clone());
+        Self::configure_npc(&mut npc, self.bt_mode, &self.scheduler, &self.tracer);
+        self.npcs.push(npc);
+        self.npcs.len() - 1
+    }
+
+    pub fn npcs(&self) -> &[NPCCharacterLogic] {
+        &self.npcs
+    }
+
+    pub fn npcs_mut(&mut self) -> &mut [NPCCharacterLogic] {
+        &mut self.npcs
+    }
+
+    pub fn npc_snapshots(&self) -> Vec<CharacterSnapshot> {
+        self.npcs.iter().map(|npc| npc.snapshot()).collect()
+    }
+
+    /// Sets the character controlled by the script, the world map is assigned to it
+    pub fn set_player(&mut self, mut player: ScriptedCharacterLogic) {
+        player.set_logic_map(self.map.clone());
+        self.player = Some(player);
+    }
+
+    pub fn player(&self) -> Option<&ScriptedCharacterLogic> {
+        self.player.as_ref()
+    }
+
+    /// The snapshot of the player with the frame of the world, for the scripts
+    pub fn player_snapshot(&self) -> Option<CharacterSnapshot> {
+        let mut snapshot = self.player.as_ref()?.snapshot();
+        snapshot.frame = self.frame;
+        Some(snapshot)
+    }
+
+    pub fn player_mut(&mut self) -> Option<&mut ScriptedCharacterLogic> {
+        self.player.as_mut()
+    }
+
+    pub fn executor(&self) -> &CommandExecutor {
+        &self.executor
+    }
+
+    pub fn executor_mut(&mut self) -> &mut CommandExecutor {
+        &mut self.executor
+    }
+
+    /// Result of the executor in the last simulated step.
+    /// `ExecutorResult::Empty` means the scripted character has nothing to do.
+    pub fn executor_result(&self) -> ExecutorResult {
+        self.executor_result
+    }
+
+    /// Attaches the script driving the scripted character and starts evaluating the level
+    pub fn set_source(&mut self, source: Box<dyn CommandSource>) {
+        self.executor.set_source(source);
+        self.level_running = self.level.is_some();
+    }
+
+    pub fn set_level(&mut self, level: LevelEvaluator) {
+        self.level = Some(level);
+    }
+
+    pub fn level(&self) -> Option<&LevelEvaluator> {
+        self.level.as_ref()
+    }
+
+    pub fn level_status(&self) -> Option<&LevelStatus> {
+        self.level.as_ref().map(|level| level.status())
+    }
+
+    /// Advances the simulation by `delta` seconds.
+    /// The time is simulated in fixed steps, the remainder is kept for the next call.
+    /// Returns the number of simulated steps.
+    pub fn step(&mut self, delta: f32) -> usize {
+        self.accumulator += delta;
+
+        let mut steps = 0;
+        while self.accumulator >= self.time_step {
+            self.accumulator -= self.time_step;
+            self.tick();
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Simulates exactly one fixed step
+    pub fn tick(&mut self) {
+        let dt = self.time_step;
+
+        // the whole world waits while the debugger has paused the script
+        if self.executor.is_paused() {
+            self.executor_result = ExecutorResult::Paused;
+            return;
+        }
+
+        self.update_perception();
+        self.process_npcs(dt);
+
+        if let Some(player) = &mut self.player {
+            self.executor.observe(self.frame, dt, player);
+            self.executor_result = self.executor.tick(dt, player, &self.map);
+            player.process(dt, &self.map);
+        }
+
+        self.evaluate_level(dt);
+        self.frame += 1;
+    }
+
+    // All NPCs perceive the same state of the world, before any of them moves
+    fn update_perception(&mut self) {
+        let mut stimuli: Vec<Stimulus> = self
+            .npcs
+            .iter()
+            .map(|npc| Stimulus::from_snapshot(&npc.snapshot(), EntityKind::Npc))
+            .collect();
+        if let Some(player) = &self.player {
+            stimuli.push(Stimulus::from_snapshot(
+                &player.snapshot(),
+                EntityKind::Player,
+            ));
+        }
+
+        let mut seen_by = Vec::new();
+        for npc in self.npcs.iter_mut() {
+            let perceived = npc
+                .perception()
+                .perceive(&self.map, &npc.snapshot(), &stimuli);
+            let sees_player = perceived
+                .iter()
+                .any(|entity| entity.kind == EntityKind::Player && entity.seen);
+            if sees_player {
+                seen_by.push(npc.get_id());
+                // only when the NPC starts to see the player
+                if !self.seen_by.contains(&npc.get_id()) {
+                    self.executor.push_event(GameEvent::SeenBy {
+                        npc: npc.get_id(),
+                        cell: npc.get_cell_position(),
+                    });
+                }
+            }
+            // AvoidDetection fails on the same sight as `on_seen_by`
+            if self.level_running {
+                if let Some(level) = &mut self.level {
+                    level.observe_npc(npc.get_cell_position(), &perceived);
+                }
+            }
+            npc.set_perceived(perceived);
+        }
+        self.seen_by = seen_by;
+    }
+
+    fn process_npcs(&mut self, dt: f32) {
+        if self.bt_mode != BtExecutionMode::Batch {
+            for npc in self.npcs.iter_mut() {
+                npc.process(dt, &self.map);
+            }
+            return;
+        }
+
+        // tick all trees against this frame's snapshots, then apply the results in order
+        let jobs = self.npcs.iter().map(|npc| npc.bt_job(dt)).collect();
+        let results = tick_batch(jobs, Some(&self.tracer));
+        for (npc, (_, result)) in self.npcs.iter_mut().zip(results) {
+            npc.process_commands(result);
+            npc.process_state(dt, &self.map);
+        }
+    }
+
+    fn evaluate_level(&mut self, dt: f32) {
+        if !self.level_running {
+            return;
+        }
+        let (Some(level), Some(player)) = (&mut self.level, &self.player) else {
+            return;
+        };
+
+        if level.tick(dt, player, &self.executor) != LevelStatus::InProgress {
+            // the level is over: stop the script
+            self.level_running = false;
+            self.executor.reset();
+        }
+    }
+
+    /// Captures the state of the level in progress.
+    /// The code of a running script is not known to the world, the host sets `script`.
+    pub fn save(&self) -> SaveGame {
+        SaveGame {
+            version: SAVE_VERSION,
+            frame: self.frame,
+            accumulator: self.accumulator,
+            objects: self.map.objects().objects(),
+            npcs: self.npcs.iter().map(|npc| npc.save()).collect(),
+            player: self.player.as_ref().map(|player| player.save()),
+            executor: self.executor.save(),
+            level: self.level.as_ref().map(|level| level.save()),
+            level_running: self.level_running,
+            seen_by: self.seen_by.clone(),
+            script: None,
+        }
+    }
+
+    /// Restores a save game into this world, which has to be built for the same level:
+    /// the same NPCs in the same order, the player and the objectives.
+    /// Nothing is changed if the save doesn't match the world.
+    /// A script which was running is attached again with `resume_source`.
+    pub fn load(&mut self, save: &SaveGame) -> Result<(), String> {
+        let npc_ids: Vec<_> = self.npcs.iter().map(|npc| npc.get_id()).collect();
+        let saved_ids: Vec<_> = save.npcs.iter().map(|npc| npc.id).collect();
+        if npc_ids != saved_ids {
+            return Err(format!(
+                "The save has NPCs {:?}, the level has {:?}",
+                saved_ids, npc_ids
+            ));
+        }
+        if self.player.is_some() != save.player.is_some() {
+            return Err("The save doesn't match the scripted character of the level".to_string());
+        }
+        match (&mut self.level, &save.level) {
+            (Some(level), Some(saved)) => level.restore(saved)?,
+            (None, None) => {}
+            _ => return Err("The save doesn't match the objectives of the level".to_string()),
+        }
+
+        self.map.objects().restore(save.objects.clone());
+        for (npc, saved) in self.npcs.iter_mut().zip(&save.npcs) {
+            npc.restore(saved);
+        }
+        if let (Some(player), Some(saved)) = (&mut self.player, &save.player) {
+            player.restore(saved);
+        }
+        self.executor.restore(&save.executor);
+        self.executor_result = ExecutorResult::Empty;
+        self.level_running = save.level_running;
+        self.seen_by = save.seen_by.clone();
+        self.tracer.clear();
+        self.accumulator = save.accumulator;
+        self.frame = save.frame;
+        Ok(())
+    }
+
+    /// Attaches the restarted script of a loaded save game and fast-forwards it
+    /// to the command it was at. The source is dropped if the script had already finished.
+    /// Fails if the script doesn't give the commands of the saved run.
+    pub fn resume_source(
+        &mut self,
+        source: Box<dyn CommandSource>,
+        save: &SaveGame,
+    ) -> Result<(), String> {
+        match &self.player {
+            Some(player) => self.executor.resume_source(source, &save.executor, player),
+            None => Ok(()),
+        }
+    }
+
+    /// Restores the initial state of the level: objects, characters, executor and objectives
+    pub fn reset(&mut self) {
+        self.map.objects().reset();
+        for npc in self.npcs.iter_mut() {
+            npc.reset();
+        }
+        if let Some(player) = &mut self.player {
+            player.reset();
+        }
+        self.executor.reset();
+        self.executor_result = ExecutorResult::Empty;
+        if let Some(level) = &mut self.level {
+            level.reset();
+        }
+        self.level_running = false;
+        self.seen_by.clear();
+        self.tracer.clear();
+        self.accumulator = 0.0;
+        self.frame = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::commands::{ExecutionPlayerCommand, PlayerCommand};
+    use crate::bt::blackboard::BlackboardValue;
+    use crate::bt::leafs::{FindTarget, FollowPath, IsAtTarget, MoveToTarget, NextWaypoint};
+    use crate::bt::nodes::{Selector, Sequence};
+    use crate::bt::wait::Wait;
+    use crate::bt::BehaviourTree;
+    use crate::level::Objective;
+    use crate::map::logic_map::LogicCell;
+    use crate::map::StepType;
+    use crate::save::SourceStep;
+    use crate::test_utils::test_world::{animator, flat_map};
+    use crate::StateRequest;
+    use platform::types::{Vector2D, Vector2Di};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn make_world() -> World {
+        crate::test_utils::test_init::ensure_init();
+        let mut world = World::new(flat_map(6, 6));
+
+        let route = vec![Vector2D::new(0.0, 5.0), Vector2D::new(4.0, 5.0)];
+        let mut npc = NPCCharacterLogic::new(1, animator());
+        npc.bt = Arc::new(BehaviourTree::new(Box::new(Selector::new(vec![
+            Box::new(Sequence::new(vec![
+                Box::new(NextWaypoint::new(route, "target_pos", 64.0)),
+                Box::new(Wait::new(0.1)),
+                Box::new(IsAtTarget::new("target_pos")),
+            ])),
+            Box::new(MoveToTarget::new("target_pos")),
+        ]))));
+        let index = world.add_npc(npc);
+        world.npcs_mut()[index].set_cell_position(0, 5);
+
+        let mut player = ScriptedCharacterLogic::new(2, animator());
+        player.set_logic_map(world.map().clone());
+        player.set_cell_position(1, 1);
+        player.set_direction(platform::types::Direction::EAST);
+        player.try_transition(StateRequest::Idle).unwrap();
+        world.set_player(player);
+
+        let commands = [
+            PlayerCommand::MoveEast,
+            PlayerCommand::MoveEast,
+            PlayerCommand::MoveSouth,
+        ];
+        world.executor_mut().set_commands(
+            commands
+                .iter()
+                .enumerate()
+                .map(|(i, command)| ExecutionPlayerCommand {
+                    command: command.clone(),
+                    line: i + 1,
+                    column: 1,
+                })
+                .collect(),
+        );
+        world
+    }
+
+    // positions of all characters after every step
+    fn record(world: &mut World, steps: usize) -> Vec<(Vector2D, Vector2D)> {
+        (0..steps)
+            .map(|_| {
+                world.tick();
+                (
+                    world.npcs()[0].get_position(),
+                    world.player().unwrap().get_position(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_time_step() {
+        let mut world = make_world();
+        world.set_time_step(0.02);
+
+        assert_eq!(world.step(0.05), 2);
+        // 0.01 left from the previous call
+        assert_eq!(world.step(0.01), 1);
+        assert_eq!(world.frame(), 3);
+    }
+
+    #[test]
+    fn test_runs_are_identical() {
+        let first = record(&mut make_world(), 300);
+        let second = record(&mut make_world(), 300);
+        assert_eq!(first, second);
+
+        let mut world = make_world();
+        let _ = record(&mut world, 300);
+        assert_eq!(
+            world.player().unwrap().get_cell_position(),
+            Vector2Di::new(3, 2)
+        );
+        assert_ne!(first[0].0, first[299].0, "the NPC should patrol");
+    }
+
+    #[test]
+    fn test_reset_replays_the_same_run() {
+        let mut world = make_world();
+        let first = record(&mut world, 100);
+
+        world.reset();
+        world.npcs_mut()[0].set_cell_position(0, 5);
+        let second = record(&mut world, 100);
+
+        // the executor is cleared by reset, so only the NPC replays the same path
+        let npc_first: Vec<_> = first.iter().map(|(npc, _)| *npc).collect();
+        let npc_second: Vec<_> = second.iter().map(|(npc, _)| *npc).collect();
+        assert_eq!(npc_first, npc_second);
+    }
+
+    #[test]
+    fn test_batch_mode_matches_inline() {
+        let inline = record(&mut make_world(), 300);
+
+        let mut world = make_world();
+        world.set_bt_mode(BtExecutionMode::Batch);
+        let batch = record(&mut world, 300);
+        assert_eq!(inline, batch);
+    }
+
+    struct ListSource(std::collections::VecDeque<PlayerCommand>);
+
+    impl CommandSource for ListSource {
+        fn next_command(
+            &mut self,
+            _last_result: Option<bool>,
+            _character: &ScriptedCharacterLogic,
+        ) -> Option<ExecutionPlayerCommand> {
+            self.0.pop_front().map(|command| ExecutionPlayerCommand {
+                command,
+                line: 1,
+                column: 1,
+            })
+        }
+    }
+
+    #[test]
+    fn test_traces_match_in_inline_and_batch_modes() {
+        let traces = |mode| {
+            let mut world = make_world();
+            world.set_bt_mode(mode);
+            assert!(world.tracer().last(1).is_none());
+
+            world.tracer().set_enabled(true);
+            record(&mut world, 10);
+            world.tracer().history(1)
+        };
+
+        let inline = traces(BtExecutionMode::Inline);
+        assert_eq!(inline.len(), 10);
+        // the first tick picks the waypoint and starts waiting
+        assert_eq!(inline[0].nodes[0].name, "Selector");
+        assert!(inline[0]
+            .blackboard
+            .iter()
+            .any(|diff| diff.key == "target_pos" && diff.before.is_none()));
+        assert_eq!(inline, traces(BtExecutionMode::Batch));
+    }
+
+    #[test]
+    fn test_loaded_save_continues_the_run() {
+        let script = || {
+            Box::new(ListSource(
+                [
+                    PlayerCommand::MoveEast,
+                    PlayerCommand::MoveSouth,
+                    PlayerCommand::MoveSouth,
+                    PlayerCommand::MoveWest,
+                ]
+                .into(),
+            ))
+        };
+        let mut world = make_world();
+        world.executor_mut().reset();
+        world.set_source(script());
+        let _ = record(&mut world, 45);
+
+        let text = world.save().to_ron().unwrap();
+        let expected = record(&mut world, 300);
+
+        // the same level built again, the script is restarted
+        let save = SaveGame::from_ron(&text).unwrap();
+        let mut loaded = make_world();
+        loaded.load(&save).unwrap();
+        loaded.resume_source(script(), &save).unwrap();
+        assert_eq!(loaded.frame(), 45);
+        assert_eq!(record(&mut loaded, 300), expected);
+        assert_eq!(
+            loaded.executor().executed_commands(),
+            world.executor().executed_commands()
+        );
+
+        // a world of another level is left as it was
+        let mut other = World::new(flat_map(6, 6));
+        assert!(other.load(&save).is_err());
+        assert_eq!(other.frame(), 0);
+    }
+
+    #[test]
+    fn test_loaded_save_continues_the_hooks() {
+        let mut world = make_world();
+        world.executor_mut().reset();
+        world.set_source(Box::new(Bouncer::default()));
+        let mut saves = vec![];
+        for _ in 0..12 {
+            let _ = record(&mut world, 50);
+            saves.push((world.save().to_ron().unwrap(), record(&mut world, 300)));
+        }
+        // the player has bounced off the edge of the map and waits
+        assert_eq!(
+            world.player().unwrap().get_cell_position(),
+            Vector2Di::new(5, 3)
+        );
+
+        for (text, expected) in saves.iter() {
+            let save = SaveGame::from_ron(text).unwrap();
+            let mut loaded = make_world();
+            loaded.load(&save).unwrap();
+            loaded
+                .resume_source(Box::new(Bouncer::default()), &save)
+                .unwrap();
+            assert_eq!(&record(&mut loaded, 300), expected);
+        }
+
+        // the frames in which the script only waited are counted, not kept one by one:
+        // only the frames in which the NPC sees the player again add steps
+        let steps = |text: &String| SaveGame::from_ron(text).unwrap().executor.source.unwrap();
+        let polls = |steps: &[SourceStep]| steps.iter().map(|step| step.count).sum::<u32>();
+        let (before, after) = (steps(&saves[10].0), steps(&saves[11].0));
+        assert_eq!(polls(&after) - polls(&before), 350);
+        assert_eq!(after.len() - before.len(), 2);
+    }
+
+    #[test]
+    fn test_level_is_evaluated_while_script_runs() {
+        let mut world = make_world();
+        world.executor_mut().reset();
+        world.set_level(LevelEvaluator::new(vec![Objective::ReachCell {
+            x: 3,
+            y: 1,
+        }]));
+
+        world.set_source(Box::new(ListSource(
+            [PlayerCommand::MoveEast, PlayerCommand::MoveEast].into(),
+        )));
+        let _ = record(&mut world, 300);
+        assert_eq!(world.level_status(), Some(&LevelStatus::Success));
+    }
+
+    #[test]
+    fn test_npc_follows_path_around_wall() {
+        crate::test_utils::test_init::ensure_init();
+        // wall in column 2, open only at the last row
+        let mut map = flat_map(6, 6);
+        for j in 0..5 {
+            map.set_cell(
+                2,
+                j,
+                Some(LogicCell {
+                    walkable: false,
+                    height: 0,
+                    step_type: StepType::None,
+                }),
+            );
+        }
+        let mut world = World::new(map);
+
+        let mut npc = NPCCharacterLogic::new(1, animator());
+        npc.bt = Arc::new(BehaviourTree::new(Box::new(FollowPath::new(
+            world.map().clone(),
+            "target_pos",
+        ))));
+        let index = world.add_npc(npc);
+        world.npcs_mut()[index].set_cell_position(0, 0);
+        let target = world.map().get_screen_position(Vector2Di::new(4, 0));
+        world.npcs()[index]
+            .snapshot()
+            .blackboard
+            .set("target_pos", BlackboardValue::Vector(target));
+
+        for _ in 0..1200 {
+            world.tick();
+        }
+        assert_eq!(world.npcs()[0].get_cell_position(), Vector2Di::new(4, 0));
+    }
+
+    #[test]
+    fn test_npc_finds_the_player() {
+        crate::test_utils::test_init::ensure_init();
+        let mut world = World::new(flat_map(6, 6));
+
+        let mut npc = NPCCharacterLogic::new(1, animator());
+        npc.bt = Arc::new(BehaviourTree::new(Box::new(FindTarget::new("target_pos"))));
+        let index = world.add_npc(npc);
+        world.npcs_mut()[index].set_cell_position(0, 0);
+        world.npcs_mut()[index].set_direction(platform::types::Direction::EAST);
+
+        let mut player = ScriptedCharacterLogic::new(2, animator());
+        player.set_logic_map(world.map().clone());
+        player.set_cell_position(3, 1);
+        world.set_player(player);
+        let events = Rc::new(RefCell::new(vec![]));
+        world.set_source(Box::new(EventLog(events.clone())));
+
+        world.tick();
+        let npc = &world.npcs()[0];
+        assert_eq!(npc.perceived().len(), 1);
+        assert!(npc.perceived()[0].seen);
+        assert_eq!(
+            npc.snapshot().blackboard.get_vector("target_pos"),
+            Some(world.map().get_screen_position(Vector2Di::new(3, 1)))
+        );
+
+        // the NPC turns away
+        world.npcs_mut()[0].set_direction(platform::types::Direction::WEST);
+        world.tick();
+        assert!(world.npcs()[0].perceived().is_empty());
+
+        // the script hears of the NPC every time it starts to see the player
+        world.npcs_mut()[0].set_direction(platform::types::Direction::EAST);
+        world.tick();
+        world.tick();
+        let seen_by = GameEvent::SeenBy {
+            npc: 1,
+            cell: Vector2Di::new(0, 0),
+        };
+        assert_eq!(*events.borrow(), vec![seen_by.clone(), seen_by]);
+    }
+
+    #[test]
+    fn test_loaded_save_keeps_the_npcs_which_see_the_player() {
+        crate::test_utils::test_init::ensure_init();
+        let make_world = |events: Rc<RefCell<Vec<GameEvent>>>| {
+            let mut world = World::new(flat_map(6, 6));
+            let mut npc = NPCCharacterLogic::new(1, animator());
+            npc.bt = Arc::new(BehaviourTree::new(Box::new(Wait::new(1.0))));
+            let index = world.add_npc(npc);
+            world.npcs_mut()[index].set_cell_position(0, 0);
+            world.npcs_mut()[index].set_direction(platform::types::Direction::EAST);
+
+            let mut player = ScriptedCharacterLogic::new(2, animator());
+            player.set_logic_map(world.map().clone());
+            player.set_cell_position(3, 1);
+            world.set_player(player);
+            world.set_source(Box::new(EventLog(events)));
+            world
+        };
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut world = make_world(events.clone());
+        world.tick();
+        let save = world.save();
+        world.tick();
+
+        // the NPC still sees the player after loading: `on_seen_by` is not called again
+        let loaded_events = Rc::new(RefCell::new(vec![]));
+        let mut loaded = make_world(Rc::new(RefCell::new(vec![])));
+        loaded.load(&save).unwrap();
+        loaded
+            .resume_source(Box::new(EventLog(loaded_events.clone())), &save)
+            .unwrap();
+        loaded.tick();
+        assert_eq!(events.borrow().len(), 1);
+        assert_eq!(*loaded_events.borrow(), *events.borrow());
+    }
+
+    #[test]
+    fn test_spotted_by_the_sight_of_the_npc() {
+        crate::test_utils::test_init::ensure_init();
+        let spotted = |player_cell: Vector2Di, map: LogicMap| {
+            let mut world = World::new(map);
+            let mut npc = NPCCharacterLogic::new(1, animator());
+            npc.bt = Arc::new(BehaviourTree::new(Box::new(Wait::new(1.0))));
+            let index = world.add_npc(npc);
+            world.npcs_mut()[index].set_cell_position(0, 0);
+            world.npcs_mut()[index].set_direction(platform::types::Direction::EAST);
+
+            let mut player = ScriptedCharacterLogic::new(2, animator());
+            player.set_logic_map(world.map().clone());
+            player.set_cell_position(player_cell.x, player_cell.y);
+            world.set_player(player);
+            world.set_level(LevelEvaluator::new(vec![
+                Objective::ReachCell { x: 5, y: 5 },
+                Objective::AvoidDetection { range: 3 },
+            ]));
+            let events = Rc::new(RefCell::new(vec![]));
+            world.set_source(Box::new(EventLog(events.clone())));
+
+            world.tick();
+            let failed = matches!(world.level_status(), Some(LevelStatus::Failed(_)));
+            let seen = !events.borrow().is_empty();
+            assert_eq!(failed, seen, "the level and the script disagree");
+            failed
+        };
+
+        // in the view cone, but not straight in front of the NPC
+        assert!(spotted(Vector2Di::new(2, 1), flat_map(6, 6)));
+
+        // straight in front, behind a higher cell
+        let mut map = flat_map(6, 6);
+        map.set_cell(
+            1,
+            0,
+            Some(LogicCell {
+                walkable: true,
+                height: 1,
+                step_type: StepType::None,
+            }),
+        );
+        assert!(!spotted(Vector2Di::new(2, 0), map));
+    }
+
+    // A script which only waits for events
+    struct EventLog(Rc<RefCell<Vec<GameEvent>>>);
+
+    impl CommandSource for EventLog {
+        fn next_command(
+            &mut self,
+            _last_result: Option<bool>,
+            _character: &ScriptedCharacterLogic,
+        ) -> Option<ExecutionPlayerCommand> {
+            None
+        }
+
+        fn observe(&mut self, _frame: u64, _dt: f32, events: &[GameEvent]) {
+            self.0.borrow_mut().extend_from_slice(events);
+        }
+
+        fn is_alive(&self) -> bool {
+            true
+        }
+    }
+
+    // A script driven by its hooks: walks east until `on_blocked`, then two cells south and waits
+    #[derive(Default)]
+    struct Bouncer {
+        blocked: bool,
+        south: u32,
+    }
+
+    impl CommandSource for Bouncer {
+        fn next_command(
+            &mut self,
+            _last_result: Option<bool>,
+            _character: &ScriptedCharacterLogic,
+        ) -> Option<ExecutionPlayerCommand> {
+            let command = if !self.blocked {
+                PlayerCommand::MoveEast
+            } else if self.south > 0 {
+                self.south -= 1;
+                PlayerCommand::MoveSouth
+            } else {
+                return None;
+            };
+            Some(ExecutionPlayerCommand {
+                command,
+                line: 1,
+                column: 1,
+            })
+        }
+
+        fn observe(&mut self, _frame: u64, _dt: f32, events: &[GameEvent]) {
+            if !self.blocked && events.iter().any(|e| matches!(e, GameEvent::Blocked(_))) {
+                self.blocked = true;
+                self.south = 2;
+            }
+        }
+
+        fn is_alive(&self) -> bool {
+            true
+        }
+    }
+}
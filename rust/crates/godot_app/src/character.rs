@@ -1,3 +1,4 @@
+use game_core::ai::perception::Perception;
 use game_core::character::CharacterId;
 use game_core::map::LogicMap;
 use godot::classes::{AnimatedSprite2D, Area2D, IArea2D};
@@ -5,16 +6,14 @@ use godot::prelude::*;
 use platform::logger::LogType;
 
 use crate::godot_animator::GodotAnimator;
-use game_core::bt::leafs::{IsAtTarget, MoveToTarget, NextWaypoint};
-use game_core::bt::nodes::{Selector, Sequence};
-use game_core::bt::wait::Wait;
-use game_core::bt::{BTRef, BehaviourTree};
+use game_core::bt::{BTRef, BehaviourTree, BuildContext, NodeRegistry, TreeDescription};
 use game_core::NPCCharacterLogic;
 use platform::types::{Vector2D, Vector2Di};
 
 //use platform::shared::logger_global::log;
 
 use platform::{log_error, log_info};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(GodotClass)]
@@ -23,6 +22,7 @@ pub struct Character {
     base: Base<Area2D>,
     logic: Option<NPCCharacterLogic>,
     logic_map: Option<Arc<LogicMap>>,
+    behaviour: Option<TreeDescription>, // tree from the level file, patrol if None
 }
 
 //#[godot_api]
@@ -31,7 +31,10 @@ impl Character {
     pub fn set_logic_map(&mut self, logic_map: Arc<LogicMap>) {
         self.logic_map = Some(logic_map.clone());
         let patrol = self.get_patrol_point();
+        // the tree walks along the paths on this map
+        let tree = self.build_tree();
         if let Some(logic) = &mut self.logic {
+            logic.bt = tree;
             logic.set_logic_map(logic_map);
             let cell = logic.snap_to_cell();
             logic.set_start_cell(cell);
@@ -46,41 +49,64 @@ impl Character {
         }
     }
 
-    // check if animation is still in process, keep out the switching to new animation
+    /// Selects the behaviour tree of the level by the "behaviour" meta of the node.
+    /// Without the meta, or if the level has no such tree, the character patrols.
+    pub fn set_behaviours(&mut self, behaviours: &HashMap<String, TreeDescription>) {
+        let variant = self.base().get_meta("behaviour");
+        if variant.is_nil() {
+            return;
+        }
+        let name = variant.to_string();
+        match behaviours.get(&name) {
+            Some(description) => self.behaviour = Some(description.clone()),
+            None => log_error!("Behaviour '{}' is not described in the level", name),
+        }
+    }
+
     fn build_tree(&self) -> BTRef {
-        // test patrol
-        // Tree structure:
-        // Sequence
-        //  1. Patrol Logic (update target, return Success when arrived)
-        //  2. Wait (pause)
-        //  3. WalkToTarget
-
-        // Actually, a better structure for continuous movement is:
+        // Patrol:
         // Selector
         //   1. Sequence (Target Reached?)
         //       a. Patrol (Calculate next point if arrived)
         //       b. Wait (Visualize looking around)
-        //   2. WalkToTarget (Keep moving to current target)
-        let route = if let Some(points) = self.get_patrol_point() {
-            points
-        } else {
-            vec![]
-        };
+        //   2. FollowPath (Keep walking to current target along the path on the map,
+        //      so the patrol points don't have to be in a straight line)
+        let description = self
+            .behaviour
+            .clone()
+            .unwrap_or_else(|| TreeDescription::patrol(2.0, "FollowPath"));
+        let context = BuildContext::new(
+            self.logic_map.clone(),
+            self.get_patrol_point().unwrap_or_default(),
+        );
+
+        match NodeRegistry::default().build(&description, &context) {
+            Ok(tree) => tree,
+            Err(err) => {
+                log_error!("Cannot build behaviour '{}': {}", description.name, err);
+                Arc::new(BehaviourTree::default())
+            }
+        }
+    }
 
-        let tile_size = if let Some(map) = &self.logic_map {
-            map.get_cell_size()
-        } else {
-            64.0
+    // Senses of the NPC, tuned with the "view_range", "view_angle" and "hearing_radius" meta
+    fn get_perception(&self) -> Perception {
+        let mut perception = Perception::default();
+        let meta = |name: &str| {
+            let variant = self.base().get_meta(name);
+            (!variant.is_nil()).then_some(variant)
         };
 
-        Arc::new(BehaviourTree::new(Box::new(Selector::new(vec![
-            Box::new(Sequence::new(vec![
-                Box::new(NextWaypoint::new(route, "target_pos", tile_size)),
-                Box::new(Wait::new(2.0)),
-                Box::new(IsAtTarget::new("target_pos")),
-            ])),
-            Box::new(MoveToTarget::new("target_pos")),
-        ]))))
+        if let Some(range) = meta("view_range").and_then(|v| v.try_to::<i32>().ok()) {
+            perception.view_range = range;
+        }
+        if let Some(angle) = meta("view_angle").and_then(|v| v.try_to::<f32>().ok()) {
+            perception.view_angle = angle;
+        }
+        if let Some(radius) = meta("hearing_radius").and_then(|v| v.try_to::<i32>().ok()) {
+            perception.hearing_radius = radius;
+        }
+        perception
     }
 
     fn get_id(&self) -> CharacterId {
@@ -135,6 +161,11 @@ impl Character {
             logic.reset();
         }
     }
+
+    // Hands the character logic over to the World simulation, the node keeps only the sprite
+    pub fn take_logic(&mut self) -> Option<NPCCharacterLogic> {
+        self.logic.take()
+    }
 }
 
 #[godot_api]
@@ -144,6 +175,7 @@ impl IArea2D for Character {
             base,
             logic: None,
             logic_map: None,
+            behaviour: None,
         }
     }
 
@@ -156,16 +188,13 @@ impl IArea2D for Character {
             .get_node_as::<AnimatedSprite2D>("AnimatedSprite2D");
         let animator = Box::new(GodotAnimator::new(sprite));
 
-        //build BT tree
-        let tree = self.build_tree();
-
         //get id from meta
         let id = self.get_id();
         log_info!("Character[{}] id: {}", &name, id);
 
+        // the behaviour tree is built once the logic map is set
         let mut logic = NPCCharacterLogic::new(id, animator);
-
-        logic.bt = tree;
+        logic.set_perception(self.get_perception());
 
         self.logic = Some(logic);
 
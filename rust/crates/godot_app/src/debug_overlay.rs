@@ -1,7 +1,20 @@
+use godot::classes::ThemeDB;
 use godot::prelude::*;
 use std::sync::Arc;
 
-use game_core::map::LogicMap;
+use game_core::bt::{NodeStatus, TickTrace};
+use game_core::map::{LogicMap, WorldObject};
+
+const TRACE_FONT_SIZE: i32 = 10;
+const TRACE_LINE_HEIGHT: f32 = 12.0;
+
+fn status_color(status: NodeStatus) -> Color {
+    match status {
+        NodeStatus::SUCCESS => Color::from_rgba(0.2, 0.9, 0.2, 1.0),
+        NodeStatus::FAILURE => Color::from_rgba(0.9, 0.2, 0.2, 1.0),
+        NodeStatus::RUNNING => Color::from_rgba(1.0, 0.85, 0.0, 1.0),
+    }
+}
 
 #[derive(GodotClass)]
 #[class(base=Node2D)]
@@ -11,6 +24,7 @@ pub struct DebugOverlay {
     logic_map: Option<Arc<LogicMap>>,
     tile_size: f32,
     visible: bool,
+    traces: Vec<(Vector2, TickTrace)>, // the last BT tick of every NPC, drawn next to it
 }
 
 #[godot_api]
@@ -21,6 +35,7 @@ impl INode2D for DebugOverlay {
             logic_map: None,
             tile_size: 64.0,
             visible: true,
+            traces: Vec::new(),
         }
     }
 
@@ -58,6 +73,34 @@ impl INode2D for DebugOverlay {
                 }
             }
         }
+
+        // items and closed doors
+        for placed in map.objects().objects() {
+            let origin = Vector2::new(
+                placed.x as f32 * self.tile_size,
+                placed.y as f32 * self.tile_size,
+            );
+            match placed.object {
+                WorldObject::Item(_) => {
+                    let center = origin + Vector2::new(self.tile_size / 2.0, self.tile_size / 2.0);
+                    self.base_mut().draw_circle(
+                        center,
+                        self.tile_size / 6.0,
+                        Color::from_rgba(1.0, 0.85, 0.0, 0.8),
+                    );
+                }
+                WorldObject::Door { open: false, .. } => {
+                    let rect = Rect2::new(origin, Vector2::new(self.tile_size, self.tile_size));
+                    self.base_mut()
+                        .draw_rect_ex(rect, Color::from_rgba(0.5, 0.3, 0.1, 0.6))
+                        .filled(true)
+                        .done();
+                }
+                _ => (),
+            }
+        }
+
+        self.draw_traces();
     }
 }
 
@@ -71,4 +114,42 @@ impl DebugOverlay {
         self.visible = !self.visible;
         self.base_mut().queue_redraw();
     }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Sets the behaviour tree traces to show, with the positions of the characters
+    pub fn set_traces(&mut self, traces: Vec<(Vector2, TickTrace)>) {
+        self.traces = traces;
+    }
+
+    // Visited nodes coloured by their status, then the changed blackboard keys
+    fn draw_traces(&mut self) {
+        let Some(font) = ThemeDB::singleton().get_fallback_font() else {
+            return;
+        };
+
+        for (position, trace) in self.traces.clone() {
+            let mut line = position + Vector2::new(self.tile_size / 2.0, -self.tile_size);
+            for node in &trace.nodes {
+                let text = format!("{}{} {:?}", "  ".repeat(node.depth), node.name, node.status);
+                self.base_mut()
+                    .draw_string_ex(&font, line, text.as_str())
+                    .font_size(TRACE_FONT_SIZE)
+                    .modulate(status_color(node.status))
+                    .done();
+                line.y += TRACE_LINE_HEIGHT;
+            }
+            for diff in &trace.blackboard {
+                let text = format!("{}: {:?}", diff.key, diff.after);
+                self.base_mut()
+                    .draw_string_ex(&font, line, text.as_str())
+                    .font_size(TRACE_FONT_SIZE)
+                    .modulate(Color::from_rgba(0.8, 0.8, 0.8, 1.0))
+                    .done();
+                line.y += TRACE_LINE_HEIGHT;
+            }
+        }
+    }
 }
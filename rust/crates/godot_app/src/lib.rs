@@ -6,7 +6,6 @@ mod grid_overlay;
 mod scene;
 mod scripted_character;
 
-use game_core::ai::worker::{init_bt_system, shutdown_bt_system};
 use godot::classes::{ITileMapLayer, TileMapLayer};
 use godot::prelude::*;
 
@@ -48,20 +47,9 @@ unsafe impl ExtensionLibrary for GoblinExtension {
 
                 init_logger(Box::new(GodotLogger) as Box<dyn Logger + Send + Sync>);
                 log_info!("Logger is ready!");
-
-                log_info!("Initializing the AI system");
-                init_bt_system();
-                log_info!("AI System is ready!");
+                // BT schedulers are owned by the World of each scene and stop with it
             }
             _ => (),
         }
     }
-
-    fn on_level_deinit(level: InitLevel) {
-        if level == InitLevel::Scene {
-            godot_print!("Shutting down BT worker thread...");
-            shutdown_bt_system();
-            godot_print!("BT worker thread stopped.");
-        }
-    }
 }
@@ -1,5 +1,10 @@
+use game_core::ai::worker::BtExecutionMode;
 use game_core::executor::ExecutorResult;
-use game_core::CommandExecutor;
+use game_core::level::{Level, LevelEvaluator, LevelStatus};
+use game_core::replay::Recorder;
+use game_core::save::SaveGame;
+use game_core::World;
+use godot::classes::notify::Node2DNotification;
 use godot::classes::{CodeEdit, INode2D, Node2D, RichTextLabel, TextureButton, TileMapLayer};
 use godot::prelude::*;
 use platform::logger::LogType;
@@ -10,10 +15,16 @@ use crate::grid_overlay::GridOverlay;
 use crate::scripted_character::ScriptedCharacter;
 use godot::classes::Input;
 use platform::{log_debug, log_error, log_info};
+use scripting_vm::vm::script_error::ScriptError;
 use scripting_vm::ScriptVM;
 use std::sync::Arc;
 
-use game_core::map::{LogicCell, LogicMap, StepType};
+use game_core::map::{LogicCell, LogicMap, StepType, WorldObject};
+
+// the level in progress, written when the game is closed
+const SAVE_FILE: &str = "savegame.ron";
+// the last run of the script, attached to bug reports
+const RECORDING_FILE: &str = "replay.ron";
 
 fn get_step_type(step_type: &str) -> StepType {
     match step_type {
@@ -35,36 +46,50 @@ fn read_logic_cell(tilemap: &TileMapLayer, cell: Vector2i) -> Option<LogicCell>
     }
 }
 
+// Objects are painted on the optional "objects_map" layer. Its tileset defines the custom data
+// layers "item" (item name), "door" (a closed door) and "key" (item which opens the door)
+fn read_world_object(tilemap: &TileMapLayer, cell: Vector2i) -> Option<WorldObject> {
+    let tile_data = tilemap.get_cell_tile_data(cell)?;
+    if tile_data.get_custom_data("door").to::<bool>() {
+        let key = tile_data.get_custom_data("key").to::<String>();
+        return Some(WorldObject::Door {
+            open: false,
+            key: if key.is_empty() { None } else { Some(key) },
+        });
+    }
+    let item = tile_data.get_custom_data("item").to::<String>();
+    if item.is_empty() {
+        None
+    } else {
+        Some(WorldObject::Item(item))
+    }
+}
+
 #[derive(GodotClass)]
 #[class(base=Node2D)]
 struct Scene {
     base: Base<Node2D>,
-    logic_map: Option<Arc<LogicMap>>,
+    world: Option<World>, // owns the map and the logic of all characters
     code_editor: Option<Gd<CodeEdit>>,
-    scripted_character: Option<Gd<ScriptedCharacter>>,
-    executor: CommandExecutor,
     log_box: Option<Gd<RichTextLabel>>,
     highlighted_line: i32,
     script_vm: Option<ScriptVM>,
+    level_reported: bool, // the end of the level has been shown to the player
+    recorder: Option<Recorder>, // records the current run of the script
+    running_code: Option<String>, // the code of the current run, saved with the level
+    scripted_character: Option<Gd<ScriptedCharacter>>, // the sprite of the goblin
+    showing_variables: bool, // the variables of the paused script are in the log box
 }
 
 #[godot_api]
 impl Scene {
     fn reset(&mut self) {
-        // Reset executor
-        self.executor.reset();
-
-        // Reset all characters to their start position
-        let sorting_node = self.base().get_node_as::<Node2D>("SortingNode2D");
-        let children = sorting_node.get_children();
-        for node in children.iter_shared() {
-            if let Ok(mut character) = node.clone().try_cast::<Character>() {
-                character.bind_mut().reset();
-            }
-            if let Ok(mut character) = node.clone().try_cast::<ScriptedCharacter>() {
-                character.bind_mut().reset();
-            }
+        // Reset the characters, the executor, the map objects and the objectives
+        if let Some(world) = &mut self.world {
+            world.reset();
         }
+        self.level_reported = false;
+        self.running_code = None;
 
         // Clear log box
         if let Some(log_box) = &mut self.log_box {
@@ -92,26 +117,219 @@ impl Scene {
         }
     }
 
+    #[func]
+    fn on_breakpoint_toggled(&mut self, line: i64) {
+        // the editor counts the lines from 0, the script positions from 1
+        if let Some(world) = &mut self.world {
+            world
+                .executor_mut()
+                .debugger_mut()
+                .toggle_breakpoint(line as usize + 1);
+        }
+    }
+
+    // List the variables of the paused script in the log box, once per pause
+    fn show_variables(&mut self) {
+        if self.showing_variables {
+            return;
+        }
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        self.showing_variables = true;
+        let Some(variables) = world.executor_mut().variables() else {
+            return;
+        };
+
+        let mut text = String::from("Paused\n");
+        for (title, list) in [
+            ("Globals", &variables.globals),
+            ("Locals", &variables.locals),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+            text += &format!("{}:\n", title);
+            for variable in list {
+                text += &format!("  {} = {}\n", variable.name, variable.value);
+            }
+        }
+        if let Some(log_box) = &mut self.log_box {
+            log_box.set_text(&text);
+        }
+    }
+
+    // Tell the player when the puzzle is solved or lost
+    fn report_level_status(&mut self) {
+        if self.level_reported {
+            return;
+        }
+        let message = match self.world.as_ref().and_then(|world| world.level_status()) {
+            Some(LevelStatus::Success) => "Level complete!".to_string(),
+            Some(LevelStatus::Failed(reason)) => reason.clone(),
+            _ => return,
+        };
+
+        log_info!("Level finished: {}", message);
+        self.level_reported = true;
+        if let Some(log_box) = &mut self.log_box {
+            log_box.set_text(&message);
+        }
+    }
+
+    // highlight the current executing line in the editor
+    fn highlight_current_line(&mut self) {
+        let Some(world) = &self.world else {
+            return;
+        };
+        let line = world.executor().current_line() as i32 - 1;
+        if let Some(code_editor) = &mut self.code_editor {
+            // clear previous highlight
+            if self.highlighted_line >= 0 {
+                code_editor.set_line_background_color(
+                    self.highlighted_line,
+                    Color::from_rgba(0.0, 0.0, 0.0, 0.0),
+                ); // transparent
+            }
+            // set new highlight
+            code_editor.set_line_background_color(line, Color::from_rgba(0.4, 0.8, 0.4, 0.4)); //semi-transparent
+            self.highlighted_line = line;
+            code_editor.set_caret_line(line);
+            code_editor.center_viewport_to_caret();
+        }
+    }
+
     fn run_script(&mut self, code: String) {
         log_debug!("Run script: {}", &code);
         self.clear_highlights();
 
         if let Some(vm) = &mut self.script_vm {
             vm.set_code(&code);
-            match vm.run_script() {
-                Ok(commands) => {
-                    log_debug!("Scene: Commands for executor: {:?}", commands);
-                    self.executor.set_commands(commands);
-                }
-                Err(e) => {
-                    log_debug!("Script error: {:?}", e);
-                    if let Some(log_box) = &mut self.log_box {
-                        log_box.set_text(&e.message);
-                    }
-                    self.highlight_errors(e.line - 1);
+            // the script is suspended at every API call and resumed by the executor
+            // once the goblin has performed the command
+            if let Some(world) = &mut self.world {
+                let recorder = Recorder::new(Some(code.clone()), world.time_step());
+                world.set_source(recorder.record_source(Box::new(vm.start_coroutine())));
+                self.recorder = Some(recorder);
+                self.running_code = Some(code);
+            }
+        }
+    }
+
+    // Add the messages printed with `console.log()` to the log box, with their line
+    fn show_console(&mut self) {
+        let Some(vm) = &mut self.script_vm else {
+            return;
+        };
+        let messages = vm.take_console();
+        let Some(log_box) = &mut self.log_box else {
+            return;
+        };
+        for message in messages {
+            let color = match message.level {
+                LogType::Error => Color::from_rgb(1.0, 0.4, 0.3),
+                LogType::Warn => Color::from_rgb(1.0, 0.8, 0.3),
+                _ => Color::from_rgb(1.0, 1.0, 1.0),
+            };
+            log_box.push_color(color);
+            log_box.add_text(&format!("Line {}: {}\n", message.line, message.text));
+            log_box.pop();
+        }
+    }
+
+    // Show the speech bubble of the goblin, given with `say()`
+    fn show_speech(&mut self) {
+        let (Some(world), Some(character)) = (&self.world, &mut self.scripted_character) else {
+            return;
+        };
+        let speech = world
+            .player()
+            .and_then(|player| player.speech())
+            .map(|speech| speech.text.as_str());
+        character.bind_mut().show_speech(speech);
+    }
+
+    // Show the error which stopped the running script
+    fn report_script_error(&mut self) {
+        let Some(err) = self.script_vm.as_mut().and_then(|vm| vm.take_error()) else {
+            return;
+        };
+        self.show_script_error(&err);
+    }
+
+    // Show the friendly message of the error, translated with `tr()` if the project has
+    // a translation for its key, and mark the line of the error
+    fn show_script_error(&mut self, err: &ScriptError) {
+        log_debug!("Script error: {:?}", err);
+        let message = err.friendly_message_with(|key| {
+            let text = self.base().tr(&StringName::from(key)).to_string();
+            // tr() returns the key itself when there is no translation
+            (text != key).then_some(text)
+        });
+        if let Some(log_box) = &mut self.log_box {
+            log_box.set_text(&message);
+        }
+        if err.line > 0 {
+            self.highlight_errors(err.line - 1);
+        }
+    }
+
+    // Keep the level in progress and the code of the running script, which is replayed
+    // on load: not the text of the editor, which may have been edited since the run
+    fn save_game(&self) {
+        let Some(world) = &self.world else {
+            return;
+        };
+        let mut save = world.save();
+        save.script = self.running_code.clone();
+
+        match save.save_to_file(SAVE_FILE) {
+            Ok(()) => log_info!("Game saved to {}", SAVE_FILE),
+            Err(err) => log_error!("Cannot save the game: {}", err),
+        }
+    }
+
+    // Write the last run, so it can be replayed with `console_app --replay`
+    fn save_recording(&self) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        if let Err(err) = recorder.recording().save_to_file(RECORDING_FILE) {
+            log_error!("Cannot write the recording: {}", err);
+        }
+    }
+
+    // Resume the level where it was left, the running script is restarted and fast-forwarded
+    fn load_game(&mut self) {
+        let save = match SaveGame::load_from_file(SAVE_FILE) {
+            Ok(save) => save,
+            Err(err) => {
+                log_info!("No saved game loaded: {}", err);
+                return;
+            }
+        };
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        if let Err(err) = world.load(&save) {
+            log_error!("Cannot load the saved game: {}", err);
+            return;
+        }
+        self.running_code = None;
+
+        if let Some(code) = &save.script {
+            if let Some(editor) = &mut self.code_editor {
+                editor.set_text(code);
+            }
+            if let Some(vm) = &mut self.script_vm {
+                vm.set_code(code);
+                match world.resume_source(Box::new(vm.start_coroutine()), &save) {
+                    Ok(()) => self.running_code = Some(code.clone()),
+                    Err(err) => log_error!("Cannot resume the saved script: {}", err),
                 }
             }
         }
+        log_info!("Game loaded from {}", SAVE_FILE);
     }
 
     fn highlight_errors(&mut self, line: i32) {
@@ -134,7 +352,11 @@ impl Scene {
 impl INode2D for Scene {
     fn init(base: Base<Node2D>) -> Self {
         let script_vm = match ScriptVM::new("") {
-            Ok(vm) => Some(vm),
+            Ok(mut vm) => {
+                // the variables of the script can be inspected when it pauses
+                vm.set_debug(true);
+                Some(vm)
+            }
             Err(err) => {
                 log_error!("Cannot initialize ScriptVM due to error: {}", err);
                 None
@@ -143,13 +365,16 @@ impl INode2D for Scene {
 
         Self {
             base,
-            logic_map: None,
+            world: None,
             code_editor: None,
-            scripted_character: None,
-            executor: CommandExecutor::new(),
             log_box: None,
             highlighted_line: -1,
             script_vm,
+            level_reported: false,
+            recorder: None,
+            running_code: None,
+            scripted_character: None,
+            showing_variables: false,
         }
     }
 
@@ -200,10 +425,38 @@ impl INode2D for Scene {
             }
         }
 
+        if let Some(objects_tilemap) = self.base().try_get_node_as::<TileMapLayer>("objects_map") {
+            for y in 0..height {
+                for x in 0..width {
+                    let cell = Vector2i::new(origin_x + x as i32, origin_y + y as i32);
+                    if let Some(object) = read_world_object(&objects_tilemap, cell) {
+                        logic_map
+                            .objects_mut()
+                            .place(platform::types::Vector2Di::new(x as i32, y as i32), object);
+                    }
+                }
+            }
+        }
+
         let _ = logic_map.save_to_file("logic_map.ron");
 
         let logic_arc = Arc::new(logic_map);
-        self.logic_map = Some(logic_arc.clone());
+        let mut world = World::from_arc(logic_arc.clone());
+        // the trees of the NPCs are ticked in parallel, a replay gives the same result
+        world.set_bt_mode(BtExecutionMode::Batch);
+
+        // objectives of the level, the level has no goal without the file
+        let behaviours = match Level::load_from_file("level.ron") {
+            Ok(level) => {
+                log_info!("Level '{}': {:?}", level.name, level.objectives);
+                world.set_level(LevelEvaluator::from_level(&level));
+                level.behaviours
+            }
+            Err(err) => {
+                log_info!("No level objectives loaded: {}", err);
+                Default::default()
+            }
+        };
 
         // update logic map in Characters
         // get SortingNode2D, as it keeps all characters
@@ -213,26 +466,46 @@ impl INode2D for Scene {
             log_info!("==>> Child: {}", &node.get_name());
             log_info!("==>> Child type: {}", &node.get_class());
 
+            // the characters keep their sprites, the logic is simulated by the World
             if let Ok(mut character) = node.clone().try_cast::<Character>() {
-                character.bind_mut().set_logic_map(logic_arc.clone());
+                let mut character = character.bind_mut();
+                character.set_behaviours(&behaviours);
+                character.set_logic_map(logic_arc.clone());
+                if let Some(logic) = character.take_logic() {
+                    world.add_npc(logic);
+                }
             }
 
             // update the scripted character as well
             if let Ok(mut character) = node.try_cast::<ScriptedCharacter>() {
-                character.bind_mut().set_logic_map(logic_arc.clone());
-                self.scripted_character = Some(character.clone());
+                {
+                    let mut character = character.bind_mut();
+                    character.set_logic_map(logic_arc.clone());
+                    if let Some(logic) = character.take_logic() {
+                        world.set_player(logic);
+                    }
+                }
+                self.scripted_character = Some(character);
             }
         }
-
         let mut overlay = self.base().get_node_as::<DebugOverlay>("DebugOverlay");
         overlay.bind_mut().set_logic_map(logic_arc.clone());
+        // the behaviour trees are traced while the debug overlay shows them
+        world.tracer().set_enabled(overlay.bind().is_visible());
+        self.world = Some(world);
 
         let mut grid_overlay = self.base().get_node_as::<GridOverlay>("GridOverlay");
         grid_overlay.bind_mut().set_logic_map(logic_arc.clone());
 
         // Get CodeEdit
         log_debug!("CodeEditor");
-        let editor = self.base().get_node_as::<CodeEdit>("CodeEdit");
+        let mut editor = self.base().get_node_as::<CodeEdit>("CodeEdit");
+        // a click in the gutter toggles a breakpoint of the debugger
+        editor.set_draw_breakpoints_gutter(true);
+        editor.connect(
+            "breakpoint_toggled",
+            &self.base_mut().callable("on_breakpoint_toggled"),
+        );
         self.code_editor = Some(editor);
 
         log_debug!("CodeEditor loaded: {:?}", &self.code_editor);
@@ -245,70 +518,78 @@ impl INode2D for Scene {
 
         button.connect("pressed", &self.base_mut().callable("on_run_pressed"));
 
+        self.load_game();
+
         //let tilemap = self.base().get_node_as::<TileMapLayer>("logic_map");
     }
 
+    fn on_notification(&mut self, what: Node2DNotification) {
+        if what == Node2DNotification::WM_CLOSE_REQUEST {
+            self.save_game();
+            self.save_recording();
+        }
+    }
+
     fn process(&mut self, delta: f32) {
-        if let Some(logic_map) = &self.logic_map {
-            if let Some(character) = &mut self.scripted_character {
-                let mut char_bind = character.bind_mut();
-                if let Some(logic) = &mut char_bind.logic {
-                    let result = self.executor.tick(delta, logic, logic_map);
-
-                    log_debug!("Executor result: {:?}", result);
-
-                    if result != ExecutorResult::Empty {
-                        let current_line = self.executor.current_line();
-                        let line = current_line as i32 - 1;
-                        if let Some(code_editor) = &mut self.code_editor {
-                            // clear previous highlight
-                            if self.highlighted_line >= 0 {
-                                code_editor.set_line_background_color(
-                                    self.highlighted_line,
-                                    Color::from_rgba(0.0, 0.0, 0.0, 0.0),
-                                ); // transparent
-                            }
-                            // set new highlight
-                            code_editor.set_line_background_color(
-                                line,
-                                Color::from_rgba(0.4, 0.8, 0.4, 0.4),
-                            ); //semi-transparent
-                            self.highlighted_line = line;
-                            code_editor.set_caret_line(line);
-                            code_editor.center_viewport_to_caret();
-                        }
-                        // highlight the current executing line in the editor
-                        if let Some(code_editor) = &mut self.code_editor {
-                            code_editor.set_caret_line(current_line as i32 - 1);
-                            code_editor.center_viewport_to_caret();
-                        }
-                    } else {
-                        //if the commands are empty, call update function in the script
-                        if let Some(vm) = &mut self.script_vm {
-                            let snapshot = logic.snapshot();
-                            match vm.tick(&snapshot) {
-                                Ok(commands) => {
-                                    log_debug!("Update call: commands: {:?}", commands);
-                                    CommandExecutor::apply(commands, logic);
-                                }
-                                Err(err) => {
-                                    log_debug!("Script error: {:?}", err);
-                                    if let Some(log_box) = &mut self.log_box {
-                                        log_box.set_text(&err.message);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        world.step(delta);
+        let result = world.executor_result();
+        log_debug!("Executor result: {:?}", result);
+
+        if result == ExecutorResult::Paused {
+            self.highlight_current_line();
+            self.show_variables();
+        } else if result != ExecutorResult::Empty {
+            self.showing_variables = false;
+            self.highlight_current_line();
+        }
+        if let (Some(recorder), Some(world)) = (&mut self.recorder, &self.world) {
+            recorder.record_frame(delta, world);
         }
 
+        self.show_console();
+        self.show_speech();
+        self.report_script_error();
+        self.report_level_status();
+
         let input = Input::singleton();
 
+        let mut overlay = self.base().get_node_as::<DebugOverlay>("DebugOverlay");
         if input.is_action_just_pressed("toggle_debug_overlay") {
-            let mut overlay = self.base().get_node_as::<DebugOverlay>("DebugOverlay");
             overlay.bind_mut().toggle();
+            if let Some(world) = &self.world {
+                world.tracer().set_enabled(overlay.bind().is_visible());
+            }
+        }
+        if let Some(world) = &self.world {
+            if world.tracer().is_enabled() {
+                let traces = world
+                    .npcs()
+                    .iter()
+                    .filter_map(|npc| {
+                        let trace = world.tracer().last(npc.get_id())?;
+                        let position = npc.get_position();
+                        Some((Vector2::new(position.x, position.y), trace))
+                    })
+                    .collect();
+                overlay.bind_mut().set_traces(traces);
+            }
+        }
+
+        if let Some(world) = &mut self.world {
+            let executor = world.executor_mut();
+            if input.is_action_just_pressed("debug_pause") {
+                if executor.is_paused() {
+                    executor.resume();
+                } else {
+                    executor.pause();
+                }
+            }
+            if input.is_action_just_pressed("debug_step") && executor.is_paused() {
+                executor.step_over();
+            }
         }
 
         if input.is_action_just_pressed("toggle_grid_overlay") {
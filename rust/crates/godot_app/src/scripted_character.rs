@@ -1,6 +1,7 @@
 use game_core::character::CharacterId;
 use game_core::map::LogicMap;
-use godot::classes::{AnimatedSprite2D, Area2D, IArea2D};
+use godot::classes::{AnimatedSprite2D, Area2D, IArea2D, Label};
+use godot::global::HorizontalAlignment;
 use godot::prelude::*;
 use platform::logger::LogType;
 
@@ -16,6 +17,7 @@ pub struct ScriptedCharacter {
     base: Base<Area2D>,
     pub logic: Option<ScriptedCharacterLogic>,
     logic_map: Option<Arc<LogicMap>>,
+    speech_label: Option<Gd<Label>>, // the speech bubble, created on the first `say()`
 }
 
 impl ScriptedCharacter {
@@ -45,6 +47,39 @@ impl ScriptedCharacter {
         }
     }
 
+    // Hands the character logic over to the World simulation, the node keeps only the sprite
+    pub fn take_logic(&mut self) -> Option<ScriptedCharacterLogic> {
+        self.logic.take()
+    }
+
+    /// Shows the text over the character, or hides the bubble with `None`
+    pub fn show_speech(&mut self, text: Option<&str>) {
+        if text.is_none() && self.speech_label.is_none() {
+            return;
+        }
+        let mut label = self.speech_label();
+        match text {
+            Some(text) => {
+                label.set_text(text);
+                label.show();
+            }
+            None => label.hide(),
+        }
+    }
+
+    fn speech_label(&mut self) -> Gd<Label> {
+        if let Some(label) = &self.speech_label {
+            return label.clone();
+        }
+        let mut label = Label::new_alloc();
+        label.set_horizontal_alignment(HorizontalAlignment::CENTER);
+        label.set_position(Vector2::new(-64.0, -72.0));
+        label.set_size(Vector2::new(128.0, 24.0));
+        self.base_mut().add_child(&label);
+        self.speech_label = Some(label.clone());
+        label
+    }
+
     pub fn reset(&mut self) {
         if let Some(logic) = &mut self.logic {
             logic.reset();
@@ -59,6 +94,7 @@ impl IArea2D for ScriptedCharacter {
             base,
             logic: None,
             logic_map: None,
+            speech_label: None,
         }
     }
 
@@ -1,5 +1,6 @@
 use crate::types::Vector2D;
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub enum Direction {
@@ -21,6 +22,22 @@ impl Display for Direction {
     }
 }
 
+// Parses direction names used in scripts: "north", "up", "East", ...
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Direction::*;
+        match s.to_lowercase().as_str() {
+            "north" | "up" => Ok(NORTH),
+            "south" | "down" => Ok(SOUTH),
+            "west" | "left" => Ok(WEST),
+            "east" | "right" => Ok(EAST),
+            _ => Err(format!("Unknown direction: {}", s)),
+        }
+    }
+}
+
 impl Direction {
     pub fn to_vector(&self) -> Vector2D {
         use Direction::*;
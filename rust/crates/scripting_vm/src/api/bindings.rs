@@ -1,84 +1,318 @@
 use boa_engine::{
-    object::FunctionObjectBuilder, property::Attribute, Context, JsResult, JsString, JsValue,
-    NativeFunction,
+    js_string, object::FunctionObjectBuilder, property::Attribute, Context, JsNativeError,
+    JsResult, JsString, JsValue, NativeFunction,
 };
 
-use crate::{api::script_event::ScriptEvent, runtime::script_instance::ScriptInstance};
+use crate::{
+    api::console::register_console,
+    api::map_view::{cell_info_object, register_map_view},
+    api::memory::register_memory,
+    api::params::{check_args, Arg, Param},
+    api::script_event::ScriptEvent,
+    api::snapshot::register_character,
+    runtime::script_instance::ScriptInstance,
+    vm::vm::collapse_events,
+};
 use game_core::api::commands::PlayerCommand;
+use game_core::api::sensing::WorldView;
 use platform::types::Vector2Di;
 
-fn register_function(ctx: &mut Context, name: &str, cmd: PlayerCommand, args: usize) {
-    let func = FunctionObjectBuilder::new(ctx.realm(), unsafe {
-        NativeFunction::from_closure(move |_this, args, ctx| {
+/// Names of all functions exposed to scripts.
+/// Every call of these functions is tracked by the preprocessor (see `instrument_code`).
+pub const API_FUNCTIONS: &[&str] = &[
+    "step_right",
+    "step_left",
+    "step_up",
+    "step_down",
+    "set_position",
+    "wait",
+    "pick",
+    "open",
+    "say",
+    "can_move",
+    "cell_ahead",
+    "height_at",
+    "is_stairs_ahead",
+];
+
+type CommandFn = fn(&[Arg]) -> PlayerCommand;
+type QueryFn = fn(&WorldView, &[Arg], &mut Context) -> JsResult<JsValue>;
+
+/// What an API function does when it is called
+#[derive(Clone, Copy)]
+pub enum ApiKind {
+    Command(CommandFn), // gives a command to the character
+    Query(QueryFn),     // answers a question about the world around the character
+}
+
+/// A function exposed to scripts. The arguments of every call are checked against
+/// `params` before the function runs, a wrong call throws a TypeError.
+pub struct ApiFunction {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    pub kind: ApiKind,
+}
+
+/// All functions exposed to scripts, registered by `register_api`.
+/// A new function is added here and to `API_FUNCTIONS`.
+pub const API: &[ApiFunction] = &[
+    ApiFunction {
+        name: "step_right",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::MoveEast),
+    },
+    ApiFunction {
+        name: "step_left",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::MoveWest),
+    },
+    ApiFunction {
+        name: "step_up",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::MoveNorth),
+    },
+    ApiFunction {
+        name: "step_down",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::MoveSouth),
+    },
+    ApiFunction {
+        name: "set_position",
+        params: &[Param::integer("x"), Param::integer("y")],
+        kind: ApiKind::Command(|args| {
+            PlayerCommand::SetPosition(Vector2Di {
+                x: args[0].integer(),
+                y: args[1].integer(),
+            })
+        }),
+    },
+    ApiFunction {
+        name: "wait",
+        params: &[Param::number("seconds")],
+        kind: ApiKind::Command(|args| PlayerCommand::Wait(args[0].number() as f32)),
+    },
+    ApiFunction {
+        name: "pick",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::Pick),
+    },
+    ApiFunction {
+        name: "open",
+        params: &[],
+        kind: ApiKind::Command(|_| PlayerCommand::Open),
+    },
+    ApiFunction {
+        name: "say",
+        params: &[Param::text("text")],
+        kind: ApiKind::Command(|args| PlayerCommand::Say(args[0].text().to_string())),
+    },
+    ApiFunction {
+        name: "can_move",
+        params: &[Param::direction("direction")],
+        kind: ApiKind::Query(can_move),
+    },
+    ApiFunction {
+        name: "cell_ahead",
+        params: &[],
+        kind: ApiKind::Query(cell_ahead),
+    },
+    ApiFunction {
+        name: "height_at",
+        params: &[Param::integer("dx"), Param::integer("dy")],
+        kind: ApiKind::Query(height_at),
+    },
+    ApiFunction {
+        name: "is_stairs_ahead",
+        params: &[],
+        kind: ApiKind::Query(is_stairs_ahead),
+    },
+];
+
+// Registers a function giving a command to the character
+fn register_command(ctx: &mut Context, function: &'static ApiFunction, command: CommandFn) {
+    let func = FunctionObjectBuilder::new(
+        ctx.realm(),
+        NativeFunction::from_copy_closure(move |_this, args, ctx| {
+            let args = check_args(function.name, function.params, args)?;
             let instance = ctx
                 .get_data::<ScriptInstance>()
                 .expect("ScriptInstance missing");
 
-            match cmd {
-                PlayerCommand::SetPosition(_) => {
-                    if args.len() > 1 {
-                        match (args[0].as_i32(), args[1].as_i32()) {
-                            (Some(x), Some(y)) => {
-                                instance.events.borrow_mut().push(ScriptEvent::Command(
-                                    PlayerCommand::SetPosition(Vector2Di { x, y }),
-                                ));
-                            }
-                            (_, _) => (),
-                        }
-                    }
-                }
-                PlayerCommand::Wait(_) => {
-                    if args.len() > 0 {
-                        match args[0].as_number() {
-                            Some(val) => instance
-                                .events
-                                .borrow_mut()
-                                .push(ScriptEvent::Command(PlayerCommand::Wait(val as f32))),
-                            _ => (),
-                        }
+            instance.budget.count_command()?;
+            instance
+                .events
+                .borrow_mut()
+                .push(ScriptEvent::Command(command(&args)));
+
+            // Coroutine mode: suspend until the host has performed the command
+            let Some(stepper) = instance.stepper.clone() else {
+                return Ok(JsValue::undefined());
+            };
+            let commands = collapse_events(std::mem::take(&mut *instance.events.borrow_mut()));
+            match stepper.suspend(commands, ctx) {
+                Some(result) => {
+                    // the time spent by the host doesn't count for the script
+                    if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+                        instance.budget.restart_clock();
                     }
+                    Ok(JsValue::from(result))
                 }
-                _ => {
-                    instance.events.borrow_mut().push(ScriptEvent::Command(cmd));
-                }
+                None => Err(JsNativeError::error()
+                    .with_message("Script was stopped")
+                    .into()),
+            }
+        }),
+    )
+    .name(function.name)
+    .length(function.params.len())
+    .build();
+
+    let _ = ctx.register_global_property(JsString::from(function.name), func, Attribute::all());
+}
+
+// Registers a sensing function: it does not emit a command, but answers a question
+// about the world around the character
+fn register_query(ctx: &mut Context, function: &'static ApiFunction, query: QueryFn) {
+    let func = FunctionObjectBuilder::new(
+        ctx.realm(),
+        NativeFunction::from_copy_closure(move |_this, args, ctx| {
+            let args = check_args(function.name, function.params, args)?;
+            let instance = ctx.get_data::<ScriptInstance>();
+            if let Some(instance) = instance {
+                instance.forget_position();
             }
-            Ok(JsValue::undefined())
-        })
-    })
-    .name(name)
-    .length(args)
+            let world = instance.and_then(|instance| instance.world.borrow().clone());
+
+            match world {
+                Some(world) => query(&world, &args, ctx),
+                None => Err(JsNativeError::error()
+                    .with_message(format!(
+                        "{}() cannot see the world right now",
+                        function.name
+                    ))
+                    .into()),
+            }
+        }),
+    )
+    .name(function.name)
+    .length(function.params.len())
     .build();
 
-    let _ = ctx.register_global_property(JsString::from(name), func, Attribute::all());
+    let _ = ctx.register_global_property(JsString::from(function.name), func, Attribute::all());
+}
+
+// can_move("north"): true if the character can step in the direction
+fn can_move(world: &WorldView, args: &[Arg], _ctx: &mut Context) -> JsResult<JsValue> {
+    Ok(JsValue::from(world.can_move(args[0].direction())))
+}
+
+// cell_ahead(): { x, y, walkable, height, stairs } of the cell in front of the character
+fn cell_ahead(world: &WorldView, _args: &[Arg], ctx: &mut Context) -> JsResult<JsValue> {
+    Ok(cell_info_object(&world.cell_ahead(), ctx)?.into())
+}
+
+// height_at(dx, dy): level of the cell at the offset from the character
+fn height_at(world: &WorldView, args: &[Arg], _ctx: &mut Context) -> JsResult<JsValue> {
+    Ok(JsValue::from(
+        world.height_at(args[0].integer(), args[1].integer()),
+    ))
 }
 
-fn step_binding(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
-    let line = args.get(0).and_then(|v| v.as_number()).unwrap_or(0.0) as usize;
+fn is_stairs_ahead(world: &WorldView, _args: &[Arg], _ctx: &mut Context) -> JsResult<JsValue> {
+    Ok(JsValue::from(world.is_stairs_ahead()))
+}
+
+// __line(line, column, callee[, scope]): records the source position of the next API call
+// and returns the callee, so `__line(2, 5, step_up)()` behaves like `step_up()`
+fn line_binding(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let line = args.first().and_then(|v| v.as_number()).unwrap_or(0.0) as usize;
+    let column = args.get(1).and_then(|v| v.as_number()).unwrap_or(0.0) as usize;
 
     let instance = ctx.get_data::<ScriptInstance>().unwrap();
-    instance.events.borrow_mut().push(ScriptEvent::Line(line));
+    instance.budget.check_time()?;
+    instance
+        .events
+        .borrow_mut()
+        .push(ScriptEvent::Position { line, column });
+
+    // with the debugger: the scope of the call, to read the variables if the script pauses
+    if let Some(scope) = args.get(3) {
+        ctx.global_object()
+            .set(js_string!("__scope"), scope.clone(), false, ctx)?;
+    }
+
+    Ok(args.get(2).cloned().unwrap_or_default())
+}
 
-    Ok(JsValue::undefined())
+// __budget(): checks the time left to the script at the start of a function body and of
+// every loop iteration. Returns true, so it can be the condition of a `for (;;)`.
+fn budget_binding(_this: &JsValue, _args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+        instance.budget.check_time()?;
+    }
+
+    Ok(JsValue::from(true))
 }
 
 pub fn register_api(ctx: &mut Context) {
-    register_function(ctx, "step_right", PlayerCommand::MoveEast, 0);
-    register_function(ctx, "step_left", PlayerCommand::MoveWest, 0);
-    register_function(ctx, "step_up", PlayerCommand::MoveNorth, 0);
-    register_function(ctx, "step_down", PlayerCommand::MoveSouth, 0);
-    register_function(
-        ctx,
-        "set_position",
-        PlayerCommand::SetPosition(Vector2Di { x: 0, y: 0 }),
-        2,
-    );
-    register_function(ctx, "wait", PlayerCommand::Wait(0.0), 1);
-
-    // Register __line(N) for source line tracking (inserted by preprocessor)
-    let line_fn = NativeFunction::from_fn_ptr(step_binding);
+    for function in API {
+        match function.kind {
+            ApiKind::Command(command) => register_command(ctx, function, command),
+            ApiKind::Query(query) => register_query(ctx, function, query),
+        }
+    }
+
+    register_console(ctx);
+    register_memory(ctx);
+    register_map_view(ctx);
+    register_character(ctx); // uses `memory` and `map`
+
+    // Register __line(N, C, callee) for source position tracking (inserted by preprocessor)
+    let line_fn = NativeFunction::from_fn_ptr(line_binding);
     let line_func = FunctionObjectBuilder::new(ctx.realm(), line_fn)
         .name("__line")
-        .length(1)
+        .length(3)
         .build();
     let _ = ctx.register_global_property(JsString::from("__line"), line_func, Attribute::all());
+
+    // Register __budget() for the time limit (inserted by preprocessor)
+    let budget_fn = NativeFunction::from_fn_ptr(budget_binding);
+    let budget_func = FunctionObjectBuilder::new(ctx.realm(), budget_fn)
+        .name("__budget")
+        .length(0)
+        .build();
+    let _ = ctx.register_global_property(JsString::from("__budget"), budget_func, Attribute::all());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::preprocessor::instrument_code;
+    use crate::vm::vm::create_context;
+    use boa_engine::Source;
+    use game_core::test_utils::test_world::flat_map;
+    use platform::types::Direction;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_api_names_are_tracked_by_the_preprocessor() {
+        let names: Vec<&str> = API.iter().map(|function| function.name).collect();
+        assert_eq!(names, API_FUNCTIONS);
+    }
+
+    #[test]
+    fn test_calls_without_command_leave_no_events() {
+        let instance = ScriptInstance::default();
+        let map = Arc::new(flat_map(3, 3));
+        *instance.world.borrow_mut() =
+            Some(WorldView::new(map, Vector2Di::new(1, 1), Direction::NORTH));
+        let mut ctx = create_context(instance);
+
+        let code = "for (let i = 0; i < 100; i++) { can_move('north'); console.log(i); }";
+        ctx.eval(Source::from_bytes(&instrument_code(code)))
+            .unwrap();
+        let instance = ctx.get_data::<ScriptInstance>().unwrap();
+        assert!(instance.events.borrow().is_empty());
+        assert_eq!(instance.console.borrow().last().unwrap().line, 1);
+    }
 }
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsResult, JsValue,
+    NativeFunction,
+};
+use platform::logger::LogType;
+use platform::shared::logger_global::log;
+
+use crate::api::script_event::ScriptEvent;
+use crate::runtime::coroutine::CoroutineEvent;
+use crate::runtime::script_instance::ScriptInstance;
+
+/*
+ * `console.log`, `console.warn` and `console.error` for the scripts.
+ * Every message keeps the line of the call: the preprocessor tracks these calls like
+ * the API calls (see `instrument_code`). The host gets the messages of its script
+ * from `ScriptVM::take_console`, they are also written to the platform log.
+ */
+
+/// Console calls tracked by the preprocessor
+pub const CONSOLE_FUNCTIONS: &[&str] = &["console.log", "console.warn", "console.error"];
+
+// Messages kept until the host takes them, the oldest are dropped
+const MAX_CONSOLE_MESSAGES: usize = 200;
+
+/// A message printed by the script
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleMessage {
+    pub level: LogType, // Info for `console.log`
+    pub text: String,
+    pub line: usize, // 0 if the line is not known
+}
+
+/// Writes the message to the platform log and keeps it for the host
+pub(crate) fn record(output: &RefCell<Vec<ConsoleMessage>>, message: ConsoleMessage) {
+    log(
+        message.level,
+        &format!("[script line {}] {}", message.line, message.text),
+    );
+
+    let mut output = output.borrow_mut();
+    if output.len() >= MAX_CONSOLE_MESSAGES {
+        output.remove(0);
+    }
+    output.push(message);
+}
+
+pub(crate) fn register_console(ctx: &mut Context) {
+    let console = ObjectInitializer::new(ctx)
+        .function(
+            NativeFunction::from_fn_ptr(console_log),
+            js_string!("log"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(console_warn),
+            js_string!("warn"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(console_error),
+            js_string!("error"),
+            0,
+        )
+        .build();
+
+    let _ = ctx.register_global_property(js_string!("console"), console, Attribute::all());
+}
+
+fn console_log(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    print(LogType::Info, args, ctx)
+}
+
+fn console_warn(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    print(LogType::Warn, args, ctx)
+}
+
+fn console_error(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    print(LogType::Error, args, ctx)
+}
+
+// The values are printed like the browser console does: strings without quotes,
+// separated by spaces
+fn print(level: LogType, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let text = args
+        .iter()
+        .map(|value| match value.as_string() {
+            Some(text) => text.to_std_string_escaped(),
+            None => value.display().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let Some(instance) = ctx.get_data::<ScriptInstance>() else {
+        return Ok(JsValue::undefined());
+    };
+    instance.budget.check_time()?;
+
+    // `__line` has recorded the position of this call just before it
+    let line = instance
+        .events
+        .borrow()
+        .iter()
+        .rev()
+        .find_map(|event| match event {
+            ScriptEvent::Position { line, .. } => Some(*line),
+            _ => None,
+        })
+        .unwrap_or(0);
+    instance.forget_position();
+    let message = ConsoleMessage { level, text, line };
+
+    // a coroutine hands the message to the host right away
+    match &instance.stepper {
+        Some(stepper) => {
+            let _ = stepper.yield_tx.send(CoroutineEvent::Console(message));
+        }
+        None => instance.console.borrow_mut().push(message),
+    }
+
+    Ok(JsValue::undefined())
+}
@@ -0,0 +1,148 @@
+use boa_engine::{
+    js_string, object::builtins::JsFunction, Context, JsObject, JsResult, JsString, JsValue,
+};
+use game_core::character::snapshot::CharacterSnapshot;
+use game_core::executor::GameEvent;
+use platform::types::Vector2Di;
+
+use crate::api::snapshot::character_object;
+
+/*
+ * Lifecycle hooks: functions the script may define besides its main code.
+ *   start()                 once, after the main code
+ *   update(character, dt)   every frame the goblin has nothing to do, `dt` in seconds
+ *   on_blocked(direction)   a move was blocked: "north", "south", "east" or "west"
+ *   on_arrived(cell)        a move has ended on the cell { x, y }
+ *   on_item_picked(item)    the name of the picked item
+ *   on_seen_by(npc)         an NPC has started to see the goblin: { id, cell_x, cell_y }
+ * A coroutine with `update` or an `on_*` hook keeps running after its main code, so the
+ * global variables keep their values between the calls. The events are handled once the
+ * main code and `start()` are over, an event which happens while a hook runs is handled
+ * after it.
+ * The hooks are read from the globals at every call, so a script may give `update`
+ * another function: `update = next_phase;`.
+ */
+
+pub const START: &str = "start";
+pub const UPDATE: &str = "update";
+
+/// Hooks called for the events of the world
+pub const EVENT_HOOKS: &[&str] = &["on_blocked", "on_arrived", "on_item_picked", "on_seen_by"];
+
+/// The global function `name` of the script, if it is defined
+fn hook(name: JsString, ctx: &mut Context) -> Option<JsFunction> {
+    ctx.global_object().get(name, ctx).ok()?.as_function()
+}
+
+/// Calls the hook if the script has defined it
+pub(crate) fn call_hook(name: &str, args: &[JsValue], ctx: &mut Context) -> JsResult<()> {
+    call(hook(JsString::from(name), ctx), args, ctx)
+}
+
+fn call(hook: Option<JsFunction>, args: &[JsValue], ctx: &mut Context) -> JsResult<()> {
+    if let Some(hook) = hook {
+        hook.call(&JsValue::undefined(), args, ctx)?;
+    }
+    Ok(())
+}
+
+/// Whether the script waits for events after its main code
+pub(crate) fn has_hooks(ctx: &mut Context) -> bool {
+    std::iter::once(UPDATE)
+        .chain(EVENT_HOOKS.iter().copied())
+        .any(|name| hook(JsString::from(name), ctx).is_some())
+}
+
+/// Calls the hooks of the events in their order, then `update(character, dt)`.
+/// The snapshot is kept by the character object until the next call.
+pub(crate) fn run_hooks(
+    events: &[GameEvent],
+    character: Option<CharacterSnapshot>,
+    dt: f32,
+    ctx: &mut Context,
+) -> JsResult<()> {
+    for event in events {
+        let (name, arg) = event_hook(event, ctx)?;
+        call_hook(name, &[arg], ctx)?;
+    }
+
+    let character = match character {
+        Some(snapshot) => character_object(snapshot, ctx)?,
+        None => JsValue::undefined(),
+    };
+    // every frame: the name is a static string, not built for each lookup
+    let update = hook(js_string!("update"), ctx);
+    call(update, &[character, JsValue::from(dt as f64)], ctx)
+}
+
+// The hook of the event and its argument
+fn event_hook(event: &GameEvent, ctx: &mut Context) -> JsResult<(&'static str, JsValue)> {
+    Ok(match event {
+        GameEvent::Blocked(direction) => (
+            "on_blocked",
+            JsValue::from(JsString::from(direction.to_string().as_str())),
+        ),
+        GameEvent::Arrived(cell) => ("on_arrived", cell_object(*cell, ctx)?.into()),
+        GameEvent::ItemPicked(item) => (
+            "on_item_picked",
+            JsValue::from(JsString::from(item.as_str())),
+        ),
+        GameEvent::SeenBy { npc, cell } => {
+            let obj = JsObject::with_object_proto(ctx.intrinsics());
+            obj.set(JsString::from("id"), JsValue::from(*npc), false, ctx)?;
+            obj.set(JsString::from("cell_x"), JsValue::from(cell.x), false, ctx)?;
+            obj.set(JsString::from("cell_y"), JsValue::from(cell.y), false, ctx)?;
+            ("on_seen_by", obj.into())
+        }
+    })
+}
+
+fn cell_object(cell: Vector2Di, ctx: &mut Context) -> JsResult<JsObject> {
+    let obj = JsObject::with_object_proto(ctx.intrinsics());
+    obj.set(JsString::from("x"), JsValue::from(cell.x), false, ctx)?;
+    obj.set(JsString::from("y"), JsValue::from(cell.y), false, ctx)?;
+    Ok(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use platform::types::Direction;
+
+    #[test]
+    fn test_event_arguments() {
+        let mut ctx = Context::default();
+        let (name, arg) = event_hook(&GameEvent::Blocked(Direction::EAST), &mut ctx).unwrap();
+        assert_eq!(name, "on_blocked");
+        assert_eq!(arg.as_string().unwrap().to_std_string_escaped(), "east");
+
+        let event = GameEvent::Arrived(Vector2Di::new(2, 3));
+        let (name, arg) = event_hook(&event, &mut ctx).unwrap();
+        assert_eq!(name, "on_arrived");
+        let cell = arg.as_object().unwrap();
+        let y = cell.get(JsString::from("y"), &mut ctx).unwrap();
+        assert_eq!(y.as_number(), Some(3.0));
+    }
+
+    #[test]
+    fn test_hooks_keep_the_script_state() {
+        let mut ctx = Context::default();
+        assert!(!has_hooks(&mut ctx));
+
+        let code = "let picked = []; function on_item_picked(item) { picked.push(item); }";
+        ctx.eval(Source::from_bytes(code)).unwrap();
+        assert!(has_hooks(&mut ctx));
+
+        let events = [
+            GameEvent::ItemPicked("key".to_string()),
+            GameEvent::ItemPicked("gem".to_string()),
+        ];
+        run_hooks(&events, None, 0.016, &mut ctx).unwrap();
+        let picked = ctx.eval(Source::from_bytes("picked.join()")).unwrap();
+        assert_eq!(
+            picked.as_string().unwrap().to_std_string_escaped(),
+            "key,gem"
+        );
+    }
+}
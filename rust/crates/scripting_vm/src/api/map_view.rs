@@ -0,0 +1,93 @@
+use boa_engine::{
+    js_string,
+    object::{FunctionObjectBuilder, ObjectInitializer},
+    property::Attribute,
+    Context, JsObject, JsResult, JsString, JsValue, NativeFunction,
+};
+use game_core::api::sensing::CellInfo;
+use platform::types::Direction;
+
+use crate::runtime::script_instance::ScriptInstance;
+
+/*
+ * `character.map`: read-only view of the cells next to the character.
+ *   if (character.map.north.walkable) step_up();
+ * `north`, `south`, `east`, `west` and `ahead` are getters built once per context:
+ * a cell is looked up in the world view of the script (`ScriptInstance::world`) only
+ * when the script reads it.
+ */
+
+/// Registers the `map` object as the hidden global `__map`
+pub(crate) fn register_map_view(ctx: &mut Context) {
+    let cells = [
+        ("north", Some(Direction::NORTH)),
+        ("south", Some(Direction::SOUTH)),
+        ("east", Some(Direction::EAST)),
+        ("west", Some(Direction::WEST)),
+        ("ahead", None),
+    ];
+    let getters: Vec<_> = cells
+        .into_iter()
+        .map(|(name, direction)| {
+            let getter = FunctionObjectBuilder::new(
+                ctx.realm(),
+                NativeFunction::from_copy_closure(move |_this, _args, ctx| {
+                    neighbour(direction, ctx)
+                }),
+            )
+            .name(name)
+            .build();
+            (name, getter)
+        })
+        .collect();
+
+    let mut map = ObjectInitializer::new(ctx);
+    for (name, getter) in getters {
+        map.accessor(
+            JsString::from(name),
+            Some(getter),
+            None,
+            Attribute::ENUMERABLE,
+        );
+    }
+    let map = map.build();
+
+    let _ = ctx.register_global_property(js_string!("__map"), map, Attribute::empty());
+}
+
+/// The `map` object registered for the context
+pub(crate) fn map_object(ctx: &mut Context) -> JsResult<JsValue> {
+    ctx.global_object().get(js_string!("__map"), ctx)
+}
+
+// The cell next to the character in the direction, `None` for the cell ahead
+fn neighbour(direction: Option<Direction>, ctx: &mut Context) -> JsResult<JsValue> {
+    let Some(world) = ctx
+        .get_data::<ScriptInstance>()
+        .and_then(|instance| instance.world.borrow().clone())
+    else {
+        return Ok(JsValue::undefined());
+    };
+
+    let direction = direction.unwrap_or(world.direction());
+    let info = world.cell_info(world.cell() + direction.to_vector());
+    Ok(cell_info_object(&info, ctx)?.into())
+}
+
+/// `{ x, y, walkable, height, stairs }` of the cell
+pub(crate) fn cell_info_object(info: &CellInfo, ctx: &mut Context) -> JsResult<JsObject> {
+    let obj = JsObject::with_object_proto(ctx.intrinsics());
+
+    obj.set(js_string!("x"), JsValue::from(info.cell.x), false, ctx)?;
+    obj.set(js_string!("y"), JsValue::from(info.cell.y), false, ctx)?;
+    obj.set(
+        js_string!("walkable"),
+        JsValue::from(info.walkable),
+        false,
+        ctx,
+    )?;
+    obj.set(js_string!("height"), JsValue::from(info.height), false, ctx)?;
+    obj.set(js_string!("stairs"), JsValue::from(info.stairs), false, ctx)?;
+
+    Ok(obj)
+}
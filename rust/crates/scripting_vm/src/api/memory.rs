@@ -0,0 +1,232 @@
+use boa_engine::{
+    js_string,
+    object::builtins::{JsArray, JsProxyBuilder},
+    property::Attribute,
+    Context, JsNativeError, JsObject, JsResult, JsString, JsValue,
+};
+use game_core::bt::blackboard::{Blackboard, BlackboardValue};
+use platform::types::Vector2D;
+
+use crate::runtime::script_instance::ScriptInstance;
+
+/*
+ * `character.memory`: the memory of the character (its Blackboard) for the scripts.
+ *   memory.visited = memory.visited + 1;
+ *   memory.door = { x: 3, y: 4 };
+ *   delete memory.door;
+ * The object is a Proxy built once per context: every read and write goes to the blackboard
+ * of the last snapshot given to the script (`ScriptInstance::character`). The snapshot shares
+ * the blackboard with the character, so the values are kept in the save games.
+ * Memory keeps true/false, numbers, texts and points { x, y }.
+ */
+
+/// Registers the `memory` proxy as the hidden global `__memory`
+pub(crate) fn register_memory(ctx: &mut Context) {
+    let target = JsObject::with_object_proto(ctx.intrinsics());
+    let memory: JsObject = JsProxyBuilder::new(target)
+        .get(memory_get)
+        .set(memory_set)
+        .has(memory_has)
+        .delete_property(memory_delete)
+        .own_keys(memory_keys)
+        .get_own_property_descriptor(memory_descriptor)
+        .build(ctx)
+        .into();
+
+    let _ = ctx.register_global_property(js_string!("__memory"), memory, Attribute::empty());
+}
+
+/// The `memory` object registered for the context
+pub(crate) fn memory_object(ctx: &mut Context) -> JsResult<JsValue> {
+    ctx.global_object().get(js_string!("__memory"), ctx)
+}
+
+// The blackboard of the character, the handle shares its data
+fn blackboard(ctx: &Context) -> JsResult<Blackboard> {
+    ctx.get_data::<ScriptInstance>()
+        .and_then(|instance| {
+            let character = instance.character.borrow();
+            character
+                .as_ref()
+                .map(|snapshot| snapshot.blackboard.as_ref().clone())
+        })
+        .ok_or_else(|| {
+            JsNativeError::error()
+                .with_message("memory can't be used right now")
+                .into()
+        })
+}
+
+// The name of the property, `None` for a symbol
+fn key(args: &[JsValue]) -> Option<String> {
+    Some(args.get(1)?.as_string()?.to_std_string_escaped())
+}
+
+// get(target, key, receiver)
+fn memory_get(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let Some(key) = key(args) else {
+        return Ok(JsValue::undefined());
+    };
+    match blackboard(ctx)?.get(&key) {
+        Some(value) => to_js(&value, ctx),
+        None => Ok(JsValue::undefined()),
+    }
+}
+
+// set(target, key, value, receiver), `undefined` or `null` forgets the key
+fn memory_set(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let Some(key) = key(args) else {
+        return Ok(JsValue::from(false));
+    };
+    let blackboard = blackboard(ctx)?;
+    let value = args.get(2).cloned().unwrap_or_default();
+    if value.is_null_or_undefined() {
+        blackboard.remove(&key);
+    } else {
+        blackboard.set(&key, from_js(&key, &value, ctx)?);
+    }
+    Ok(JsValue::from(true))
+}
+
+// has(target, key): `"door" in memory`
+fn memory_has(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let Some(key) = key(args) else {
+        return Ok(JsValue::from(false));
+    };
+    Ok(JsValue::from(blackboard(ctx)?.has(&key)))
+}
+
+// deleteProperty(target, key)
+fn memory_delete(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    if let Some(key) = key(args) {
+        blackboard(ctx)?.remove(&key);
+    }
+    Ok(JsValue::from(true))
+}
+
+// ownKeys(target): the keys of the memory in alphabetical order, for `Object.keys(memory)`
+fn memory_keys(_this: &JsValue, _args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let blackboard = blackboard(ctx)?;
+    let mut keys: Vec<String> = blackboard
+        .entries()
+        .into_keys()
+        .filter(|key| blackboard.has(key)) // without the state of the tree nodes
+        .collect();
+    keys.sort();
+
+    let keys = keys
+        .iter()
+        .map(|key| JsValue::from(JsString::from(key.as_str())));
+    Ok(JsArray::from_iter(keys, ctx).into())
+}
+
+// getOwnPropertyDescriptor(target, key): every key is a plain value
+fn memory_descriptor(_this: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let Some(key) = key(args) else {
+        return Ok(JsValue::undefined());
+    };
+    let Some(value) = blackboard(ctx)?.get(&key) else {
+        return Ok(JsValue::undefined());
+    };
+
+    let value = to_js(&value, ctx)?;
+    let descriptor = JsObject::with_object_proto(ctx.intrinsics());
+    descriptor.set(js_string!("value"), value, false, ctx)?;
+    for flag in ["writable", "enumerable", "configurable"] {
+        descriptor.set(JsString::from(flag), JsValue::from(true), false, ctx)?;
+    }
+    Ok(descriptor.into())
+}
+
+/// The value of the blackboard as the script sees it
+pub(crate) fn to_js(value: &BlackboardValue, ctx: &mut Context) -> JsResult<JsValue> {
+    Ok(match value {
+        BlackboardValue::Bool(value) => JsValue::from(*value),
+        BlackboardValue::Int(value) => JsValue::from(*value),
+        BlackboardValue::Float(value) => JsValue::from(*value as f64),
+        BlackboardValue::String(value) => JsValue::from(JsString::from(value.as_str())),
+        BlackboardValue::Vector(point) => {
+            let obj = JsObject::with_object_proto(ctx.intrinsics());
+            obj.set(js_string!("x"), JsValue::from(point.x as f64), false, ctx)?;
+            obj.set(js_string!("y"), JsValue::from(point.y as f64), false, ctx)?;
+            obj.into()
+        }
+        BlackboardValue::NodeState(status) => {
+            JsValue::from(JsString::from(format!("{:?}", status).as_str()))
+        }
+    })
+}
+
+/// The value written by the script, whole numbers are kept as integers
+pub(crate) fn from_js(key: &str, value: &JsValue, ctx: &mut Context) -> JsResult<BlackboardValue> {
+    if let Some(value) = value.as_boolean() {
+        return Ok(BlackboardValue::Bool(value));
+    }
+    if let Some(number) = value.as_number().filter(|n| n.is_finite()) {
+        let in_range = (i32::MIN as f64..=i32::MAX as f64).contains(&number);
+        return Ok(if number.fract() == 0.0 && in_range {
+            BlackboardValue::Int(number as i32)
+        } else {
+            BlackboardValue::Float(number as f32)
+        });
+    }
+    if let Some(text) = value.as_string() {
+        return Ok(BlackboardValue::String(text.to_std_string_escaped()));
+    }
+    if let Some(obj) = value.as_object() {
+        let x = obj.get(js_string!("x"), ctx)?.as_number();
+        let y = obj.get(js_string!("y"), ctx)?.as_number();
+        if let (Some(x), Some(y)) = (x, y) {
+            return Ok(BlackboardValue::Vector(Vector2D::new(x as f32, y as f32)));
+        }
+    }
+
+    Err(JsNativeError::typ()
+        .with_message(format!(
+            "memory.{} can keep true/false, a number, a text or a point {{ x, y }}, got {}",
+            key,
+            value.display()
+        ))
+        .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    fn value(code: &str, ctx: &mut Context) -> BlackboardValue {
+        let value = ctx.eval(Source::from_bytes(code)).unwrap();
+        from_js("test", &value, ctx).unwrap()
+    }
+
+    #[test]
+    fn test_values_of_the_script() {
+        let mut ctx = Context::default();
+        assert_eq!(value("3", &mut ctx), BlackboardValue::Int(3));
+        assert_eq!(value("0.5", &mut ctx), BlackboardValue::Float(0.5));
+        assert_eq!(
+            value("'key'", &mut ctx),
+            BlackboardValue::String("key".into())
+        );
+        assert_eq!(
+            value("({ x: 3, y: 4 })", &mut ctx),
+            BlackboardValue::Vector(Vector2D::new(3.0, 4.0))
+        );
+
+        let array = ctx.eval(Source::from_bytes("[1, 2]")).unwrap();
+        assert!(from_js("path", &array, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_values_for_the_script() {
+        let mut ctx = Context::default();
+        let point = to_js(&BlackboardValue::Vector(Vector2D::new(1.0, 2.0)), &mut ctx).unwrap();
+        assert_eq!(
+            from_js("point", &point, &mut ctx).unwrap(),
+            BlackboardValue::Vector(Vector2D::new(1.0, 2.0))
+        );
+        let flag = to_js(&BlackboardValue::Bool(true), &mut ctx).unwrap();
+        assert_eq!(flag.as_boolean(), Some(true));
+    }
+}
@@ -1,4 +1,9 @@
 pub mod bindings;
+pub mod console;
+pub mod hooks;
+pub mod map_view;
+pub mod memory;
+pub mod params;
 pub mod preprocessor;
 pub mod script_event;
 pub mod snapshot;
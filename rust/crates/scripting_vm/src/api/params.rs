@@ -0,0 +1,237 @@
+use std::str::FromStr;
+
+use boa_engine::{JsNativeError, JsResult, JsValue};
+use platform::types::Direction;
+
+/*
+ * Parameters of the API functions. The bindings declare the parameters of every function
+ * and `check_args` turns the JS arguments into checked values before the function runs.
+ * A wrong call throws a TypeError starting with the function name, e.g.
+ * `wait() needs 1 value: seconds (a number)`, which ScriptError shows as a wrong argument.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    Integer,   // a whole number, e.g. a cell coordinate
+    Number,    // any finite number
+    Direction, // "north", "south", "east" or "west"
+    Text,      // a string, numbers and booleans are turned into text
+}
+
+impl ParamType {
+    // How the type is described to the kid
+    fn describe(self) -> &'static str {
+        match self {
+            ParamType::Integer => "a whole number",
+            ParamType::Number => "a number",
+            ParamType::Direction => "\"north\", \"south\", \"east\" or \"west\"",
+            ParamType::Text => "a text",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamType,
+}
+
+impl Param {
+    pub const fn integer(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamType::Integer,
+        }
+    }
+
+    pub const fn number(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamType::Number,
+        }
+    }
+
+    pub const fn direction(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamType::Direction,
+        }
+    }
+
+    pub const fn text(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamType::Text,
+        }
+    }
+}
+
+/// A checked argument, of the type of its parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Integer(i32),
+    Number(f64),
+    Direction(Direction),
+    Text(String),
+}
+
+impl Arg {
+    pub fn integer(&self) -> i32 {
+        match self {
+            Arg::Integer(value) => *value,
+            Arg::Number(value) => *value as i32,
+            Arg::Direction(_) | Arg::Text(_) => 0,
+        }
+    }
+
+    pub fn number(&self) -> f64 {
+        match self {
+            Arg::Integer(value) => *value as f64,
+            Arg::Number(value) => *value,
+            Arg::Direction(_) | Arg::Text(_) => 0.0,
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        match self {
+            Arg::Direction(direction) => *direction,
+            _ => Direction::NORTH,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Arg::Text(text) => text,
+            _ => "",
+        }
+    }
+}
+
+/// Checks the number and the types of the arguments of a call of `name`
+pub fn check_args(name: &str, params: &[Param], args: &[JsValue]) -> JsResult<Vec<Arg>> {
+    if args.len() != params.len() {
+        return Err(type_error(format!(
+            "{}() {}, got {}",
+            name,
+            expected(params),
+            count(args.len())
+        )));
+    }
+
+    params
+        .iter()
+        .zip(args)
+        .map(|(param, value)| {
+            check_arg(param, value).ok_or_else(|| {
+                type_error(format!(
+                    "{}(): {} must be {}, got {}",
+                    name,
+                    param.name,
+                    param.kind.describe(),
+                    value.display()
+                ))
+            })
+        })
+        .collect()
+}
+
+fn check_arg(param: &Param, value: &JsValue) -> Option<Arg> {
+    match param.kind {
+        ParamType::Integer => {
+            let number = value.as_number().filter(|n| n.is_finite())?;
+            let in_range = (i32::MIN as f64..=i32::MAX as f64).contains(&number);
+            (number.fract() == 0.0 && in_range).then_some(Arg::Integer(number as i32))
+        }
+        ParamType::Number => value.as_number().filter(|n| n.is_finite()).map(Arg::Number),
+        ParamType::Direction => {
+            let text = value.as_string()?.to_std_string_escaped();
+            Direction::from_str(&text).ok().map(Arg::Direction)
+        }
+        ParamType::Text => {
+            if let Some(text) = value.as_string() {
+                Some(Arg::Text(text.to_std_string_escaped()))
+            } else if value.is_number() || value.is_boolean() {
+                Some(Arg::Text(value.display().to_string()))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// "takes no values", "needs 1 value: seconds (a number)"
+fn expected(params: &[Param]) -> String {
+    if params.is_empty() {
+        return "takes no values".to_string();
+    }
+    let list: Vec<String> = params
+        .iter()
+        .map(|param| format!("{} ({})", param.name, param.kind.describe()))
+        .collect();
+    format!("needs {}: {}", count(params.len()), list.join(", "))
+}
+
+fn count(n: usize) -> String {
+    match n {
+        1 => "1 value".to_string(),
+        n => format!("{} values", n),
+    }
+}
+
+fn type_error(message: String) -> boa_engine::JsError {
+    JsNativeError::typ().with_message(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::JsString;
+
+    const POSITION: &[Param] = &[Param::integer("x"), Param::integer("y")];
+
+    fn message(result: JsResult<Vec<Arg>>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let args = [JsValue::from(3), JsValue::from(4.0)];
+        assert_eq!(
+            check_args("set_position", POSITION, &args).unwrap(),
+            vec![Arg::Integer(3), Arg::Integer(4)]
+        );
+
+        let args = [JsValue::from(JsString::from("east"))];
+        let checked = check_args("can_move", &[Param::direction("direction")], &args).unwrap();
+        assert_eq!(checked[0].direction(), Direction::EAST);
+
+        let args = [JsValue::from(42)];
+        let checked = check_args("say", &[Param::text("text")], &args).unwrap();
+        assert_eq!(checked[0].text(), "42");
+    }
+
+    #[test]
+    fn test_wrong_number_of_arguments() {
+        assert_eq!(
+            message(check_args("wait", &[Param::number("seconds")], &[])),
+            "TypeError: wait() needs 1 value: seconds (a number), got 0 values"
+        );
+        assert_eq!(
+            message(check_args("step_up", &[], &[JsValue::from(1)])),
+            "TypeError: step_up() takes no values, got 1 value"
+        );
+    }
+
+    #[test]
+    fn test_wrong_types() {
+        let args = [JsValue::from(JsString::from("a")), JsValue::from(3)];
+        assert!(message(check_args("set_position", POSITION, &args))
+            .starts_with("TypeError: set_position(): x must be a whole number"));
+
+        let args = [JsValue::from(1.5), JsValue::from(3)];
+        assert!(check_args("set_position", POSITION, &args).is_err());
+
+        let args = [JsValue::from(f64::NAN)];
+        assert!(check_args("wait", &[Param::number("seconds")], &args).is_err());
+    }
+}
@@ -1,73 +1,808 @@
-/// List of known command function names that should be tracked
-const COMMAND_FUNCTIONS: &[&str] = &[
-    "step_up",
-    "step_down",
-    "step_right",
-    "step_left",
-    "set_position",
-    "wait",
-    "for",
-];
-
-/// Instruments JavaScript source code by inserting `__line(N);` calls
-/// before each command function call, where N is the 1-based line number.
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+
+use boa_ast::{
+    expression::{literal::LiteralKind, Call, Expression, Identifier},
+    function::FunctionBody,
+    pattern::Pattern,
+    scope::Scope,
+    statement::{DoWhileLoop, ForInLoop, ForLoop, ForOfLoop, WhileLoop},
+    visitor::{VisitWith, Visitor},
+    Declaration, Position, Script, Spanned, Statement, StatementListItem,
+};
+use boa_interner::{Interner, Sym, ToInternedString};
+use boa_parser::{Parser, Source};
+
+use crate::api::bindings::API_FUNCTIONS;
+use crate::api::console::CONSOLE_FUNCTIONS;
+
+/// A call of a registered API function found in the script source.
+/// `line` and `column` are 1-based and point to the first character of the callee name.
+#[derive(Debug, Clone, PartialEq)]
+struct ApiCall {
+    name: String,
+    line: usize,
+    column: usize,
+}
+
+/// AST visitor collecting every call expression whose callee is a plain identifier
+/// matching one of the registered API functions or is one of the console functions.
+/// Other method calls (`obj.wait()`), identifiers in comments or string literals and names
+/// which only start with an API name (`waiting`) are never matched.
+struct ApiCallCollector<'a> {
+    interner: &'a Interner,
+    calls: Vec<ApiCall>,
+}
+
+impl<'ast> Visitor<'ast> for ApiCallCollector<'_> {
+    type BreakTy = Infallible;
+
+    fn visit_call(&mut self, node: &'ast Call) -> ControlFlow<Self::BreakTy> {
+        if let Some(name) = self.callee(node.function()) {
+            // the span of the call itself starts at its arguments
+            let start = node.function().span().start();
+            self.calls.push(ApiCall {
+                name,
+                line: start.line_number() as usize,
+                column: start.column_number() as usize,
+            });
+        }
+        // arguments may contain nested calls
+        node.visit_with(self)
+    }
+}
+
+impl ApiCallCollector<'_> {
+    // The name of the called function if the call is instrumented
+    fn callee(&self, function: &Expression) -> Option<String> {
+        let name = match function {
+            Expression::Identifier(ident) => self.interner.resolve_expect(ident.sym()).to_string(),
+            // `console.log()` gets its line like an API call
+            Expression::PropertyAccess(access) => access.to_interned_string(self.interner),
+            _ => return None,
+        };
+        (API_FUNCTIONS.contains(&name.as_str()) || CONSOLE_FUNCTIONS.contains(&name.as_str()))
+            .then_some(name)
+    }
+}
+
+// Texts inserted by `instrument_code_with_budget`, `original_column` leaves them out again
+const CHECK: &str = " __budget();"; // after the `{` of a function or loop body
+const TEST: &str = "__budget(), "; // before the condition of a loop without a block
+const CONDITION: &str = "__budget()"; // the condition of a `for (;;)` without a block
+const OPEN: &str = "(__budget(), "; // around the expression body of an arrow function
+const CLOSE: &str = ")";
+const OPEN_BLOCK: &str = "{__budget(); "; // around the statement of a `for-in/of` without a block
+const CLOSE_BLOCK: &str = " }";
+
+/// AST visitor finding where the time checks go: at the start of every function body and
+/// of every loop body, so callbacks called by builtins (`forEach`, `sort`, ...) are checked too.
+/// A loop body which is a single statement gets no `if` in front of it, which could take
+/// the `else` of the code around: the check goes into the condition of the loop,
+/// or braces are put around the statement of a `for-in/of`.
+///
+/// Function bodies have a span, statements don't. The visitor keeps the end of the last
+/// node with a span and looks for the loop keyword, the end of the loop head and the body
+/// in the tokens after it: only keywords, labels, punctuators and comments are left there.
+struct BudgetCollector<'a> {
+    code: &'a str,
+    line_offsets: &'a [usize],
+    pos: usize,                         // byte offset after the last node with a span
+    checks: Vec<(usize, &'static str)>, // byte offset and inserted text
+}
+
+impl<'ast> Visitor<'ast> for BudgetCollector<'_> {
+    type BreakTy = Infallible;
+
+    fn visit_expression(&mut self, node: &'ast Expression) -> ControlFlow<Self::BreakTy> {
+        node.visit_with(self)?;
+        self.move_past(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_identifier(&mut self, node: &'ast Identifier) -> ControlFlow<Self::BreakTy> {
+        self.move_past(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_pattern(&mut self, node: &'ast Pattern) -> ControlFlow<Self::BreakTy> {
+        node.visit_with(self)?;
+        self.move_past(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_function_body(&mut self, node: &'ast FunctionBody) -> ControlFlow<Self::BreakTy> {
+        let span = node.span();
+        let (Some(start), Some(end)) = (self.offset(span.start()), self.offset(span.end())) else {
+            return node.visit_with(self);
+        };
+        let outside = self.pos;
+        self.pos = start;
+
+        if self.code[start..].starts_with('{') {
+            let at = self.after_directives(node).unwrap_or(start + 1);
+            self.checks.push((at, CHECK));
+        } else {
+            // `x => x * 2` becomes `x => (__budget(), x * 2)`
+            self.checks.push((start, OPEN));
+            self.checks.push((end, CLOSE));
+        }
+        node.visit_with(self)?;
+
+        self.pos = outside.max(end);
+        ControlFlow::Continue(())
+    }
+
+    fn visit_while_loop(&mut self, node: &'ast WhileLoop) -> ControlFlow<Self::BreakTy> {
+        let head = self.skip_to(self.pos, "while");
+        self.visit_expression(node.condition())?;
+        let body = head.and_then(|_| self.skip_to(self.pos, ")"));
+        if !self.check_block(body, node.body()) {
+            self.check_condition(node.condition());
+        }
+        self.visit_statement(node.body())
+    }
+
+    fn visit_do_while_loop(&mut self, node: &'ast DoWhileLoop) -> ControlFlow<Self::BreakTy> {
+        if !self.check_block(self.skip_to(self.pos, "do"), node.body()) {
+            self.check_condition(node.cond());
+        }
+        self.visit_statement(node.body())?;
+        self.visit_expression(node.cond())
+    }
+
+    fn visit_for_loop(&mut self, node: &'ast ForLoop) -> ControlFlow<Self::BreakTy> {
+        let head = self
+            .skip_to(self.pos, "for")
+            .and_then(|at| self.skip_to(at, "("));
+        if let Some(head) = head {
+            // `for (;;)` has nothing with a span in its head
+            self.pos = self.pos.max(head);
+        }
+        if let Some(init) = node.init() {
+            self.visit_for_loop_initializer(init)?;
+        }
+        // after the first `;`, where the condition goes if there is none
+        let condition = head.and_then(|_| self.skip_to(self.pos, ";"));
+        if let Some(condition) = node.condition() {
+            self.visit_expression(condition)?;
+        }
+        if let Some(final_expr) = node.final_expr() {
+            self.visit_expression(final_expr)?;
+        }
+        let body = head.and_then(|_| self.skip_to(self.pos, ")"));
+        if !self.check_block(body, node.body()) {
+            match (node.condition(), condition) {
+                (Some(expression), _) => self.check_condition(expression),
+                (None, Some(at)) => self.checks.push((at, CONDITION)),
+                (None, None) => {}
+            }
+        }
+        self.visit_statement(node.body())
+    }
+
+    fn visit_for_in_loop(&mut self, node: &'ast ForInLoop) -> ControlFlow<Self::BreakTy> {
+        let head = self.skip_to(self.pos, "for");
+        self.visit_iterable_loop_initializer(node.initializer())?;
+        self.visit_expression(node.target())?;
+        self.check_iteration(head.and_then(|_| self.skip_to(self.pos, ")")), node.body());
+        self.visit_statement(node.body())
+    }
+
+    fn visit_for_of_loop(&mut self, node: &'ast ForOfLoop) -> ControlFlow<Self::BreakTy> {
+        let head = self.skip_to(self.pos, "for");
+        self.visit_iterable_loop_initializer(node.initializer())?;
+        self.visit_expression(node.iterable())?;
+        self.check_iteration(head.and_then(|_| self.skip_to(self.pos, ")")), node.body());
+        self.visit_statement(node.body())
+    }
+}
+
+/// AST visitor finding the end of the last node with a span
+#[derive(Default)]
+struct SpanEnd {
+    end: Option<Position>,
+}
+
+impl SpanEnd {
+    fn of(node: &impl VisitWith) -> Option<Position> {
+        let mut visitor = SpanEnd::default();
+        let _ = node.visit_with(&mut visitor);
+        visitor.end
+    }
+
+    fn record(&mut self, end: Position) {
+        self.end = self.end.max(Some(end));
+    }
+}
+
+impl<'ast> Visitor<'ast> for SpanEnd {
+    type BreakTy = Infallible;
+
+    // the span of a node covers its children
+    fn visit_expression(&mut self, node: &'ast Expression) -> ControlFlow<Self::BreakTy> {
+        self.record(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_identifier(&mut self, node: &'ast Identifier) -> ControlFlow<Self::BreakTy> {
+        self.record(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_pattern(&mut self, node: &'ast Pattern) -> ControlFlow<Self::BreakTy> {
+        self.record(node.span().end());
+        ControlFlow::Continue(())
+    }
+
+    fn visit_function_body(&mut self, node: &'ast FunctionBody) -> ControlFlow<Self::BreakTy> {
+        self.record(node.span().end());
+        ControlFlow::Continue(())
+    }
+}
+
+impl BudgetCollector<'_> {
+    fn offset(&self, position: Position) -> Option<usize> {
+        byte_offset(
+            self.code,
+            self.line_offsets,
+            position.line_number() as usize,
+            position.column_number() as usize,
+        )
+    }
+
+    fn move_past(&mut self, end: Position) {
+        if let Some(end) = self.offset(end) {
+            self.pos = self.pos.max(end);
+        }
+    }
+
+    // Inserts the check at the start of the body of a loop if it is a block,
+    // `at` is the end of its head. Returns false if the body is a single statement.
+    fn check_block(&mut self, at: Option<usize>, body: &Statement) -> bool {
+        if !matches!(body, Statement::Block(_)) {
+            return false;
+        }
+        let Some(at) = at.map(|at| skip_blanks(self.code, at)) else {
+            return true;
+        };
+        if self.code[at..].starts_with('{') {
+            self.checks.push((at + 1, CHECK));
+            self.pos = at;
+        }
+        true
+    }
+
+    // `while (x > 0)` becomes `while (__budget(), x > 0)`
+    fn check_condition(&mut self, condition: &Expression) {
+        if let Some(at) = self.offset(condition.span().start()) {
+            self.checks.push((at, TEST));
+        }
+    }
+
+    // A `for-in/of` has no condition: `for (x of xs) f(x);` becomes
+    // `for (x of xs) {__budget(); f(x); }`. A statement whose end is not found is not checked.
+    fn check_iteration(&mut self, at: Option<usize>, body: &Statement) {
+        if self.check_block(at, body) {
+            return;
+        }
+        let Some(at) = at.map(|at| skip_blanks(self.code, at)) else {
+            return;
+        };
+        if let Some(end) = self.statement_end(at, body) {
+            self.checks.push((at, OPEN_BLOCK));
+            self.checks.push((end, CLOSE_BLOCK));
+            self.pos = at;
+        }
+    }
+
+    /// The byte offset after the last token of `statement`, which starts at `from` or later
+    /// with no string before it. `None` for the statements it doesn't follow, like `switch`.
+    fn statement_end(&self, from: usize, statement: &Statement) -> Option<usize> {
+        let end = match statement {
+            Statement::Block(block) => {
+                let mut at = self.skip_to(from, "{")?;
+                for item in block.statement_list().statements() {
+                    at = match item {
+                        StatementListItem::Statement(statement) => {
+                            self.statement_end(at, statement)?
+                        }
+                        StatementListItem::Declaration(declaration) => {
+                            self.declaration_end(declaration)?
+                        }
+                    };
+                }
+                return self.skip_to(at, "}");
+            }
+            Statement::Expression(expression) => self.offset(expression.span().end())?,
+            Statement::Var(var) => self.offset(SpanEnd::of(var)?)?,
+            Statement::Empty => return self.skip_to(from, ";"),
+            Statement::If(node) => {
+                let at = self.skip_to(self.offset(node.cond().span().end())?, ")")?;
+                let at = self.statement_end(at, node.body())?;
+                return match node.else_node() {
+                    Some(other) => self.statement_end(self.skip_to(at, "else")?, other),
+                    None => Some(at),
+                };
+            }
+            Statement::WhileLoop(node) => {
+                let at = self.skip_to(self.offset(node.condition().span().end())?, ")")?;
+                return self.statement_end(at, node.body());
+            }
+            Statement::DoWhileLoop(node) => {
+                self.skip_to(self.offset(node.cond().span().end())?, ")")?
+            }
+            Statement::ForLoop(node) => {
+                let head = self.skip_to(self.skip_to(from, "for")?, "(")?;
+                let head = [
+                    node.init().and_then(SpanEnd::of),
+                    node.condition().map(|condition| condition.span().end()),
+                    node.final_expr().map(|final_expr| final_expr.span().end()),
+                ]
+                .into_iter()
+                .flatten()
+                .filter_map(|end| self.offset(end))
+                .fold(head, usize::max);
+                return self.statement_end(self.skip_to(head, ")")?, node.body());
+            }
+            Statement::ForInLoop(node) => {
+                let at = self.skip_to(self.offset(node.target().span().end())?, ")")?;
+                return self.statement_end(at, node.body());
+            }
+            Statement::ForOfLoop(node) => {
+                let at = self.skip_to(self.offset(node.iterable().span().end())?, ")")?;
+                return self.statement_end(at, node.body());
+            }
+            Statement::Return(node) => match node.target() {
+                Some(target) => self.offset(target.span().end())?,
+                None => self.skip_to(from, "return")?,
+            },
+            Statement::Throw(node) => self.offset(node.target().span().end())?,
+            Statement::Break(node) => self.label_end(self.skip_to(from, "break")?, node.label())?,
+            Statement::Continue(node) => {
+                self.label_end(self.skip_to(from, "continue")?, node.label())?
+            }
+            _ => return None,
+        };
+        Some(self.semicolon(end))
+    }
+
+    // After the `;` ending a statement at `end`, if it was not left to the automatic insertion
+    fn semicolon(&self, end: usize) -> usize {
+        let at = skip_blanks(self.code, end);
+        if self.code[at..].starts_with(';') {
+            at + 1
+        } else {
+            end
+        }
+    }
+
+    // The end of a declaration in a block, ended like a statement
+    fn declaration_end(&self, declaration: &Declaration) -> Option<usize> {
+        let end = self.offset(SpanEnd::of(declaration)?)?;
+        match declaration {
+            // the end of the body
+            Declaration::FunctionDeclaration(_)
+            | Declaration::GeneratorDeclaration(_)
+            | Declaration::AsyncFunctionDeclaration(_)
+            | Declaration::AsyncGeneratorDeclaration(_) => Some(end),
+            Declaration::Lexical(_) => Some(self.semicolon(end)),
+            Declaration::ClassDeclaration(_) => None,
+        }
+    }
+
+    // The end of the label of a `break` or `continue` after `at`, if there is one
+    fn label_end(&self, at: usize, label: Option<Sym>) -> Option<usize> {
+        match label {
+            Some(_) => self.skip_token(at).map(|(_, end)| end),
+            None => Some(at),
+        }
+    }
+
+    // "use strict" has to stay the first statement of the body: the byte offset after
+    // the directives if the body starts with some, each ended by a semicolon
+    fn after_directives(&self, body: &FunctionBody) -> Option<usize> {
+        let mut after = None;
+        for statement in body.statements() {
+            let StatementListItem::Statement(statement) = statement else {
+                break;
+            };
+            let Statement::Expression(Expression::Literal(literal)) = statement.as_ref() else {
+                break;
+            };
+            if !matches!(literal.kind(), LiteralKind::String(_)) {
+                break;
+            }
+            let end = skip_blanks(self.code, self.offset(literal.span().end())?);
+            if !self.code[end..].starts_with(';') {
+                break;
+            }
+            after = Some(end + 1);
+        }
+        after
+    }
+
+    /// The byte offset after the next token `token` from `from`, where `token` is a word
+    /// or a punctuator of one character. `None` if a string comes first: between two nodes
+    /// with a span there are none, the position is lost.
+    fn skip_to(&self, from: usize, token: &str) -> Option<usize> {
+        let mut at = from;
+        loop {
+            let (start, end) = self.skip_token(at)?;
+            if &self.code[start..end] == token {
+                return Some(end);
+            }
+            at = end;
+        }
+    }
+
+    // The start and the end of the next token from `from`, see `skip_to`
+    fn skip_token(&self, from: usize) -> Option<(usize, usize)> {
+        let code = self.code.as_bytes();
+        let start = skip_blanks(self.code, from);
+        let mut at = start;
+        match *code.get(at)? {
+            b'"' | b'\'' | b'`' => return None,
+            c if is_word_byte(c) => {
+                while code.get(at).is_some_and(|c| is_word_byte(*c)) {
+                    at += 1;
+                }
+            }
+            _ => at += 1,
+        }
+        Some((start, at))
+    }
+}
+
+// Letters, digits, `_`, `$` and the bytes of the non-ASCII characters
+fn is_word_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || !c.is_ascii()
+}
+
+/// The byte offset of the next character in `code` after `from` which is
+/// neither white space nor part of a comment.
+fn skip_blanks(code: &str, from: usize) -> usize {
+    let mut at = from;
+    loop {
+        let rest = &code[at..];
+        let trimmed = rest.trim_start();
+        at += rest.len() - trimmed.len();
+        if trimmed.starts_with("//") {
+            at += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if trimmed.starts_with("/*") {
+            at += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+        } else {
+            return at;
+        }
+    }
+}
+
+/// Instruments JavaScript source code by wrapping the callee of every API function call
+/// into `__line(N, C, callee)`, where N and C are the 1-based line and column of the call.
+/// `__line` records the position and returns the callee, so the call itself is unchanged.
+///
+/// The script is parsed with boa's parser, so only real call expressions are
+/// instrumented and several calls on one line get their own column.
+/// If the script cannot be parsed it is returned unchanged: the syntax error is
+/// reported later by the VM with its original position.
 ///
 /// The user writes clean JS — this preprocessing is invisible to them.
 ///
 /// Example:
 /// ```text
 /// for (let i = 0; i < 3; i++) {   // line 1 — no command, unchanged
-///     step_up();                    // line 2 → "__line(2); step_up();"
+///     step_up(); step_left();       // line 2 → "__line(2, 5, step_up)(); __line(2, 16, step_left)();"
 /// }
-/// step_right();                    // line 4 → "__line(4); step_right();"
 /// ```
 pub fn instrument_code(code: &str) -> String {
-    let mut result = String::with_capacity(code.len() + code.len() / 4);
+    instrument(code, false, false)
+}
+
+/// Same as `instrument_code`, and also starts every function body and every loop body
+/// with `__budget()`, which checks the time limit of the script. A script can't run long
+/// without entering one of them, so the time limit stops a runaway script even if it never
+/// calls the game API, also in a callback called by a builtin like `forEach` or `sort`.
+///
+/// Example:
+/// ```text
+/// function walk(n) { step_up(); }   // → "function walk(n) { __budget(); __line(1, 20, step_up)(); }"
+/// while (busy) tick();              // → "while (__budget(), busy) tick();"
+/// for (x of xs) f(x);               // → "for (x of xs) {__budget(); f(x); }"
+/// list.sort((a, b) => a - b);       // → "list.sort((a, b) => (__budget(), a - b));"
+/// ```
+pub fn instrument_code_with_budget(code: &str) -> String {
+    instrument(code, true, false)
+}
 
-    for (index, line) in code.lines().enumerate() {
-        let line_number = index + 1; // 1-based
+// Evaluates a name in the scope of an API call, for the debugger
+const SCOPE: &str = ", (__n) => eval(__n)";
+// Evaluates a name in the global scope of the script, the declaration is hoisted
+const GLOBAL_SCOPE: &str = "\nfunction __global_scope(__n) { return eval(__n); }\n";
 
-        if contains_command_call(line) {
-            // Find the position of the command call and insert __line(N); before it
-            if let Some(insert_pos) = find_command_insert_position(line) {
-                result.push_str(&line[..insert_pos]);
-                result.push_str(&format!("__line({});", line_number));
-                result.push_str(&line[insert_pos..]);
-            } else {
-                result.push_str(line);
-            }
-        } else {
-            result.push_str(line);
+/// Same as `instrument_code_with_budget`, and also passes the scope of every API call
+/// to `__line`, so the debugger can read the variables of a script paused in that call.
+/// `__global_scope` is appended to read the global variables.
+///
+/// Example:
+/// ```text
+/// step_up();   // → "__line(1, 1, step_up, (__n) => eval(__n))();"
+/// ```
+pub fn instrument_code_for_debugger(code: &str) -> String {
+    instrument(code, true, true)
+}
+
+fn instrument(code: &str, budget: bool, scopes: bool) -> String {
+    let Some((script, interner)) = parse(code) else {
+        return code.to_string();
+    };
+    let line_offsets = line_offsets(code);
+
+    // byte offset, length of the replaced text and the text put there
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for call in find_calls(&script, &interner) {
+        let Some(start) = byte_offset(code, &line_offsets, call.line, call.column) else {
+            continue;
+        };
+        if code.get(start..start + call.name.len()) != Some(call.name.as_str()) {
+            continue;
         }
+        let scope = if scopes { SCOPE } else { "" };
+        let wrapped = format!(
+            "__line({}, {}, {}{})",
+            call.line, call.column, call.name, scope
+        );
+        edits.push((start, call.name.len(), wrapped));
+    }
+    if budget {
+        let checks = find_budget_checks(&script, code, &line_offsets);
+        edits.extend(
+            checks
+                .into_iter()
+                .map(|(at, text)| (at, 0, text.to_string())),
+        );
+    }
+    // a check goes before a call starting at the same place
+    edits.sort_by_key(|(at, len, _)| (*at, *len));
+
+    let mut result = String::with_capacity(code.len() + edits.len() * 24);
+    let mut copied = 0;
 
-        result.push('\n');
+    for (at, len, text) in edits {
+        // do not trust spans pointing back
+        if at < copied {
+            continue;
+        }
+        result.push_str(&code[copied..at]);
+        result.push_str(&text);
+        copied = at + len;
     }
 
+    result.push_str(&code[copied..]);
+    if scopes {
+        result.push_str(GLOBAL_SCOPE);
+    }
     result
 }
 
-/// Checks if a line contains any known command function call
-fn contains_command_call(line: &str) -> bool {
-    COMMAND_FUNCTIONS.iter().any(|func| line.contains(func))
+/// AST visitor collecting the names of all identifiers: variables, parameters and functions
+struct IdentifierCollector<'a> {
+    interner: &'a Interner,
+    names: Vec<String>,
 }
 
-/// Finds the byte position where `__line(N);` should be inserted.
-/// Returns the position of the first command function call found,
-/// preserving leading whitespace.
-fn find_command_insert_position(line: &str) -> Option<usize> {
-    let mut earliest: Option<usize> = None;
+impl<'ast> Visitor<'ast> for IdentifierCollector<'_> {
+    type BreakTy = Infallible;
 
-    for func in COMMAND_FUNCTIONS {
-        if let Some(pos) = line.find(func) {
-            match earliest {
-                Some(current) if pos < current => earliest = Some(pos),
-                None => earliest = Some(pos),
-                _ => {}
+    fn visit_identifier(&mut self, node: &'ast Identifier) -> ControlFlow<Self::BreakTy> {
+        let name = self.interner.resolve_expect(node.sym()).to_string();
+        if !self.names.contains(&name) {
+            self.names.push(name);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Names the debugger looks up when the script is paused, in the order they first appear.
+/// The API functions and the helpers of the preprocessor are left out.
+pub fn identifier_names(code: &str) -> Vec<String> {
+    let Some((script, interner)) = parse(code) else {
+        return Vec::new();
+    };
+
+    let mut collector = IdentifierCollector {
+        interner: &interner,
+        names: Vec::new(),
+    };
+    let _ = script.visit_with(&mut collector);
+
+    collector
+        .names
+        .into_iter()
+        .filter(|name| {
+            !API_FUNCTIONS.contains(&name.as_str()) && name != "console" && !name.starts_with("__")
+        })
+        .collect()
+}
+
+/// Parses the script, `None` if it has syntax errors
+fn parse(code: &str) -> Option<(Script, Interner)> {
+    let mut interner = Interner::default();
+    let mut parser = Parser::new(Source::from_bytes(code));
+    let script = parser
+        .parse_script(&Scope::new_global(), &mut interner)
+        .ok()?;
+    Some((script, interner))
+}
+
+/// All API calls of the script sorted by their position
+fn find_calls(script: &Script, interner: &Interner) -> Vec<ApiCall> {
+    let mut collector = ApiCallCollector {
+        interner,
+        calls: Vec::new(),
+    };
+    let _ = script.visit_with(&mut collector);
+
+    let mut calls = collector.calls;
+    calls.sort_by_key(|call| (call.line, call.column));
+    calls
+}
+
+/// Where the time checks go and what is inserted there, see `BudgetCollector`
+fn find_budget_checks(
+    script: &Script,
+    code: &str,
+    line_offsets: &[usize],
+) -> Vec<(usize, &'static str)> {
+    let mut collector = BudgetCollector {
+        code,
+        line_offsets,
+        pos: 0,
+        checks: Vec::new(),
+    };
+    let _ = script.visit_with(&mut collector);
+    collector.checks
+}
+
+/// Byte offsets of the beginning of every line.
+fn line_offsets(code: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Converts a 1-based line/column position (columns are counted in characters)
+/// into a byte offset in `code`. The column after the last character is the end of the code.
+fn byte_offset(code: &str, line_offsets: &[usize], line: usize, column: usize) -> Option<usize> {
+    let line_start = *line_offsets.get(line.checked_sub(1)?)?;
+    let line_text = &code[line_start..];
+    let char_offset = column.checked_sub(1)?;
+
+    line_text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(line_text.len()))
+        .nth(char_offset)
+        .map(|i| line_start + i)
+}
+
+/// Converts a 1-based column in a line of the instrumented code back to the column
+/// in the script, by leaving out the `__line(N, C, ` wrappers and the time checks before it.
+/// A column inside a wrapper points to the callee, one inside a check to the code after it.
+pub fn original_column(instrumented_line: &str, column: usize) -> usize {
+    let chars: Vec<char> = instrumented_line.chars().collect();
+    let target = column.saturating_sub(1).min(chars.len());
+    let starts_with = |at: usize, text: &str| {
+        text.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(at + k) == Some(&c))
+    };
+
+    // OPEN follows an arrow, TEST may follow the `(` of a loop
+    let after_arrow = |at: usize| {
+        let before: String = chars[..at].iter().collect();
+        before.trim_end().ends_with("=>")
+    };
+
+    let mut removed = 0;
+    let mut depth = 0usize; // of the parentheses
+    let mut closes = Vec::new(); // depths at which a `)` closes an OPEN
+    let mut braces = 0usize; // depth of the braces
+    let mut blocks = Vec::new(); // depths at which a `}` closes an OPEN_BLOCK
+    let mut i = 0;
+    while i < target {
+        if let Some(check) = [OPEN_BLOCK, CHECK, OPEN, TEST, CONDITION]
+            .into_iter()
+            .find(|text| starts_with(i, text) && (*text != OPEN || after_arrow(i)))
+        {
+            let len = check.chars().count();
+            if target < i + len {
+                return i - removed + 1;
             }
+            if check == OPEN {
+                closes.push(depth);
+            }
+            if check == OPEN_BLOCK {
+                blocks.push(braces);
+            }
+            removed += len;
+            i += len;
+            continue;
+        }
+        if let Some((prefix, name, suffix)) = wrapper_at(&chars[i..]) {
+            if target < i + prefix {
+                return i - removed + 1;
+            }
+            let end = i + prefix + name; // the end of the callee name
+            if target < end + suffix {
+                return target.min(end) - removed - prefix + 1;
+            }
+            removed += prefix + suffix;
+            i = end + suffix;
+            continue;
+        }
+        match chars[i] {
+            '(' => depth += 1,
+            ')' if closes.last() == Some(&depth) => {
+                closes.pop();
+                removed += CLOSE.len();
+            }
+            ')' => depth = depth.saturating_sub(1),
+            '{' => braces += 1,
+            '}' if blocks.last() == Some(&braces) => {
+                blocks.pop();
+                removed += CLOSE_BLOCK.len();
+            }
+            '}' => braces = braces.saturating_sub(1),
+            _ => {}
         }
+        i += 1;
     }
+    target - removed + 1
+}
+
+/// Lengths of the `__line(N, C, ` prefix, of the callee name and of the wrapper suffix
+/// if a wrapper starts at `chars`
+fn wrapper_at(chars: &[char]) -> Option<(usize, usize, usize)> {
+    let starts_with = |at: usize, text: &str| {
+        text.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(at + k) == Some(&c))
+    };
+    let digits = |at: usize| {
+        chars[at.min(chars.len())..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
 
-    earliest
+    if !starts_with(0, "__line(") {
+        return None;
+    }
+    // __line(N, C, callee)
+    let mut prefix = "__line(".len();
+    for _ in 0..2 {
+        let n = digits(prefix);
+        if n == 0 || !starts_with(prefix + n, ", ") {
+            return None;
+        }
+        prefix += n + 2;
+    }
+
+    let name = chars[prefix.min(chars.len())..]
+        .iter()
+        .take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '$' || **c == '.')
+        .count();
+    if name == 0 {
+        return None;
+    }
+    let end = prefix + name;
+    if starts_with(end, ")") {
+        Some((prefix, name, 1))
+    } else if starts_with(end, SCOPE) && starts_with(end + SCOPE.len(), ")") {
+        Some((prefix, name, SCOPE.len() + 1))
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -78,7 +813,7 @@ mod tests {
     fn test_simple_command() {
         let code = "step_up();";
         let result = instrument_code(code);
-        assert_eq!(result.trim(), "__line(1);step_up();");
+        assert_eq!(result.trim(), "__line(1, 1, step_up)();");
     }
 
     #[test]
@@ -86,7 +821,7 @@ mod tests {
         let code = "    step_right();";
         let result = instrument_code(code);
         let lines: Vec<&str> = result.lines().collect();
-        assert_eq!(lines[0], "    __line(1);step_right();");
+        assert_eq!(lines[0], "    __line(1, 5, step_right)();");
     }
 
     #[test]
@@ -95,7 +830,7 @@ mod tests {
         let result = instrument_code(code);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines[0], "for (let i = 0; i < 3; i++) {");
-        assert_eq!(lines[1], "    __line(2);step_up();");
+        assert_eq!(lines[1], "    __line(2, 5, step_up)();");
         assert_eq!(lines[2], "}");
     }
 
@@ -111,9 +846,9 @@ mod tests {
         let code = "step_up();\nlet x = 1;\nstep_right();";
         let result = instrument_code(code);
         let lines: Vec<&str> = result.lines().collect();
-        assert_eq!(lines[0], "__line(1);step_up();");
+        assert_eq!(lines[0], "__line(1, 1, step_up)();");
         assert_eq!(lines[1], "let x = 1;");
-        assert_eq!(lines[2], "__line(3);step_right();");
+        assert_eq!(lines[2], "__line(3, 1, step_right)();");
     }
 
     #[test]
@@ -122,7 +857,169 @@ mod tests {
         let result = instrument_code(code);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines[0], "function update() {");
-        assert_eq!(lines[1], "    __line(2);step_up();");
+        assert_eq!(lines[1], "    __line(2, 5, step_up)();");
         assert_eq!(lines[2], "}");
     }
+
+    #[test]
+    fn test_two_calls_on_one_line() {
+        let code = "step_up(); step_left();";
+        let result = instrument_code(code);
+        assert_eq!(
+            result,
+            "__line(1, 1, step_up)(); __line(1, 12, step_left)();"
+        );
+    }
+
+    #[test]
+    fn test_comment_not_instrumented() {
+        let code = "// step_up later\n/* wait(1) */\nlet x = 1;";
+        assert_eq!(instrument_code(code), code);
+    }
+
+    #[test]
+    fn test_string_literal_not_instrumented() {
+        let code = "let s = \"wait\";\nlet t = 'step_up()';";
+        assert_eq!(instrument_code(code), code);
+    }
+
+    #[test]
+    fn test_similar_identifiers_not_instrumented() {
+        let code = "let waiting = 1;\nfunction format() {}\nformat();";
+        assert_eq!(instrument_code(code), code);
+    }
+
+    #[test]
+    fn test_method_call_not_instrumented() {
+        let code = "let o = { wait: function() {} };\no.wait();";
+        assert_eq!(instrument_code(code), code);
+    }
+
+    #[test]
+    fn test_console_call_gets_its_line() {
+        let code = "let x = 1;\nconsole.log(\"x is\", x); say(x);";
+        let result = instrument_code(code);
+        assert_eq!(
+            result,
+            "let x = 1;\n__line(2, 1, console.log)(\"x is\", x); __line(2, 25, say)(x);"
+        );
+
+        let line = result.lines().nth(1).unwrap();
+        assert_eq!(original_column(line, 14), 1); // console.log
+    }
+
+    #[test]
+    fn test_call_inside_expression() {
+        let code = "if (true) { wait(1); }";
+        let result = instrument_code(code);
+        assert_eq!(result, "if (true) { __line(1, 13, wait)(1); }");
+    }
+
+    #[test]
+    fn test_missing_semicolon_keeps_statements_apart() {
+        let code = "let x = 1\nstep_up()";
+        let result = instrument_code(code);
+        assert_eq!(result, "let x = 1\n__line(2, 1, step_up)()");
+    }
+
+    #[test]
+    fn test_syntax_error_returns_code_unchanged() {
+        let code = "step_up(;";
+        assert_eq!(instrument_code(code), code);
+    }
+
+    #[test]
+    fn test_budget_checks_function_bodies() {
+        let code = "function walk(n) { step_up(); }\nwalk(3);\n[3, 1].sort((a, b) => a - b);";
+        let result = instrument_code_with_budget(code);
+        assert_eq!(
+            result,
+            "function walk(n) { __budget(); __line(1, 20, step_up)(); }\nwalk(3);\n\
+             [3, 1].sort((a, b) => (__budget(), a - b));"
+        );
+    }
+
+    #[test]
+    fn test_budget_checks_loop_bodies() {
+        let code = "while (busy) { tick(); }\nfor (;;) step_up();\n\
+                    for (const x of [\")\"]) /* ) */ do x--; while (x > 0)";
+        let result = instrument_code_with_budget(code);
+        assert_eq!(
+            result,
+            "while (busy) { __budget(); tick(); }\nfor (;__budget();) __line(2, 10, step_up)();\n\
+             for (const x of [\")\"]) /* ) */ {__budget(); do x--; while (__budget(), x > 0) }"
+        );
+    }
+
+    #[test]
+    fn test_budget_checks_keep_the_else() {
+        // an `if` put in front of the loop body would take the `else`
+        let code = "if (a) while (c) x(); else y();\n\
+                    if (a) for (k in o) if (k) x()\nelse { y() } else z();";
+        let result = instrument_code_with_budget(code);
+        assert_eq!(
+            result,
+            "if (a) while (__budget(), c) x(); else y();\n\
+             if (a) for (k in o) {__budget(); if (k) x()\nelse { y() } } else z();"
+        );
+
+        let line = result.lines().next().unwrap();
+        assert_eq!(original_column(line, 27), 15); // c
+        assert_eq!(original_column(line, 40), 28); // y
+        let line = result.lines().nth(1).unwrap();
+        assert_eq!(original_column(line, 34), 21); // if (k)
+    }
+
+    #[test]
+    fn test_budget_keeps_use_strict_first() {
+        let code = "function f() { 'use strict'; return 1; }";
+        let result = instrument_code_with_budget(code);
+        assert_eq!(
+            result,
+            "function f() { 'use strict'; __budget(); return 1; }"
+        );
+    }
+
+    #[test]
+    fn test_original_column() {
+        let line = "function f() { __budget(); __line(1, 16, step_up)(x); }";
+        assert_eq!(original_column(line, 1), 1);
+        assert_eq!(original_column(line, 16), 15); // inside the check: the code after it
+        assert_eq!(original_column(line, 42), 16); // step_up
+        assert_eq!(original_column(line, 50), 23); // (x)
+        assert_eq!(original_column("let x = 1;", 5), 5);
+
+        let line = "f(x => (__budget(), x * 2)); g();";
+        assert_eq!(original_column(line, 21), 8); // x * 2
+        assert_eq!(original_column(line, 30), 16); // g
+    }
+
+    #[test]
+    fn test_debugger_passes_the_scope() {
+        let code = "let n = 2;\nstep_up(n);";
+        let result = instrument_code_for_debugger(code);
+        assert_eq!(
+            result,
+            "let n = 2;\n__line(2, 1, step_up, (__n) => eval(__n))(n);\n\
+             function __global_scope(__n) { return eval(__n); }\n"
+        );
+
+        let line = result.lines().nth(1).unwrap();
+        assert_eq!(original_column(line, 13), 1); // step_up
+        assert_eq!(original_column(line, 42), 8); // (n)
+    }
+
+    #[test]
+    fn test_identifier_names() {
+        let code =
+            "let count = 0;\nfunction walk(steps) { for (let i = 0; i < steps; i++) step_up(); }\n";
+        assert_eq!(identifier_names(code), vec!["count", "walk", "steps", "i"]);
+    }
+
+    #[test]
+    fn test_byte_offset_with_multibyte_chars() {
+        let code = "// ü\nlet ä = 1; step_up();";
+        let result = instrument_code(code);
+        assert_eq!(result, "// ü\nlet ä = 1; __line(2, 12, step_up)();");
+    }
 }
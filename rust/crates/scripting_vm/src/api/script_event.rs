@@ -2,6 +2,6 @@ use game_core::api::commands::PlayerCommand;
 
 #[derive(Debug, Clone)]
 pub enum ScriptEvent {
-    Line(usize),
+    Position { line: usize, column: usize },
     Command(PlayerCommand),
 }
@@ -1,16 +1,32 @@
-use boa_engine::{Context, JsObject, JsResult, JsString, JsValue};
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, FunctionObjectBuilder, ObjectInitializer},
+    property::Attribute,
+    Context, JsResult, JsString, JsValue, NativeFunction,
+};
 use game_core::character::snapshot::CharacterSnapshot;
 
-/* Helper to transform CharacterSnapshot to JSObject
+use crate::api::map_view::map_object;
+use crate::api::memory::memory_object;
+use crate::runtime::script_instance::ScriptInstance;
+
+/* The character object given to `update(character, dt)`.
+ * It is built once per context: its properties are getters reading the snapshot of
+ * the last call (`ScriptInstance::character`), so a call only stores the new snapshot.
+ * A script which keeps the object sees the character as it is now.
  * Field mapping:
   ┌─────────────────────┬────────────────────────────────┬───────────────────────────────┐
   │     JS property     │          Rust source           │            JS type            │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
   │ character.id        │ snapshot.id (u32)              │ number                        │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
-  │ character.x         │ snapshot.cell_position.x (f32) │ number                        │
+  │ character.x         │ snapshot.cell_position.x (i32) │ number                        │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.y         │ snapshot.cell_position.y (i32) │ number                        │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.cell_x    │ snapshot.cell_position.x (i32) │ number                        │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
-  │ character.y         │ snapshot.cell_position.y (f32) │ number                        │
+  │ character.cell_y    │ snapshot.cell_position.y (i32) │ number                        │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
   │ character.vx        │ snapshot.velocity.x (f32)      │ number                        │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
@@ -20,65 +36,118 @@ use game_core::character::snapshot::CharacterSnapshot;
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
   │ character.is_idle   │ snapshot.is_idle (bool)        │ boolean                       │
   ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.state     │ snapshot.state (StateType)     │ "idle"/"move"/"turn"/"wait"/  │
+  │                     │                                │ "animate"                     │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
   │ character.direction │ snapshot.direction.to_string() │ "north"/"south"/"east"/"west" │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.inventory │ snapshot.inventory             │ array of item names           │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.frame     │ snapshot.frame (u64)           │ number                        │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.memory    │ snapshot.blackboard            │ read/write object, see memory │
+  ├─────────────────────┼────────────────────────────────┼───────────────────────────────┤
+  │ character.map       │ the world view of the script   │ read-only cells, see map_view │
   └─────────────────────┴────────────────────────────────┴───────────────────────────────┘
 
-  snapshot.blackboard is omitted
+  `memory` and `map` are the objects of the context, see `memory` and `map_view`.
 */
-pub fn snapshot_to_js_object(
-    snapshot: &CharacterSnapshot,
+
+type Field = fn(&CharacterSnapshot) -> JsValue;
+
+const FIELDS: &[(&str, Field)] = &[
+    ("id", |snapshot| JsValue::from(snapshot.id)),
+    ("x", |snapshot| JsValue::from(snapshot.cell_position.x)),
+    ("y", |snapshot| JsValue::from(snapshot.cell_position.y)),
+    ("cell_x", |snapshot| JsValue::from(snapshot.cell_position.x)),
+    ("cell_y", |snapshot| JsValue::from(snapshot.cell_position.y)),
+    ("vx", |snapshot| JsValue::from(snapshot.velocity.x as f64)),
+    ("vy", |snapshot| JsValue::from(snapshot.velocity.y as f64)),
+    ("speed", |snapshot| {
+        JsValue::from(snapshot.current_speed as f64)
+    }),
+    ("is_idle", |snapshot| JsValue::from(snapshot.is_idle)),
+    ("state", |snapshot| match snapshot.state {
+        Some(state) => JsValue::from(JsString::from(state.name())),
+        None => JsValue::undefined(),
+    }),
+    ("direction", |snapshot| {
+        JsValue::from(JsString::from(snapshot.direction.to_string().as_str()))
+    }),
+    ("frame", |snapshot| JsValue::from(snapshot.frame as f64)),
+];
+
+/// Registers the character object as the hidden global `__character`.
+/// `memory` and `map` have to be registered before.
+pub(crate) fn register_character(ctx: &mut Context) {
+    let memory = memory_object(ctx).unwrap_or_default();
+    let map = map_object(ctx).unwrap_or_default();
+
+    let mut getters = Vec::new();
+    for (name, field) in FIELDS {
+        let field = *field;
+        let getter = NativeFunction::from_copy_closure(move |_this, _args, ctx| {
+            Ok(ctx
+                .get_data::<ScriptInstance>()
+                .and_then(|instance| instance.character.borrow().as_ref().map(field))
+                .unwrap_or_default())
+        });
+        getters.push((*name, getter));
+    }
+    getters.push(("inventory", NativeFunction::from_fn_ptr(inventory)));
+
+    let getters: Vec<_> = getters
+        .into_iter()
+        .map(|(name, getter)| {
+            let getter = FunctionObjectBuilder::new(ctx.realm(), getter)
+                .name(name)
+                .build();
+            (name, getter)
+        })
+        .collect();
+
+    let mut character = ObjectInitializer::new(ctx);
+    for (name, getter) in getters {
+        character.accessor(
+            JsString::from(name),
+            Some(getter),
+            None,
+            Attribute::ENUMERABLE,
+        );
+    }
+    let character = character
+        .property(js_string!("memory"), memory, Attribute::ENUMERABLE)
+        .property(js_string!("map"), map, Attribute::ENUMERABLE)
+        .build();
+
+    let _ = ctx.register_global_property(js_string!("__character"), character, Attribute::empty());
+}
+
+/// Gives the snapshot to the character object of the context and returns the object
+pub(crate) fn character_object(
+    snapshot: CharacterSnapshot,
     ctx: &mut Context,
-) -> JsResult<JsObject> {
-    let obj = JsObject::with_object_proto(ctx.intrinsics());
+) -> JsResult<JsValue> {
+    if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+        *instance.character.borrow_mut() = Some(snapshot);
+    }
+    ctx.global_object().get(js_string!("__character"), ctx)
+}
 
-    obj.set(
-        JsString::from("id"),
-        JsValue::from(snapshot.id as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("x"),
-        JsValue::from(snapshot.cell_position.x as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("y"),
-        JsValue::from(snapshot.cell_position.y as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("vx"),
-        JsValue::from(snapshot.velocity.x as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("vy"),
-        JsValue::from(snapshot.velocity.y as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("speed"),
-        JsValue::from(snapshot.current_speed as f64),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("is_idle"),
-        JsValue::from(snapshot.is_idle),
-        false,
-        ctx,
-    )?;
-    obj.set(
-        JsString::from("direction"),
-        JsValue::from(JsString::from(snapshot.direction.to_string().as_str())),
-        false,
-        ctx,
-    )?;
+// The names of the items, the array is built when the script reads it
+fn inventory(_this: &JsValue, _args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let items = ctx
+        .get_data::<ScriptInstance>()
+        .and_then(|instance| {
+            let character = instance.character.borrow();
+            character
+                .as_ref()
+                .map(|snapshot| snapshot.inventory.clone())
+        })
+        .unwrap_or_default();
 
-    Ok(obj)
+    let items = items
+        .iter()
+        .map(|item| JsValue::from(JsString::from(item.as_str())));
+    Ok(JsArray::from_iter(items, ctx).into())
 }
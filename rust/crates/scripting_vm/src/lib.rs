@@ -2,4 +2,5 @@ pub mod api;
 pub mod runtime;
 pub mod vm;
 
+pub use runtime::limits::ScriptLimits;
 pub use vm::ScriptVM;
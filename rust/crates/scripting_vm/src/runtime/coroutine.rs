@@ -0,0 +1,483 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use boa_engine::{Context, JsResult, Source};
+use game_core::api::commands::ExecutionPlayerCommand;
+use game_core::api::sensing::WorldView;
+use game_core::character::snapshot::CharacterSnapshot;
+use game_core::executor::{CommandSource, GameEvent, Variables};
+use game_core::ScriptedCharacterLogic;
+
+use crate::{
+    api::console::{record, ConsoleMessage},
+    api::hooks::{call_hook, has_hooks, run_hooks, START},
+    api::preprocessor::{
+        identifier_names, instrument_code_for_debugger, instrument_code_with_budget,
+    },
+    runtime::{
+        limits::{Budget, ScriptLimits},
+        script_instance::{ScriptInstance, Stepper},
+    },
+    vm::{script_error::ScriptError, vm::create_context},
+};
+
+/// Messages sent from the script thread to the host
+pub enum CoroutineEvent {
+    Command(ExecutionPlayerCommand),
+    Finished,
+    Error(ScriptError),
+    Variables(Variables), // the answer to `HostMessage::Inspect`
+    Console(ConsoleMessage),
+    Idle, // the script waits for events in its hooks, see `hooks`
+}
+
+/// Messages sent from the host to the suspended script
+pub enum HostMessage {
+    Resume(Resume),
+    Inspect, // the debugger asks for the variables, the script stays suspended
+}
+
+/// Message sent from the host to resume the script
+pub struct Resume {
+    pub last_result: bool, // result of the last command, returned by the API call
+    pub world: WorldView,  // the world after the command was performed
+    pub events: Vec<GameEvent>, // since the last resume, for the hooks
+    pub dt: f32,           // time since the last resume
+    pub character: CharacterSnapshot, // for `update(character, dt)`
+}
+
+/// A script suspended at every API call.
+///
+/// The script runs on its own thread with its own JS context. Each API call such as
+/// `step_up()` sends the command to the host and blocks until the host resumes the
+/// script with the command result, which becomes the return value of the call:
+/// ```text
+/// while (step_up()) {}   // walk north until blocked
+/// step_right();
+/// ```
+/// The host blocks while waiting for the next command, so the script and the game
+/// never run concurrently and the execution is deterministic.
+/// Scripts are kept short between API calls by the time limit of `ScriptLimits`,
+/// the clock restarts every time the script is resumed.
+///
+/// A script with lifecycle hooks (see `hooks`) doesn't finish after its main code:
+/// every resume calls its hooks, and a resume without any command waits for the next one.
+pub struct ScriptCoroutine {
+    yield_rx: Receiver<CoroutineEvent>,
+    resume_tx: Sender<HostMessage>,
+    started: bool,
+    finished: bool,
+    events: Vec<GameEvent>, // observed since the last resume
+    dt: f32,
+    frame: u64,
+    error: Rc<RefCell<Option<ScriptError>>>,
+    console: Rc<RefCell<Vec<ConsoleMessage>>>, // messages printed by the script
+}
+
+impl ScriptCoroutine {
+    pub(crate) fn start(
+        code: &str,
+        limits: ScriptLimits,
+        debug: bool,
+        error: Rc<RefCell<Option<ScriptError>>>,
+        console: Rc<RefCell<Vec<ConsoleMessage>>>,
+    ) -> Self {
+        let (yield_tx, yield_rx) = channel::<CoroutineEvent>();
+        let (resume_tx, resume_rx) = channel::<HostMessage>();
+
+        // with the debugger the scope of every API call is kept to read the variables
+        let (instrumented, debug_names) = if debug {
+            (instrument_code_for_debugger(code), identifier_names(code))
+        } else {
+            (instrument_code_with_budget(code), Vec::new())
+        };
+
+        // Dropping the coroutine closes the resume channel: the next API call in the
+        // script fails and the thread exits on its own, so it is never joined.
+        thread::spawn(move || {
+            // the script starts on the first resume, which brings the initial world view
+            let Ok(HostMessage::Resume(first)) = resume_rx.recv() else {
+                return;
+            };
+
+            let stepper = Rc::new(Stepper::new(yield_tx, resume_rx));
+            let mut ctx = create_context(ScriptInstance {
+                events: RefCell::default(),
+                world: RefCell::default(),
+                stepper: Some(stepper.clone()),
+                budget: Budget::new(limits),
+                debug_names,
+                console: RefCell::default(),
+                character: RefCell::default(),
+            });
+            stepper.receive(first, &mut ctx);
+            if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+                instance.budget.start();
+            }
+
+            let event = match run(&instrumented, &stepper, &mut ctx) {
+                Ok(()) => CoroutineEvent::Finished,
+                Err(err) => {
+                    CoroutineEvent::Error(ScriptError::from_js_error(err).in_source(&instrumented))
+                }
+            };
+            let _ = stepper.yield_tx.send(event);
+        });
+
+        Self {
+            yield_rx,
+            resume_tx,
+            started: false,
+            finished: false,
+            events: Vec::new(),
+            dt: 0.0,
+            frame: 0,
+            error,
+            console,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Resumes the script with the result of the previous command, the current world view
+    /// and the character, and blocks until it yields the next command, waits or finishes.
+    /// The first call starts the script.
+    pub fn resume(
+        &mut self,
+        last_result: bool,
+        world: WorldView,
+        mut character: CharacterSnapshot,
+    ) -> Option<ExecutionPlayerCommand> {
+        if self.finished {
+            return None;
+        }
+
+        character.frame = self.frame;
+        let resume = Resume {
+            last_result,
+            world,
+            events: std::mem::take(&mut self.events),
+            dt: std::mem::take(&mut self.dt),
+            character,
+        };
+        if self.resume_tx.send(HostMessage::Resume(resume)).is_err() {
+            self.finished = true;
+            return None;
+        }
+        self.started = true;
+
+        match self.next_event() {
+            Some(CoroutineEvent::Command(cmd)) => Some(cmd),
+            Some(CoroutineEvent::Idle) => None,
+            Some(CoroutineEvent::Error(err)) => {
+                *self.error.borrow_mut() = Some(err);
+                self.finished = true;
+                None
+            }
+            _ => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+
+    // Waits for the next event of the script, the messages printed on the way are recorded
+    fn next_event(&self) -> Option<CoroutineEvent> {
+        loop {
+            match self.yield_rx.recv().ok()? {
+                CoroutineEvent::Console(message) => record(&self.console, message),
+                event => return Some(event),
+            }
+        }
+    }
+
+    /// Returns the variables of the script where it is suspended, `None` before
+    /// the script has started and after it has finished.
+    /// The names are known only if the coroutine was started with the debugger
+    /// (see `ScriptVM::set_debug`).
+    pub fn variables(&mut self) -> Option<Variables> {
+        if !self.started || self.finished {
+            return None;
+        }
+        self.resume_tx.send(HostMessage::Inspect).ok()?;
+        match self.next_event() {
+            Some(CoroutineEvent::Variables(variables)) => Some(variables),
+            _ => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+impl CommandSource for ScriptCoroutine {
+    fn next_command(
+        &mut self,
+        last_result: Option<bool>,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        self.resume(
+            last_result.unwrap_or(true),
+            WorldView::from_character(character),
+            character.snapshot(),
+        )
+    }
+
+    // the sensing calls and the character object see the cell and direction of the saved run
+    fn replay_command(
+        &mut self,
+        last_result: Option<bool>,
+        world: WorldView,
+        character: &ScriptedCharacterLogic,
+    ) -> Option<ExecutionPlayerCommand> {
+        let mut snapshot = character.snapshot();
+        snapshot.cell_position = world.cell();
+        snapshot.direction = world.direction();
+        self.resume(last_result.unwrap_or(true), world, snapshot)
+    }
+
+    fn variables(&mut self) -> Option<Variables> {
+        ScriptCoroutine::variables(self)
+    }
+
+    fn observe(&mut self, frame: u64, dt: f32, events: &[GameEvent]) {
+        if self.finished {
+            return;
+        }
+        self.events.extend_from_slice(events);
+        self.dt += dt;
+        self.frame = frame;
+    }
+
+    // until the script has finished: it may wait for events in its hooks
+    fn is_alive(&self) -> bool {
+        !self.finished
+    }
+}
+
+// Runs the main code and `start()`, then the hooks each time the host resumes the script
+fn run(code: &str, stepper: &Stepper, ctx: &mut Context) -> JsResult<()> {
+    ctx.eval(Source::from_bytes(code))?;
+    call_hook(START, &[], ctx)?;
+    if !has_hooks(ctx) {
+        return Ok(());
+    }
+
+    // the main code got the results of its commands, the hooks get the next events
+    {
+        let mut inbox = stepper.inbox.borrow_mut();
+        inbox.events.clear();
+        inbox.dt = 0.0;
+    }
+
+    loop {
+        if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+            instance.budget.start();
+            // the positions of the last pass if it gave no command
+            instance.events.borrow_mut().clear();
+        }
+        let yielded = stepper.yielded.get();
+        let inbox = stepper.inbox.take();
+        run_hooks(&inbox.events, inbox.character, inbox.dt, ctx)?;
+
+        // no command for the host: wait for the next resume
+        if stepper.yielded.get() == yielded && stepper.idle(ctx).is_none() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::vm::ScriptVM;
+    use game_core::save::SaveGame;
+    use game_core::test_utils::test_world::{animator, flat_map};
+    use game_core::{ScriptedCharacterLogic, StateRequest, World};
+    use platform::types::{Direction, Vector2Di};
+
+    // Player at `cell` of a 4×4 map, the coroutine of `code` as its source
+    fn make_world(vm: &mut ScriptVM, cell: (i32, i32)) -> World {
+        let mut world = World::new(flat_map(4, 4));
+        let mut player = ScriptedCharacterLogic::new(1, animator());
+        player.set_logic_map(world.map().clone());
+        player.set_cell_position(cell.0, cell.1);
+        player.set_direction(Direction::SOUTH);
+        player.try_transition(StateRequest::Idle).unwrap();
+        world.set_player(player);
+        world.set_source(Box::new(vm.start_coroutine()));
+        world
+    }
+
+    // Ticks until the executor has finished, at most `ticks` times
+    fn run(world: &mut World, ticks: usize) {
+        for _ in 0..ticks {
+            if world.executor().is_finished() {
+                return;
+            }
+            world.tick();
+        }
+    }
+
+    fn cell(world: &World) -> Vector2Di {
+        world.player().unwrap().get_cell_position()
+    }
+
+    // The texts printed by the script since the last call
+    fn printed(vm: &mut ScriptVM) -> Vec<String> {
+        vm.take_console().into_iter().map(|m| m.text).collect()
+    }
+
+    #[test]
+    fn test_commands_return_their_result() {
+        let code = "let steps = 0;\n\
+                    while (step_up()) { steps++; }\n\
+                    console.log(steps);";
+        let mut vm = ScriptVM::new(code).unwrap();
+        let mut world = make_world(&mut vm, (1, 3));
+        run(&mut world, 1000);
+
+        assert!(world.executor().is_finished());
+        // three steps and the blocked one
+        assert_eq!(world.executor().executed_commands(), 4);
+        assert_eq!(printed(&mut vm), ["3"]);
+        assert!(vm.take_error().is_none());
+
+        let code = "if (step_up()) { console.log(\"moved\"); } else { console.log(\"blocked\"); }";
+        let mut vm = ScriptVM::new(code).unwrap();
+        let mut world = make_world(&mut vm, (1, 1));
+        run(&mut world, 1000);
+        assert_eq!(cell(&world), Vector2Di { x: 1, y: 0 });
+        assert_eq!(printed(&mut vm), ["moved"]);
+
+        let mut world = make_world(&mut vm, (1, 0));
+        run(&mut world, 1000);
+        assert_eq!(printed(&mut vm), ["blocked"]);
+    }
+
+    #[test]
+    fn test_hooks_keep_the_coroutine_idle() {
+        let code = "let ticks = 0;\n\
+                    function update() { ticks++; if (ticks == 10) step_down(); }";
+        let mut vm = ScriptVM::new(code).unwrap();
+        let mut world = make_world(&mut vm, (1, 1));
+        run(&mut world, 200);
+
+        // the script waits for the next frames, it never finishes
+        assert!(!world.executor().is_finished());
+        assert!(world.executor().has_source());
+        assert_eq!(cell(&world), Vector2Di { x: 1, y: 2 });
+        assert_eq!(world.executor().executed_commands(), 1);
+        assert!(vm.take_error().is_none());
+    }
+
+    #[test]
+    fn test_update_keeps_the_character_and_follows_a_new_function() {
+        let code = "let kept = null;\n\
+                    let frames = [];\n\
+                    function update(character) {\n\
+                        if (kept == null) { kept = character; }\n\
+                        frames.push(kept.frame);\n\
+                        if (frames.length == 3) { update = walk; }\n\
+                    }\n\
+                    function walk(character) {\n\
+                        console.log(kept === character, frames[0] < frames[1] && frames[1] < frames[2]);\n\
+                        step_down();\n\
+                        update = function () {\n\
+                            if (kept.y == 2) { console.log(\"moved\"); update = null; }\n\
+                        };\n\
+                    }";
+        let mut vm = ScriptVM::new(code).unwrap();
+        let mut world = make_world(&mut vm, (1, 1));
+        run(&mut world, 200);
+
+        // the object of the first call sees the frames and the cell of the later ones
+        assert_eq!(printed(&mut vm), ["true true", "moved"]);
+        assert_eq!(cell(&world), Vector2Di { x: 1, y: 2 });
+        assert_eq!(world.executor().executed_commands(), 1);
+        assert!(vm.take_error().is_none());
+    }
+
+    #[test]
+    fn test_script_without_hooks_finishes() {
+        let mut vm = ScriptVM::new("step_down();").unwrap();
+        let mut world = make_world(&mut vm, (1, 1));
+        run(&mut world, 1000);
+
+        assert!(world.executor().is_finished());
+        assert!(!world.executor().has_source());
+        assert_eq!(cell(&world), Vector2Di { x: 1, y: 2 });
+        assert!(vm.take_error().is_none());
+    }
+
+    #[test]
+    fn test_loaded_save_answers_as_before() {
+        let code = "let n = 0;\n\
+                    if (can_move(\"north\")) { n = 1; }\n\
+                    step_up();\n\
+                    step_up();\n\
+                    wait(1);\n\
+                    if (n == 1) { step_down(); } else { wait(0.1); }";
+        let mut vm = ScriptVM::new(code).unwrap();
+        let mut world = make_world(&mut vm, (0, 2));
+        for _ in 0..1000 {
+            if world.executor().executed_commands() == 3 {
+                break;
+            }
+            world.tick();
+        }
+        // saved during the wait, at the top of the map
+        assert_eq!(cell(&world), Vector2Di { x: 0, y: 0 });
+        let save = SaveGame::from_ron(&world.save().to_ron().unwrap()).unwrap();
+        run(&mut world, 1000);
+        assert_eq!(cell(&world), Vector2Di { x: 0, y: 1 });
+
+        // `can_move` is answered from the cell of the first poll, not from the loaded one
+        let mut loaded = make_world(&mut vm, (0, 2));
+        loaded.load(&save).unwrap();
+        loaded
+            .resume_source(Box::new(vm.start_coroutine()), &save)
+            .unwrap();
+        run(&mut loaded, 1000);
+        assert!(loaded.executor().is_finished());
+        assert_eq!(cell(&loaded), Vector2Di { x: 0, y: 1 });
+        assert!(vm.take_error().is_none());
+
+        // another script doesn't give the saved commands
+        vm.set_code("step_down();");
+        let mut loaded = make_world(&mut vm, (0, 2));
+        loaded.load(&save).unwrap();
+        let result = loaded.resume_source(Box::new(vm.start_coroutine()), &save);
+        assert!(result.is_err());
+        assert!(!loaded.executor().has_source());
+    }
+
+    #[test]
+    fn test_dropped_while_suspended() {
+        let mut vm = ScriptVM::new("step_down();\nstep_down();").unwrap();
+        let mut coroutine = vm.start_coroutine();
+        let player = ScriptedCharacterLogic::new(1, animator());
+        let command = coroutine.resume(
+            true,
+            game_core::api::sensing::WorldView::from_character(&player),
+            player.snapshot(),
+        );
+        assert!(command.is_some());
+        assert!(!coroutine.is_finished());
+
+        // the script is suspended in the first call: dropping it doesn't block,
+        // and the failure of the call in the dropped thread is not reported
+        drop(coroutine);
+        assert!(vm.take_error().is_none());
+
+        // the VM still runs its script
+        let mut world = make_world(&mut vm, (1, 1));
+        run(&mut world, 1000);
+        assert!(world.executor().is_finished());
+        assert_eq!(cell(&world), Vector2Di { x: 1, y: 3 });
+        assert!(vm.take_error().is_none());
+    }
+}
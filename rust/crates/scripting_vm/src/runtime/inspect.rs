@@ -0,0 +1,131 @@
+use boa_engine::{js_string, Context, JsString, JsValue};
+use game_core::executor::{Variable, Variables};
+
+use crate::runtime::script_instance::ScriptInstance;
+
+// Values longer than this are cut for the debugger view
+const MAX_VALUE_LENGTH: usize = 80;
+
+/// Reads the variables of a script paused in an API call.
+///
+/// The script has to be instrumented with `instrument_code_for_debugger`: `__line` keeps
+/// the scope of the last API call in the global `__scope` and `__global_scope` evaluates
+/// names in the global scope. Every name found in the script is evaluated in both scopes,
+/// a name which can't be evaluated there (not declared yet, or declared in another
+/// function) is left out, so are functions and the names of the JS builtins.
+/// A local variable with the same value as a global one of the same name is only shown
+/// as global.
+pub(crate) fn inspect_variables(ctx: &mut Context) -> Variables {
+    let names = ctx
+        .get_data::<ScriptInstance>()
+        .map(|instance| instance.debug_names.clone())
+        .unwrap_or_default();
+
+    let global = ctx.global_object();
+    let scope = global.get(js_string!("__scope"), ctx).unwrap_or_default();
+    let global_scope = global
+        .get(js_string!("__global_scope"), ctx)
+        .unwrap_or_default();
+
+    // the builtins of a fresh context, `Math` or `undefined` are not variables of the script
+    let mut fresh = Context::default();
+    let builtins = fresh.global_object();
+
+    let mut variables = Variables::default();
+    for name in names {
+        let is_builtin = builtins
+            .has_own_property(JsString::from(name.as_str()), &mut fresh)
+            .unwrap_or(false);
+        if is_builtin {
+            continue;
+        }
+
+        let global_value = evaluate(&global_scope, &name, ctx);
+        let local_value = evaluate(&scope, &name, ctx);
+
+        if let Some(value) = &global_value {
+            variables.globals.push(variable(&name, value));
+        }
+        if let Some(value) = local_value {
+            let same_as_global = global_value
+                .as_ref()
+                .is_some_and(|global| JsValue::same_value(global, &value));
+            if !same_as_global {
+                variables.locals.push(variable(&name, &value));
+            }
+        }
+    }
+    variables
+}
+
+// Calls the scope function with the name, `None` if the name is not a data variable there
+fn evaluate(scope: &JsValue, name: &str, ctx: &mut Context) -> Option<JsValue> {
+    let scope = scope.as_callable()?;
+    let value = scope
+        .call(
+            &JsValue::undefined(),
+            &[JsValue::from(JsString::from(name))],
+            ctx,
+        )
+        .ok()?;
+    (!value.is_callable()).then_some(value)
+}
+
+fn variable(name: &str, value: &JsValue) -> Variable {
+    let mut text = value.display().to_string();
+    if text.chars().count() > MAX_VALUE_LENGTH {
+        text = text.chars().take(MAX_VALUE_LENGTH).collect::<String>() + "...";
+    }
+    Variable {
+        name: name.to_string(),
+        value: text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::vm::ScriptVM;
+    use game_core::api::sensing::WorldView;
+    use game_core::executor::Variable;
+    use game_core::test_utils::test_world::animator;
+    use game_core::ScriptedCharacterLogic;
+
+    fn value<'a>(variables: &'a [Variable], name: &str) -> Option<&'a str> {
+        variables
+            .iter()
+            .find(|variable| variable.name == name)
+            .map(|variable| variable.value.as_str())
+    }
+
+    #[test]
+    fn test_variables_of_a_paused_function() {
+        let code = "let total = 10;\n\
+                    let name = \"global\";\n\
+                    function walk(steps) {\n\
+                        let name = \"local\";\n\
+                        let left = steps - 1;\n\
+                        step_up();\n\
+                    }\n\
+                    walk(3);";
+        let mut vm = ScriptVM::new(code).unwrap();
+        vm.set_debug(true);
+        let mut coroutine = vm.start_coroutine();
+        let player = ScriptedCharacterLogic::new(1, animator());
+
+        // the script is suspended in `step_up()`, inside `walk`
+        let command = coroutine.resume(true, WorldView::from_character(&player), player.snapshot());
+        assert!(command.is_some());
+        let variables = coroutine.variables().unwrap();
+
+        assert_eq!(value(&variables.globals, "total"), Some("10"));
+        assert_eq!(value(&variables.locals, "total"), None);
+        assert_eq!(value(&variables.locals, "steps"), Some("3"));
+        assert_eq!(value(&variables.locals, "left"), Some("2"));
+        assert_eq!(value(&variables.globals, "left"), None);
+        // the local one hides the global one in the function, both are shown
+        assert_eq!(value(&variables.globals, "name"), Some("\"global\""));
+        assert_eq!(value(&variables.locals, "name"), Some("\"local\""));
+        // functions are not variables
+        assert_eq!(value(&variables.globals, "walk"), None);
+    }
+}
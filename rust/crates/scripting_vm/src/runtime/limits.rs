@@ -0,0 +1,133 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use boa_engine::{JsNativeError, JsResult};
+
+// Beginnings of the messages of the errors thrown by Budget, to recognise them in ScriptError
+pub(crate) const TIME_LIMIT_MESSAGE: &str = "Script ran longer than";
+pub(crate) const COMMAND_LIMIT_MESSAGE: &str = "Script gave more than";
+
+/// Limits of a script run, so a mistake in a kid's script can't freeze the game.
+///
+/// The loop, recursion and stack limits are checked by boa. The time and command
+/// limits are checked by the game API and at the start of every function and loop body
+/// (see `instrument_code_with_budget`), so any runaway script hits one of them.
+/// The heap used by the script can't be limited in boa: `stack_size` is the only memory limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScriptLimits {
+    pub loop_iterations: u64, // iterations of all loops in one function call
+    pub recursion: usize,     // depth of nested function calls
+    pub stack_size: usize,    // values on the VM stack
+    pub time: Duration,       // per run or `update()` call, between two commands of a coroutine
+    pub commands: usize,      // per run or `update()` call, for the whole run of a coroutine
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            loop_iterations: 10_000,
+            recursion: 256,
+            stack_size: 16 * 1024,
+            time: Duration::from_millis(200),
+            commands: 1_000,
+        }
+    }
+}
+
+/// Time and commands spent by the script since the last `start`
+#[derive(Debug, Default)]
+pub struct Budget {
+    limits: Cell<ScriptLimits>,
+    deadline: Cell<Option<Instant>>,
+    commands: Cell<usize>,
+}
+
+impl Budget {
+    pub fn new(limits: ScriptLimits) -> Self {
+        Self {
+            limits: Cell::new(limits),
+            ..Default::default()
+        }
+    }
+
+    pub fn limits(&self) -> ScriptLimits {
+        self.limits.get()
+    }
+
+    pub fn set_limits(&self, limits: ScriptLimits) {
+        self.limits.set(limits);
+    }
+
+    /// Starts a new run: the clock and the command counter start from zero
+    pub fn start(&self) {
+        self.commands.set(0);
+        self.restart_clock();
+    }
+
+    /// Gives the script its full time again, e.g. after the host has resumed a coroutine
+    pub fn restart_clock(&self) {
+        self.deadline.set(Some(Instant::now() + self.limits().time));
+    }
+
+    /// Throws an uncatchable error once the script has used its time
+    pub fn check_time(&self) -> JsResult<()> {
+        match self.deadline.get() {
+            Some(deadline) if Instant::now() > deadline => Err(JsNativeError::runtime_limit()
+                .with_message(format!(
+                    "{} {} ms",
+                    TIME_LIMIT_MESSAGE,
+                    self.limits().time.as_millis()
+                ))
+                .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a command given by the script, throws an uncatchable error over the limit
+    pub fn count_command(&self) -> JsResult<()> {
+        let limit = self.limits().commands;
+        let commands = self.commands.get() + 1;
+        self.commands.set(commands);
+        if commands > limit {
+            return Err(JsNativeError::runtime_limit()
+                .with_message(format!("{} {} commands", COMMAND_LIMIT_MESSAGE, limit))
+                .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_limit() {
+        let budget = Budget::new(ScriptLimits {
+            commands: 2,
+            ..Default::default()
+        });
+        budget.start();
+        assert!(budget.count_command().is_ok());
+        assert!(budget.count_command().is_ok());
+        assert!(budget.count_command().is_err());
+
+        budget.start();
+        assert!(budget.count_command().is_ok());
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let budget = Budget::new(ScriptLimits {
+            time: Duration::ZERO,
+            ..Default::default()
+        });
+        // the clock runs only after start
+        assert!(budget.check_time().is_ok());
+
+        budget.start();
+        std::thread::sleep(Duration::from_millis(1));
+        let err = budget.check_time().unwrap_err();
+        assert!(err.to_string().contains(TIME_LIMIT_MESSAGE));
+    }
+}
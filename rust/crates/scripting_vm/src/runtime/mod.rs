@@ -1 +1,4 @@
+pub mod coroutine;
+pub mod inspect;
+pub mod limits;
 pub mod script_instance;
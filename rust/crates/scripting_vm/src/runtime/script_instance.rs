@@ -1,12 +1,139 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender};
 
-use boa_engine::JsData;
+use boa_engine::{Context, JsData};
 use boa_gc::{Finalize, Trace};
+use game_core::api::commands::ExecutionPlayerCommand;
+use game_core::api::sensing::WorldView;
+use game_core::character::snapshot::CharacterSnapshot;
+use game_core::executor::GameEvent;
 
+use crate::api::console::ConsoleMessage;
 use crate::api::script_event::ScriptEvent;
+use crate::runtime::coroutine::{CoroutineEvent, HostMessage, Resume};
+use crate::runtime::inspect::inspect_variables;
+use crate::runtime::limits::Budget;
 
 #[derive(Default, Trace, Finalize, JsData)]
 pub struct ScriptInstance {
     #[unsafe_ignore_trace]
     pub events: RefCell<Vec<ScriptEvent>>,
+
+    // The world around the character, answers sensing calls like `can_move()`
+    #[unsafe_ignore_trace]
+    pub world: RefCell<Option<WorldView>>,
+
+    // Set only when the script runs as a coroutine (see `ScriptCoroutine`)
+    #[unsafe_ignore_trace]
+    pub stepper: Option<Rc<Stepper>>,
+
+    // Time and commands left to the script, see `ScriptLimits`
+    #[unsafe_ignore_trace]
+    pub budget: Budget,
+
+    // Names the debugger looks up, empty unless the coroutine runs with the debugger
+    #[unsafe_ignore_trace]
+    pub debug_names: Vec<String>,
+
+    // Messages of `console.log()` until the host takes them, unused by coroutines
+    #[unsafe_ignore_trace]
+    pub console: RefCell<Vec<ConsoleMessage>>,
+
+    // The character of the last `update()` call, read by the character object
+    // and its `memory` (see `snapshot`)
+    #[unsafe_ignore_trace]
+    pub character: RefCell<Option<CharacterSnapshot>>,
+}
+
+impl ScriptInstance {
+    /// Drops the position `__line` has recorded for a call which gives no command,
+    /// so a script which only asks questions or prints doesn't pile them up
+    pub fn forget_position(&self) {
+        let mut events = self.events.borrow_mut();
+        if matches!(events.last(), Some(ScriptEvent::Position { .. })) {
+            events.pop();
+        }
+    }
+}
+
+/// Script side of a coroutine: hands commands to the host and waits to be resumed
+pub struct Stepper {
+    pub yield_tx: Sender<CoroutineEvent>,
+    pub resume_rx: Receiver<HostMessage>,
+    pub inbox: RefCell<Inbox>,
+    pub yielded: Cell<usize>, // number of commands handed to the host
+}
+
+/// What the host has brought with the resumes since the hooks were last called
+#[derive(Default)]
+pub struct Inbox {
+    pub events: Vec<GameEvent>,
+    pub dt: f32,
+    pub character: Option<CharacterSnapshot>,
+}
+
+impl Stepper {
+    pub fn new(yield_tx: Sender<CoroutineEvent>, resume_rx: Receiver<HostMessage>) -> Self {
+        Self {
+            yield_tx,
+            resume_rx,
+            inbox: RefCell::default(),
+            yielded: Cell::new(0),
+        }
+    }
+
+    /// Yields the commands one by one and blocks until the host has performed each of them.
+    /// Updates the world view after every command.
+    /// Returns the result of the last command, or `None` if the host has stopped the script.
+    pub fn suspend(
+        &self,
+        commands: Vec<ExecutionPlayerCommand>,
+        ctx: &mut Context,
+    ) -> Option<bool> {
+        let mut result = true;
+        for cmd in commands {
+            self.yield_tx.send(CoroutineEvent::Command(cmd)).ok()?;
+            self.yielded.set(self.yielded.get() + 1);
+            let resume = self.wait_for_resume(ctx)?;
+            result = resume.last_result;
+            self.receive(resume, ctx);
+        }
+        Some(result)
+    }
+
+    /// Tells the host the script has nothing to do and blocks until it is resumed
+    /// with the next events. Returns `None` if the host has stopped the script.
+    pub fn idle(&self, ctx: &mut Context) -> Option<()> {
+        self.yield_tx.send(CoroutineEvent::Idle).ok()?;
+        let resume = self.wait_for_resume(ctx)?;
+        self.receive(resume, ctx);
+        Some(())
+    }
+
+    /// Keeps the world view for the sensing calls and the rest for the hooks
+    pub fn receive(&self, resume: Resume, ctx: &mut Context) {
+        if let Some(instance) = ctx.get_data::<ScriptInstance>() {
+            *instance.world.borrow_mut() = Some(resume.world);
+        }
+        let mut inbox = self.inbox.borrow_mut();
+        inbox.events.extend(resume.events);
+        inbox.dt += resume.dt;
+        inbox.character = Some(resume.character);
+    }
+
+    // Answers the requests of the debugger until the host resumes the script
+    fn wait_for_resume(&self, ctx: &mut Context) -> Option<Resume> {
+        loop {
+            match self.resume_rx.recv().ok()? {
+                HostMessage::Resume(resume) => return Some(resume),
+                HostMessage::Inspect => {
+                    let variables = inspect_variables(ctx);
+                    self.yield_tx
+                        .send(CoroutineEvent::Variables(variables))
+                        .ok()?;
+                }
+            }
+        }
+    }
 }
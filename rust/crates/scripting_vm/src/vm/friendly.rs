@@ -0,0 +1,111 @@
+/*
+ * Friendly messages of the script errors, shown to the kid in the LogBox.
+ * Every message has a key, the host can look the key up in its translations
+ * (e.g. with Godot's `tr()`) and falls back to the English text below.
+ * `{line}`, `{name}`, `{details}` and `{suggestion}` are replaced with the values of the error.
+ */
+
+pub const LINE: &str = "SCRIPT_ERROR_LINE";
+pub const SUGGESTION: &str = "SCRIPT_ERROR_SUGGESTION";
+pub const SYNTAX: &str = "SCRIPT_ERROR_SYNTAX";
+pub const UNKNOWN_NAME: &str = "SCRIPT_ERROR_UNKNOWN_NAME";
+pub const WRONG_ARGUMENT: &str = "SCRIPT_ERROR_WRONG_ARGUMENT";
+pub const LOOP_LIMIT: &str = "SCRIPT_ERROR_LOOP_LIMIT";
+pub const RECURSION_LIMIT: &str = "SCRIPT_ERROR_RECURSION_LIMIT";
+pub const STACK_LIMIT: &str = "SCRIPT_ERROR_STACK_LIMIT";
+pub const TIME_LIMIT: &str = "SCRIPT_ERROR_TIME_LIMIT";
+pub const COMMAND_LIMIT: &str = "SCRIPT_ERROR_COMMAND_LIMIT";
+pub const RUNTIME: &str = "SCRIPT_ERROR_RUNTIME";
+
+/// English text of the message key
+pub fn english(key: &str) -> &'static str {
+    match key {
+        LINE => "Line {line}: ",
+        SUGGESTION => " Did you mean `{suggestion}`?",
+        SYNTAX => "the computer can't read this line. Check the brackets, quotes and semicolons.",
+        UNKNOWN_NAME => "`{name}` is not known, there is no function or variable with this name.",
+        WRONG_ARGUMENT => "`{name}()` can't use the values it was given. {details}",
+        LOOP_LIMIT => "a loop went round too many times. Does its condition ever become false?",
+        RECURSION_LIMIT => {
+            "a function called itself too many times. Does it have a case where it stops?"
+        }
+        STACK_LIMIT => "the script needs too much memory.",
+        TIME_LIMIT => "the script thought for too long without moving the goblin.",
+        COMMAND_LIMIT => "the script gave the goblin too many commands.",
+        _ => "something went wrong: {details}",
+    }
+}
+
+/// Replaces the `{name}` placeholders of the template with the values
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// Number of single character edits turning `a` into `b` (Levenshtein distance)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The name closest to `name`, if it is close enough to be a typo:
+/// `stepUp` and `Step_Up` match `step_up`, `stpe_up` is two edits away
+pub fn closest_name<'a>(name: &str, names: &[&'a str]) -> Option<&'a str> {
+    let normalize = |s: &str| s.to_lowercase().replace('_', "");
+    let normalized = normalize(name);
+    let max_distance = (name.chars().count() / 3).clamp(1, 2);
+
+    names
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&normalized, &normalize(candidate)),
+                *candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("step_up", "step_up"), 0);
+        assert_eq!(edit_distance("stepup", "step_up"), 1);
+        assert_eq!(edit_distance("stpe_up", "step_up"), 2);
+        assert_eq!(edit_distance("", "wait"), 4);
+    }
+
+    #[test]
+    fn test_closest_name() {
+        let names = ["step_up", "step_down", "wait", "pick"];
+        assert_eq!(closest_name("stepUp", &names), Some("step_up"));
+        assert_eq!(closest_name("step_dwn", &names), Some("step_down"));
+        assert_eq!(closest_name("waitt", &names), Some("wait"));
+        assert_eq!(closest_name("jump", &names), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        let text = fill(english(UNKNOWN_NAME), &[("name", "stepUp")]);
+        assert!(text.starts_with("`stepUp` is not known"));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod friendly;
 pub mod script_error;
 pub mod vm;
 
@@ -1,8 +1,34 @@
+use crate::api::bindings::API_FUNCTIONS;
+use crate::api::preprocessor::original_column;
+use crate::runtime::limits::{COMMAND_LIMIT_MESSAGE, TIME_LIMIT_MESSAGE};
+use crate::vm::friendly::{self, closest_name};
+
 #[derive(Debug)]
 pub struct ScriptError {
-    pub message: String,
-    pub line: i32,
+    pub message: String, // boa's message
+    pub line: i32,       // position in the script, -1 if unknown
     pub col: i32,
+    pub kind: ScriptErrorKind,
+    pub suggestion: Option<String>, // a fix for the error, e.g. `step_up()` for `stepUp()`
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptErrorKind {
+    Syntax,
+    UnknownName(String),   // a function or variable which doesn't exist
+    WrongArgument(String), // an API function was called with values it can't use
+    Limit(LimitKind),      // the script ran into one of its `ScriptLimits`
+    Runtime,               // any other error, also values thrown by the script
+}
+
+/// Which limit of `ScriptLimits` stopped the script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    Loop,
+    Recursion,
+    Stack,
+    Time,
+    Commands,
 }
 
 impl ScriptError {
@@ -12,10 +38,133 @@ impl ScriptError {
     pub fn from_js_error(err: boa_engine::JsError) -> Self {
         let message = err.to_string();
         let (line, col) = parse_position(&message);
-        Self { message, line, col }
+        let kind = error_kind(&message);
+        let suggestion = match &kind {
+            ScriptErrorKind::UnknownName(name) => {
+                closest_name(name, API_FUNCTIONS).map(|name| format!("{}()", name))
+            }
+            _ => None,
+        };
+        Self {
+            message,
+            line,
+            col,
+            kind,
+            suggestion,
+        }
+    }
+
+    /// Moves the column from the instrumented code, which the error was raised in,
+    /// back to the script
+    pub(crate) fn in_source(mut self, instrumented: &str) -> Self {
+        if self.line > 0 && self.col > 0 {
+            if let Some(line) = instrumented.lines().nth(self.line as usize - 1) {
+                self.col = original_column(line, self.col as usize) as i32;
+            }
+        }
+        self
+    }
+
+    pub fn is_limit(&self) -> bool {
+        matches!(self.kind, ScriptErrorKind::Limit(_))
+    }
+
+    /// Key of the friendly message, for the translations of the host
+    pub fn message_key(&self) -> &'static str {
+        match &self.kind {
+            ScriptErrorKind::Syntax => friendly::SYNTAX,
+            ScriptErrorKind::UnknownName(_) => friendly::UNKNOWN_NAME,
+            ScriptErrorKind::WrongArgument(_) => friendly::WRONG_ARGUMENT,
+            ScriptErrorKind::Limit(LimitKind::Loop) => friendly::LOOP_LIMIT,
+            ScriptErrorKind::Limit(LimitKind::Recursion) => friendly::RECURSION_LIMIT,
+            ScriptErrorKind::Limit(LimitKind::Stack) => friendly::STACK_LIMIT,
+            ScriptErrorKind::Limit(LimitKind::Time) => friendly::TIME_LIMIT,
+            ScriptErrorKind::Limit(LimitKind::Commands) => friendly::COMMAND_LIMIT,
+            ScriptErrorKind::Runtime => friendly::RUNTIME,
+        }
+    }
+
+    /// The message for the kid, in English
+    pub fn friendly_message(&self) -> String {
+        self.friendly_message_with(|_| None)
+    }
+
+    /// The message for the kid, `translate` returns the text of a message key
+    /// or `None` to use the English text
+    pub fn friendly_message_with(&self, translate: impl Fn(&str) -> Option<String>) -> String {
+        let text = |key: &str| translate(key).unwrap_or_else(|| friendly::english(key).to_string());
+
+        let line = self.line.to_string();
+        let name = match &self.kind {
+            ScriptErrorKind::UnknownName(name) | ScriptErrorKind::WrongArgument(name) => {
+                name.as_str()
+            }
+            _ => "",
+        };
+        let values = [
+            ("line", line.as_str()),
+            ("name", name),
+            ("details", details(&self.message)),
+            ("suggestion", self.suggestion.as_deref().unwrap_or("")),
+        ];
+
+        let mut message = String::new();
+        if self.line > 0 {
+            message.push_str(&text(friendly::LINE));
+        }
+        message.push_str(&text(self.message_key()));
+        if self.suggestion.is_some() {
+            message.push_str(&text(friendly::SUGGESTION));
+        }
+        friendly::fill(&message, &values)
     }
 }
 
+/// Sorts the error by the boa error type and message:
+/// `ReferenceError: stepUp is not defined`, `TypeError: wait() expects ...`,
+/// the errors of the runtime limits are `RuntimeLimit` errors
+fn error_kind(message: &str) -> ScriptErrorKind {
+    if message.starts_with("SyntaxError") {
+        return ScriptErrorKind::Syntax;
+    }
+    if let Some(rest) = message.strip_prefix("ReferenceError: ") {
+        if let Some((name, _)) = rest.split_once(" is not defined") {
+            return ScriptErrorKind::UnknownName(name.to_string());
+        }
+    }
+    if let Some(rest) = message.strip_prefix("TypeError: ") {
+        // the API functions start their messages with their name
+        if let Some((name, _)) = rest.split_once("()") {
+            if API_FUNCTIONS.contains(&name) {
+                return ScriptErrorKind::WrongArgument(name.to_string());
+            }
+        }
+    }
+
+    if message.contains("loop iteration limit") {
+        ScriptErrorKind::Limit(LimitKind::Loop)
+    } else if message.contains("recursive calls") {
+        ScriptErrorKind::Limit(LimitKind::Recursion)
+    } else if message.contains("call stack") {
+        ScriptErrorKind::Limit(LimitKind::Stack)
+    } else if message.contains(TIME_LIMIT_MESSAGE) {
+        ScriptErrorKind::Limit(LimitKind::Time)
+    } else if message.contains(COMMAND_LIMIT_MESSAGE) {
+        ScriptErrorKind::Limit(LimitKind::Commands)
+    } else {
+        ScriptErrorKind::Runtime
+    }
+}
+
+/// The first line of boa's message without the error type
+fn details(message: &str) -> &str {
+    let first_line = message.lines().next().unwrap_or("");
+    first_line
+        .split_once(": ")
+        .map_or(first_line, |(_, details)| details)
+        .trim()
+}
+
 /// Extracts line and column from boa's error string.
 /// Handles two formats:
 /// - Syntax errors:  `"at line 4, col 9"`
@@ -70,3 +219,59 @@ impl std::fmt::Display for ScriptError {
 }
 
 impl std::error::Error for ScriptError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::JsNativeError;
+
+    fn error(err: JsNativeError) -> ScriptError {
+        ScriptError::from_js_error(err.into())
+    }
+
+    #[test]
+    fn test_unknown_function_gets_a_suggestion() {
+        let err = error(JsNativeError::reference().with_message("stepUp is not defined"));
+        assert_eq!(err.kind, ScriptErrorKind::UnknownName("stepUp".into()));
+        assert_eq!(err.suggestion.as_deref(), Some("step_up()"));
+        assert_eq!(
+            err.friendly_message(),
+            "`stepUp` is not known, there is no function or variable with this name. \
+             Did you mean `step_up()`?"
+        );
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let err =
+            error(JsNativeError::syntax().with_message("expected token ';' at line 4, col 9"));
+        assert_eq!(err.kind, ScriptErrorKind::Syntax);
+        assert!(err.friendly_message().starts_with("Line 4: "));
+
+        let err = error(JsNativeError::typ().with_message("can_move() expects a direction"));
+        assert_eq!(err.kind, ScriptErrorKind::WrongArgument("can_move".into()));
+
+        let err = error(
+            JsNativeError::runtime_limit().with_message("Maximum loop iteration limit 10 exceeded"),
+        );
+        assert_eq!(err.kind, ScriptErrorKind::Limit(LimitKind::Loop));
+
+        let err = error(JsNativeError::typ().with_message("not a callable function"));
+        assert_eq!(err.kind, ScriptErrorKind::Runtime);
+        assert_eq!(
+            err.friendly_message(),
+            "something went wrong: not a callable function"
+        );
+    }
+
+    #[test]
+    fn test_translated_message() {
+        let err = error(
+            JsNativeError::runtime_limit().with_message("exceeded maximum call stack length"),
+        );
+        let message = err.friendly_message_with(|key| {
+            (key == friendly::STACK_LIMIT).then(|| "Zu viel Speicher!".to_string())
+        });
+        assert_eq!(message, "Zu viel Speicher!");
+    }
+}
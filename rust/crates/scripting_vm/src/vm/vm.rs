@@ -1,38 +1,64 @@
-use boa_engine::{Context, JsString, JsValue, Source};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use boa_engine::{Context, Source};
 
 use game_core::api::commands::ExecutionPlayerCommand;
-use game_core::character::snapshot::CharacterSnapshot;
+use game_core::api::sensing::WorldView;
 
 use crate::{
     api::{
-        bindings::register_api, preprocessor::instrument_code, script_event::ScriptEvent,
-        snapshot::snapshot_to_js_object,
+        bindings::register_api,
+        console::{record, ConsoleMessage},
+        hooks::{call_hook, START},
+        preprocessor::instrument_code_with_budget,
+        script_event::ScriptEvent,
     },
-    runtime::script_instance::ScriptInstance,
+    runtime::{coroutine::ScriptCoroutine, limits::ScriptLimits, script_instance::ScriptInstance},
     vm::script_error::ScriptError,
 };
 
-const MAX_LOOP_ITERATIONS: u64 = 10_000;
+/// Creates a JS context with the runtime limits of the instance and the game API registered
+pub(crate) fn create_context(instance: ScriptInstance) -> Context {
+    let mut ctx = Context::default();
+
+    set_runtime_limits(&mut ctx, &instance.budget.limits());
+
+    ctx.insert_data(instance);
+
+    register_api(&mut ctx);
+
+    ctx
+}
+
+// The limits checked by boa itself
+fn set_runtime_limits(ctx: &mut Context, limits: &ScriptLimits) {
+    let runtime_limits = ctx.runtime_limits_mut();
+    runtime_limits.set_loop_iteration_limit(limits.loop_iterations);
+    runtime_limits.set_recursion_limit(limits.recursion);
+    runtime_limits.set_stack_size_limit(limits.stack_size);
+}
 
 pub struct ScriptVM {
     ctx: Context,
     code: String,
+    instrumented: String, // the code run by the last `run_script`, for the error positions
+    limits: ScriptLimits,
+    debug: bool, // coroutines keep their variables readable for the debugger
+    coroutine_error: Rc<RefCell<Option<ScriptError>>>, // error raised by the last coroutine
+    console: Rc<RefCell<Vec<ConsoleMessage>>>, // messages printed by the script, all runs
 }
 
 impl ScriptVM {
     pub fn new(code: &str) -> Result<Self, ScriptError> {
-        let mut ctx = Context::default();
-
-        ctx.runtime_limits_mut()
-            .set_loop_iteration_limit(MAX_LOOP_ITERATIONS);
-
-        ctx.insert_data(ScriptInstance::default());
-
-        register_api(&mut ctx);
-
         Ok(Self {
-            ctx,
+            ctx: create_context(ScriptInstance::default()),
             code: code.to_string(),
+            instrumented: String::new(),
+            limits: ScriptLimits::default(),
+            debug: false,
+            coroutine_error: Rc::new(RefCell::new(None)),
+            console: Rc::new(RefCell::new(Vec::new())),
         })
     }
 
@@ -40,73 +66,120 @@ impl ScriptVM {
         self.code = code.to_string();
     }
 
-    pub fn run_script(&mut self) -> Result<Vec<ExecutionPlayerCommand>, ScriptError> {
-        let instrumented = instrument_code(&self.code);
+    /// Sets the limits of the next runs, coroutines started before keep their limits
+    pub fn set_limits(&mut self, limits: ScriptLimits) {
+        self.limits = limits;
+        set_runtime_limits(&mut self.ctx, &limits);
+        if let Some(instance) = self.ctx.get_data::<ScriptInstance>() {
+            instance.budget.set_limits(limits);
+        }
+    }
 
-        let _ = self
-            .ctx
-            .eval(Source::from_bytes(&instrumented))
-            .map_err(ScriptError::from_js_error)?;
+    pub fn limits(&self) -> &ScriptLimits {
+        &self.limits
+    }
+
+    /// Starts the next coroutines with the debugger, so `ScriptCoroutine::variables`
+    /// can read the variables of the paused script
+    pub fn set_debug(&mut self, enabled: bool) {
+        self.debug = enabled;
+    }
 
+    /// Sets the world view used to answer sensing calls (`can_move()`, `cell_ahead()`, ...)
+    /// made by `run_script`. Coroutines get it from the executor instead.
+    pub fn set_world_view(&mut self, world: WorldView) {
         if let Some(instance) = self.ctx.get_data::<ScriptInstance>() {
-            let events = std::mem::take(&mut *instance.events.borrow_mut());
-            Ok(collapse_events(events))
-        } else {
-            Ok(vec![])
+            *instance.world.borrow_mut() = Some(world);
         }
     }
 
-    pub fn tick(
-        &mut self,
-        snapshot: &CharacterSnapshot,
-    ) -> Result<Vec<ExecutionPlayerCommand>, ScriptError> {
-        // Build the character JS object from the snapshot
-        let char_obj =
-            snapshot_to_js_object(snapshot, &mut self.ctx).map_err(ScriptError::from_js_error)?;
-
-        // Get the 'update' function from global scope
-        let global = self.ctx.global_object();
-        let update_val = global
-            .get(JsString::from("update"), &mut self.ctx)
-            .map_err(ScriptError::from_js_error)?;
-
-        // Call update(character) - skip if not defined or not callable
-        if let Some(func_obj) = update_val.as_function() {
-            func_obj
-                .call(&JsValue::undefined(), &[char_obj.into()], &mut self.ctx)
-                .map_err(ScriptError::from_js_error)?;
-        } else {
-            return Ok(vec![]);
+    /// Starts the script as a coroutine which is suspended at every API call.
+    /// Unlike `run_script`, API calls return the result of the performed command,
+    /// so the script can react to the world state.
+    /// The returned coroutine is usually handed to `CommandExecutor::set_source`.
+    pub fn start_coroutine(&mut self) -> ScriptCoroutine {
+        self.coroutine_error.borrow_mut().take();
+        ScriptCoroutine::start(
+            &self.code,
+            self.limits,
+            self.debug,
+            self.coroutine_error.clone(),
+            self.console.clone(),
+        )
+    }
+
+    /// Returns the messages printed with `console.log()` since the last call,
+    /// by the coroutines and `run_script`
+    pub fn take_console(&mut self) -> Vec<ConsoleMessage> {
+        std::mem::take(&mut *self.console.borrow_mut())
+    }
+
+    /// Returns the error which stopped the last coroutine, if any
+    pub fn take_error(&mut self) -> Option<ScriptError> {
+        self.coroutine_error.borrow_mut().take()
+    }
+
+    pub fn run_script(&mut self) -> Result<Vec<ExecutionPlayerCommand>, ScriptError> {
+        self.instrumented = instrument_code_with_budget(&self.code);
+        self.start_budget();
+
+        let result = self
+            .ctx
+            .eval(Source::from_bytes(&self.instrumented))
+            .and_then(|_| call_hook(START, &[], &mut self.ctx));
+        self.flush_console();
+        let events = self.take_events();
+        result.map_err(|err| ScriptError::from_js_error(err).in_source(&self.instrumented))?;
+
+        Ok(collapse_events(events))
+    }
+
+    // The events of the last run, also when it failed: the next run must not give them again
+    fn take_events(&self) -> Vec<ScriptEvent> {
+        self.ctx
+            .get_data::<ScriptInstance>()
+            .map(|instance| std::mem::take(&mut *instance.events.borrow_mut()))
+            .unwrap_or_default()
+    }
+
+    // Moves the messages printed by the last run to the host, also when it failed
+    fn flush_console(&self) {
+        if let Some(instance) = self.ctx.get_data::<ScriptInstance>() {
+            for message in instance.console.take() {
+                record(&self.console, message);
+            }
         }
+    }
 
-        // Collect events
+    // Every run gets the full time and command limits
+    fn start_budget(&self) {
         if let Some(instance) = self.ctx.get_data::<ScriptInstance>() {
-            let events = std::mem::take(&mut *instance.events.borrow_mut());
-            Ok(collapse_events(events))
-        } else {
-            Ok(vec![])
+            instance.budget.start();
         }
     }
 }
 
 /// Collapse interleaved ScriptEvents into ExecutionPlayerCommands.
 ///
-/// Events come in pairs: `[Line(2), Command(MoveNorth), Line(4), Command(MoveEast)]`
-/// Each `Line(N)` sets the current line, each `Command(cmd)` produces an
-/// `ExecutionPlayerCommand` using the most recent line number.
-fn collapse_events(events: Vec<ScriptEvent>) -> Vec<ExecutionPlayerCommand> {
+/// Events come in pairs: `[Position(2, 1), Command(MoveNorth), Position(4, 5), Command(MoveEast)]`
+/// Each `Position` sets the current line and column, each `Command(cmd)` produces an
+/// `ExecutionPlayerCommand` using the most recent position.
+pub(crate) fn collapse_events(events: Vec<ScriptEvent>) -> Vec<ExecutionPlayerCommand> {
     let mut result = Vec::new();
     let mut current_line: usize = 0;
+    let mut current_column: usize = 0;
 
     for event in events {
         match event {
-            ScriptEvent::Line(line) => {
+            ScriptEvent::Position { line, column } => {
                 current_line = line;
+                current_column = column;
             }
             ScriptEvent::Command(cmd) => {
                 result.push(ExecutionPlayerCommand {
                     command: cmd,
                     line: current_line,
+                    column: current_column,
                 });
             }
         }
@@ -114,3 +187,47 @@ fn collapse_events(events: Vec<ScriptEvent>) -> Vec<ExecutionPlayerCommand> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::script_error::{LimitKind, ScriptErrorKind};
+    use game_core::api::commands::PlayerCommand;
+    use std::time::Duration;
+
+    #[test]
+    fn test_time_limit_in_callbacks() {
+        // the callbacks are called by the builtins, never by a call in the script
+        for code in [
+            "[3, 1, 2].sort((a, b) => a - b);",
+            "[1, 2].forEach(function (x) { let y = x; });",
+        ] {
+            let mut vm = ScriptVM::new(code).unwrap();
+            assert!(vm.run_script().is_ok());
+
+            vm.set_limits(ScriptLimits {
+                time: Duration::ZERO,
+                ..Default::default()
+            });
+            let err = vm.run_script().unwrap_err();
+            assert_eq!(err.kind, ScriptErrorKind::Limit(LimitKind::Time));
+        }
+    }
+
+    #[test]
+    fn test_failed_run_drops_its_commands() {
+        let mut vm = ScriptVM::new("step_up(); step_up(); step_up();").unwrap();
+        vm.set_limits(ScriptLimits {
+            commands: 2,
+            ..Default::default()
+        });
+        let err = vm.run_script().unwrap_err();
+        assert_eq!(err.kind, ScriptErrorKind::Limit(LimitKind::Commands));
+
+        // only the command of the second run, not the two left by the first one
+        vm.set_code("step_down();");
+        let commands = vm.run_script().unwrap();
+        assert_eq!(commands.len(), 1);
+        assert!(matches!(commands[0].command, PlayerCommand::MoveSouth));
+    }
+}